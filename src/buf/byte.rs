@@ -115,6 +115,7 @@ impl Writer for ByteBuf {
 #[cfg(test)]
 mod test {
     use buf::*;
+    use io;
 
     #[test]
     pub fn test_initial_buf_empty() {
@@ -143,4 +144,40 @@ mod test {
 
         assert!(buf.read_to_end().unwrap().as_slice() == b"hello");
     }
+
+    #[test]
+    pub fn test_try_advance_past_capacity() {
+        let mut buf = ByteBuf::new(8);
+
+        buf.try_advance(5).unwrap();
+        assert!(buf.remaining() == 3);
+
+        assert!(buf.try_advance(4).unwrap_err().is_buf_overflow());
+        assert!(buf.remaining() == 3);
+
+        buf.try_advance(3).unwrap();
+        assert!(buf.remaining() == 0);
+    }
+
+    #[test]
+    pub fn test_read_from_write_to() {
+        let (reader, writer) = io::pipe().unwrap();
+        let mut src = ByteBuf::new(8);
+        let mut dst = ByteBuf::new(8);
+
+        src.put_bytes(b"hello").unwrap();
+        src.flip();
+
+        assert_eq!(src.write_to(&writer).unwrap().unwrap(), 5);
+        assert!(!src.has_remaining());
+
+        assert_eq!(dst.read_from(&reader).unwrap().unwrap(), 5);
+        assert!(dst.remaining() == 3);
+
+        // Nothing left to read
+        assert!(dst.read_from(&reader).unwrap().would_block());
+
+        dst.flip();
+        assert!(dst.bytes() == b"hello");
+    }
 }
@@ -1,5 +1,8 @@
 use std::slice::bytes;
 use std::{cmp, io};
+use error::{MioResult, MioError};
+use io::{IoReader, IoWriter, NonBlock};
+use io::NonBlock::Ready;
 
 pub use self::byte::ByteBuf;
 pub use self::ring::{RingBuf, RingBufReader, RingBufWriter};
@@ -21,10 +24,205 @@ pub trait Buf {
     fn has_remaining(&self) -> bool {
         self.remaining() > 0
     }
+
+    /// Writes the bytes available from `bytes()` to `w`, advancing past the
+    /// bytes that were written. Returns the number of bytes written. As with
+    /// any single write, this may be less than `remaining()`.
+    fn write_to(&mut self, w: &IoWriter) -> MioResult<NonBlock<uint>> {
+        let res = w.write_slice(self.bytes());
+
+        match res {
+            Ok(Ready(cnt)) => self.advance(cnt),
+            _ => {}
+        }
+
+        res
+    }
+
+    /// Fills `dst` from the buffer, advancing past the copied bytes. Returns
+    /// false without consuming anything if fewer than `dst.len()` bytes are
+    /// remaining.
+    fn get_bytes(&mut self, dst: &mut [u8]) -> bool {
+        if dst.len() > self.remaining() {
+            return false;
+        }
+
+        let mut curr = 0u;
+
+        while curr < dst.len() {
+            let cnt = {
+                let src = self.bytes();
+                let cnt = cmp::min(src.len(), dst.len() - curr);
+
+                bytes::copy_memory(dst.slice_from_mut(curr), src.slice_to(cnt));
+                cnt
+            };
+
+            curr += cnt;
+            self.advance(cnt);
+        }
+
+        true
+    }
+
+    fn get_u8(&mut self) -> Option<u8> {
+        let mut b = [0u8, ..1];
+
+        if self.get_bytes(&mut b) { Some(b[0]) } else { None }
+    }
+
+    fn get_be_u16(&mut self) -> Option<u16> {
+        let mut b = [0u8, ..2];
+
+        if self.get_bytes(&mut b) { Some(from_be_bytes(&b) as u16) } else { None }
+    }
+
+    fn get_be_u32(&mut self) -> Option<u32> {
+        let mut b = [0u8, ..4];
+
+        if self.get_bytes(&mut b) { Some(from_be_bytes(&b) as u32) } else { None }
+    }
+
+    fn get_be_u64(&mut self) -> Option<u64> {
+        let mut b = [0u8, ..8];
+
+        if self.get_bytes(&mut b) { Some(from_be_bytes(&b)) } else { None }
+    }
+
+    fn get_le_u16(&mut self) -> Option<u16> {
+        let mut b = [0u8, ..2];
+
+        if self.get_bytes(&mut b) { Some(from_le_bytes(&b) as u16) } else { None }
+    }
+
+    fn get_le_u32(&mut self) -> Option<u32> {
+        let mut b = [0u8, ..4];
+
+        if self.get_bytes(&mut b) { Some(from_le_bytes(&b) as u32) } else { None }
+    }
+
+    fn get_le_u64(&mut self) -> Option<u64> {
+        let mut b = [0u8, ..8];
+
+        if self.get_bytes(&mut b) { Some(from_le_bytes(&b)) } else { None }
+    }
 }
 
 pub trait MutBuf : Buf {
     fn mut_bytes<'a>(&'a mut self) -> &'a mut [u8];
+
+    /// Reads from `r` into the space available from `mut_bytes()`,
+    /// advancing past the bytes that were read. Returns the number of bytes
+    /// read.
+    fn read_from(&mut self, r: &IoReader) -> MioResult<NonBlock<uint>> {
+        let res = r.read_slice(self.mut_bytes());
+
+        match res {
+            Ok(Ready(cnt)) => self.advance(cnt),
+            _ => {}
+        }
+
+        res
+    }
+
+    /// Advances the write cursor by `cnt` bytes, returning a `BufOverflow`
+    /// error and leaving the buffer untouched if fewer than `cnt` bytes of
+    /// space remain. Use `advance` when the count is known to be in bounds.
+    fn try_advance(&mut self, cnt: uint) -> MioResult<()> {
+        if cnt > self.remaining() {
+            return Err(MioError::buf_overflow());
+        }
+
+        self.advance(cnt);
+        Ok(())
+    }
+
+    /// Copies all of `src` into the buffer. Returns a `BufOverflow` error
+    /// without writing anything if there is not enough space remaining.
+    fn put_bytes(&mut self, src: &[u8]) -> MioResult<()> {
+        if src.len() > self.remaining() {
+            return Err(MioError::buf_overflow());
+        }
+
+        let mut curr = 0u;
+
+        while curr < src.len() {
+            let cnt = {
+                let dst = self.mut_bytes();
+                let cnt = cmp::min(dst.len(), src.len() - curr);
+
+                bytes::copy_memory(dst, src.slice(curr, curr + cnt));
+                cnt
+            };
+
+            curr += cnt;
+            self.advance(cnt);
+        }
+
+        Ok(())
+    }
+
+    fn put_u8(&mut self, v: u8) -> MioResult<()> {
+        self.put_bytes(&[v])
+    }
+
+    fn put_be_u16(&mut self, v: u16) -> MioResult<()> {
+        let mut b = [0u8, ..2];
+        to_be_bytes(v as u64, &mut b);
+        self.put_bytes(&b)
+    }
+
+    fn put_be_u32(&mut self, v: u32) -> MioResult<()> {
+        let mut b = [0u8, ..4];
+        to_be_bytes(v as u64, &mut b);
+        self.put_bytes(&b)
+    }
+
+    fn put_be_u64(&mut self, v: u64) -> MioResult<()> {
+        let mut b = [0u8, ..8];
+        to_be_bytes(v, &mut b);
+        self.put_bytes(&b)
+    }
+
+    fn put_le_u16(&mut self, v: u16) -> MioResult<()> {
+        let mut b = [0u8, ..2];
+        to_le_bytes(v as u64, &mut b);
+        self.put_bytes(&b)
+    }
+
+    fn put_le_u32(&mut self, v: u32) -> MioResult<()> {
+        let mut b = [0u8, ..4];
+        to_le_bytes(v as u64, &mut b);
+        self.put_bytes(&b)
+    }
+
+    fn put_le_u64(&mut self, v: u64) -> MioResult<()> {
+        let mut b = [0u8, ..8];
+        to_le_bytes(v, &mut b);
+        self.put_bytes(&b)
+    }
+}
+
+fn from_be_bytes(b: &[u8]) -> u64 {
+    b.iter().fold(0u64, |acc, &byte| (acc << 8) | byte as u64)
+}
+
+fn from_le_bytes(b: &[u8]) -> u64 {
+    b.iter().rev().fold(0u64, |acc, &byte| (acc << 8) | byte as u64)
+}
+
+fn to_be_bytes(mut v: u64, dst: &mut [u8]) {
+    for byte in dst.iter_mut().rev() {
+        *byte = v as u8;
+        v >>= 8;
+    }
+}
+
+fn to_le_bytes(mut v: u64, dst: &mut [u8]) {
+    for byte in dst.iter_mut() {
+        *byte = v as u8;
+        v >>= 8;
+    }
 }
 
 pub fn wrap<'a>(bytes: &'a [u8]) -> SliceBuf<'a> {
@@ -3,7 +3,8 @@ use std::num::UnsignedInt;
 use std::io::IoResult;
 use std::raw::Slice as RawSlice;
 use alloc::heap;
-use super::{Buf, MutBuf};
+use error::{MioResult, MioError};
+use super::{Buf, MutBuf, SliceBuf};
 
 /// Buf backed by a continous chunk of memory. Maintains a read cursor and a
 /// write cursor. When reads and writes reach the end of the allocated buffer,
@@ -55,6 +56,12 @@ impl RingBuf {
         self.cap
     }
 
+    /// Discards the buffered bytes, keeping the allocated memory.
+    pub fn clear(&mut self) {
+        self.pos = 0;
+        self.len = 0;
+    }
+
     // Access readable bytes as a Buf
     #[inline]
     pub fn reader<'a>(&'a mut self) -> RingBufReader<'a> {
@@ -91,6 +98,23 @@ impl RingBuf {
         self.len += cnt;
     }
 
+    // Moves the readable bytes to the start of the memory so that they can
+    // be viewed as a single slice
+    fn make_contiguous(&mut self) {
+        let pos = self.pos;
+
+        // Rotates the memory left by `pos`
+        {
+            let bytes = self.as_mut_slice();
+
+            bytes.slice_to_mut(pos).reverse();
+            bytes.slice_from_mut(pos).reverse();
+            bytes.reverse();
+        }
+
+        self.pos = 0;
+    }
+
     fn as_ptr(&self) -> *const u8 {
         self.ptr as *const u8
     }
@@ -157,6 +181,31 @@ pub struct RingBufReader<'a> {
     ring: &'a mut RingBuf
 }
 
+impl<'a> RingBufReader<'a> {
+    /// Splits off the next `n` bytes as a `SliceBuf` viewing them in place,
+    /// and advances past them, e.g. to hand a frame to a decoder once its
+    /// length is known. The reader stays borrowed while the view is alive.
+    ///
+    /// If the bytes wrap around the end of the buffer, the buffered data is
+    /// first moved to the start of the buffer. Returns a `BufUnderflow`
+    /// error without consuming anything if fewer than `n` bytes are
+    /// remaining.
+    pub fn split_to<'b>(&'b mut self, n: uint) -> MioResult<SliceBuf<'b>> {
+        if n > self.remaining() {
+            return Err(MioError::buf_underflow());
+        }
+
+        if self.ring.pos + n > self.ring.cap {
+            self.ring.make_contiguous();
+        }
+
+        let pos = self.ring.pos;
+        self.ring.advance_reader(n);
+
+        Ok(SliceBuf::wrap(self.ring.as_slice().slice(pos, pos + n)))
+    }
+}
+
 impl<'a> Buf for RingBufReader<'a> {
     #[inline]
     fn remaining(&self) -> uint {
@@ -230,7 +279,7 @@ impl<'a> Writer for RingBufWriter<'a> {
 #[cfg(test)]
 mod test {
     use std::io::EndOfFile;
-    use buf::{Buf, RingBuf};
+    use buf::{Buf, MutBuf, RingBuf};
 
     #[test]
     pub fn test_initial_buf_empty() {
@@ -270,4 +319,75 @@ mod test {
         buf.writer().write(b"hello").unwrap();
         assert!(buf.writer().write(b"world").unwrap_err().kind == EndOfFile);
     }
+
+    #[test]
+    pub fn test_length_prefixed_frame() {
+        let mut buf = RingBuf::new(8);
+
+        {
+            let mut writer = buf.writer();
+            writer.put_be_u16(3).unwrap();
+            writer.put_bytes(b"abc").unwrap();
+            writer.put_le_u16(0x0102).unwrap();
+            assert!(writer.put_be_u32(0).unwrap_err().is_buf_overflow());
+        }
+
+        let mut reader = buf.reader();
+        assert_eq!(reader.get_be_u16(), Some(3));
+
+        let mut frame = [0u8, ..3];
+        assert!(reader.get_bytes(&mut frame));
+        assert!(frame.as_slice() == b"abc");
+
+        // Not enough bytes buffered, nothing is consumed
+        assert_eq!(reader.get_be_u32(), None);
+        assert_eq!(reader.remaining(), 2);
+
+        assert_eq!(reader.get_le_u16(), Some(0x0102));
+        assert_eq!(reader.get_u8(), None);
+    }
+
+    #[test]
+    pub fn test_split_to() {
+        let mut buf = RingBuf::new(16);
+
+        buf.writer().put_bytes(b"\x03abcde").unwrap();
+
+        let mut reader = buf.reader();
+        let len = reader.get_u8().unwrap() as uint;
+
+        {
+            let frame = reader.split_to(len).unwrap();
+            assert!(frame.bytes() == b"abc");
+        }
+
+        assert_eq!(reader.remaining(), 2);
+        assert!(reader.split_to(3).unwrap_err().is_buf_underflow());
+
+        // Nothing was consumed by the failed split
+        assert!(reader.split_to(2).unwrap().bytes() == b"de");
+        assert!(!reader.has_remaining());
+    }
+
+    #[test]
+    pub fn test_split_to_wrapped() {
+        let mut buf = RingBuf::new(8);
+
+        buf.writer().put_bytes(b"xxxxxx").unwrap();
+        buf.reader().advance(6);
+
+        // The frame wraps around the end of the buffer
+        buf.writer().put_bytes(b"abcde").unwrap();
+
+        let mut reader = buf.reader();
+        assert!(reader.bytes().len() < 5);
+
+        {
+            let frame = reader.split_to(4).unwrap();
+            assert!(frame.bytes() == b"abcd");
+        }
+
+        assert!(reader.split_to(1).unwrap().bytes() == b"e");
+        assert!(!reader.has_remaining());
+    }
 }
@@ -1,5 +1,6 @@
 use std::cmp;
 use std::io::IoResult;
+use error::{MioResult, MioError};
 use super::{Buf, MutBuf};
 
 pub struct SliceBuf<'a> {
@@ -11,6 +12,20 @@ impl<'a> SliceBuf<'a> {
     pub fn wrap(bytes: &'a [u8]) -> SliceBuf<'a> {
         SliceBuf { bytes: bytes, pos: 0 }
     }
+
+    /// Splits off the next `n` bytes as a separate `SliceBuf`, without
+    /// copying, and advances past them. Returns a `BufUnderflow` error
+    /// without consuming anything if fewer than `n` bytes are remaining.
+    pub fn split_to(&mut self, n: uint) -> MioResult<SliceBuf<'a>> {
+        if n > self.remaining() {
+            return Err(MioError::buf_underflow());
+        }
+
+        let bytes = self.bytes.slice(self.pos, self.pos + n);
+
+        self.pos += n;
+        Ok(SliceBuf::wrap(bytes))
+    }
 }
 
 impl<'a> Buf for SliceBuf<'a> {
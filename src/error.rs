@@ -1,5 +1,5 @@
 use std::io;
-use nix::errno::{SysError, EAGAIN, EADDRINUSE};
+use nix::errno::{SysError, EAGAIN, EADDRINUSE, ECONNRESET, EMFILE, EMSGSIZE, ENFILE};
 
 use self::MioErrorKind::{
     Eof,
@@ -7,6 +7,15 @@ use self::MioErrorKind::{
     BufOverflow,
     WouldBlock,
     AddrInUse,
+    ConnectionReset,
+    AlreadyRegistered,
+    BroadcastDisabled,
+    FdLimit,
+    RegistrationLimit,
+    InvalidInput,
+    Unsupported,
+    MessageTooBig,
+    ResolveFailed,
     EventLoopTerminated,
     OtherError
 };
@@ -24,8 +33,17 @@ pub enum MioErrorKind {
     Eof,                    // End of file or socket closed
     WouldBlock,             // The operation would have blocked
     AddrInUse,              // Inet socket address or domain socket path already in use
+    ConnectionReset,        // The peer aborted the connection, as opposed to closing it (Eof)
     BufUnderflow,           // Buf does not contain enough data to perform read op
     BufOverflow,            // Buf does not contain enough capacity to perform write op
+    AlreadyRegistered,      // The event loop already owns a handle registered with the token
+    BroadcastDisabled,      // Sending to a broadcast address requires SO_BROADCAST
+    FdLimit,                // The process or system file descriptor limit was reached
+    RegistrationLimit,      // The event loop's `max_registrations` was reached
+    InvalidInput,           // An argument was rejected before reaching the OS
+    Unsupported,            // The operation is not supported on this platform
+    MessageTooBig,          // A message or frame exceeds the allowed size
+    ResolveFailed,          // The hostname could not be resolved
     EventLoopTerminated,    // The event loop is not running anymore
     OtherError,             // System error not covered by other kinds
 }
@@ -52,10 +70,69 @@ impl MioError {
         }
     }
 
+    pub fn already_registered() -> MioError {
+        MioError {
+            kind: AlreadyRegistered,
+            sys: None
+        }
+    }
+
+    pub fn broadcast_disabled() -> MioError {
+        MioError {
+            kind: BroadcastDisabled,
+            sys: None
+        }
+    }
+
+    pub fn registration_limit() -> MioError {
+        MioError {
+            kind: RegistrationLimit,
+            sys: None
+        }
+    }
+
+    pub fn invalid_input() -> MioError {
+        MioError {
+            kind: InvalidInput,
+            sys: None
+        }
+    }
+
+    pub fn unsupported() -> MioError {
+        MioError {
+            kind: Unsupported,
+            sys: None
+        }
+    }
+
+    pub fn message_too_big() -> MioError {
+        MioError {
+            kind: MessageTooBig,
+            sys: None
+        }
+    }
+
+    pub fn resolve_failed() -> MioError {
+        MioError {
+            kind: ResolveFailed,
+            sys: None
+        }
+    }
+
+    pub fn event_loop_terminated() -> MioError {
+        MioError {
+            kind: EventLoopTerminated,
+            sys: None
+        }
+    }
+
     pub fn from_sys_error(err: SysError) -> MioError {
         let kind = match err.kind {
             EAGAIN => WouldBlock,
             EADDRINUSE => AddrInUse,
+            ECONNRESET => ConnectionReset,
+            EMFILE | ENFILE => FdLimit,
+            EMSGSIZE => MessageTooBig,
             _ => OtherError
         };
 
@@ -93,6 +170,62 @@ impl MioError {
         }
     }
 
+    pub fn is_addr_in_use(&self) -> bool {
+        match self.kind {
+            AddrInUse => true,
+            _ => false
+        }
+    }
+
+    pub fn is_connection_reset(&self) -> bool {
+        match self.kind {
+            ConnectionReset => true,
+            _ => false
+        }
+    }
+
+    pub fn is_fd_limit(&self) -> bool {
+        match self.kind {
+            FdLimit => true,
+            _ => false
+        }
+    }
+
+    pub fn is_registration_limit(&self) -> bool {
+        match self.kind {
+            RegistrationLimit => true,
+            _ => false
+        }
+    }
+
+    pub fn is_already_registered(&self) -> bool {
+        match self.kind {
+            AlreadyRegistered => true,
+            _ => false
+        }
+    }
+
+    pub fn is_unsupported(&self) -> bool {
+        match self.kind {
+            Unsupported => true,
+            _ => false
+        }
+    }
+
+    pub fn is_message_too_big(&self) -> bool {
+        match self.kind {
+            MessageTooBig => true,
+            _ => false
+        }
+    }
+
+    pub fn is_resolve_failed(&self) -> bool {
+        match self.kind {
+            ResolveFailed => true,
+            _ => false
+        }
+    }
+
     pub fn as_io_error(&self) -> io::IoError {
         use std::io::OtherIoError;
 
@@ -100,6 +233,21 @@ impl MioError {
             Eof | BufUnderflow | BufOverflow => io::standard_error(io::EndOfFile),
             WouldBlock => io::standard_error(io::ResourceUnavailable),
             AddrInUse => io::standard_error(io::PathAlreadyExists),
+            ConnectionReset => io::standard_error(io::ConnectionReset),
+            AlreadyRegistered => io::standard_error(io::InvalidInput),
+            BroadcastDisabled => io::standard_error(io::PermissionDenied),
+            FdLimit => match self.sys {
+                Some(err) => io::IoError::from_errno(err.kind as uint, false),
+                None => io::standard_error(io::ResourceUnavailable)
+            },
+            RegistrationLimit => io::standard_error(io::ResourceUnavailable),
+            InvalidInput => io::standard_error(io::InvalidInput),
+            Unsupported => io::standard_error(io::OtherIoError),
+            MessageTooBig => match self.sys {
+                Some(err) => io::IoError::from_errno(err.kind as uint, false),
+                None => io::standard_error(io::InvalidInput)
+            },
+            ResolveFailed => io::standard_error(io::OtherIoError),
             OtherError => match self.sys {
                 Some(err) => io::IoError::from_errno(err.kind as uint, false),
                 None => io::standard_error(io::OtherIoError)
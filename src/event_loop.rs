@@ -1,58 +1,191 @@
+use std::any::{Any, AnyRefExt, AnyMutRefExt};
+use std::boxed::BoxAny;
+use std::collections::HashMap;
 use std::default::Default;
+use std::mem;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUint, SeqCst};
+use std::task;
 use std::time::duration::Duration;
 use std::uint;
+use time::Timespec;
 use error::{MioResult, MioError};
-use handler::Handler;
-use io::IoHandle;
+use handler::{Handler, HandlerAction};
+use handler::HandlerAction::{Continue, Shutdown};
+use io;
+use io::{IoHandle, NonBlock};
+use io::NonBlock::WouldBlock;
+use net::{Connect, Port, SockAddr};
+use net::tcp::TcpSocket;
 use notify::Notify;
+use os;
 use os::event;
-use poll::{Poll};
-use timer::{Timer, Timeout, TimerResult};
+use poll::{Poll, Tokens};
+use resolver;
+use resolver::Resolver;
+use timer::{Timer, NativeTimer, Timeout, TimerResult};
 use os::token::Token;
+use self::TimerEntry::{User, Idle};
+use self::NotifyError::{Full, Closed};
 
 /// A lightweight event loop.
 ///
-/// TODO:
-/// - Enforce private tokens
+/// The event loop reserves the tokens at the top of the token space for its
+/// own registrations, see `is_reserved_token`. Registering a handle with one
+/// of them fails with an `InvalidInput` error.
 
 #[deriving(Clone, Show)]
 pub struct EventLoopConfig {
     pub io_poll_timeout_ms: uint,
+    // Number of IO events handled per tick
+    pub io_poll_capacity: uint,
+    // When a tick fills the IO events buffer, it is grown up to this size
+    pub io_poll_max_capacity: uint,
+    // Keep calling `readable` for a handle until it is reported drained, see
+    // `EventLoop::drained`
+    pub io_cache_readable: bool,
+    // Count the bytes moved by the `io` helpers and the events dispatched,
+    // see `EventLoop::io_stats`
+    pub io_stats: bool,
+    // Maximum number of bytes read for a handle per tick, 0 for no limit.
+    // See `EventLoop::read_budget`.
+    pub read_budget: uint,
+    // Maximum number of handles registered at once, 0 for no limit. The
+    // registration tables are allocated up front and registering more
+    // handles fails with a `RegistrationLimit` error instead of growing
+    // them. Handles registered by the event loop itself are not counted.
+    pub max_registrations: uint,
 
     // == Notifications ==
+    // Set to 0 to disable notifications, saving the awakener's descriptors
+    // when the event loop is only used from its own thread
     pub notify_capacity: uint,
     pub messages_per_tick: uint,
 
+    // == Resolver ==
+    // Worker threads resolving hostnames, see `EventLoop::resolve`
+    pub resolver_threads: uint,
+    // Maximum number of pending resolutions
+    pub resolver_capacity: uint,
+
     // == Timer ==
     pub timer_tick_ms: u64,
     pub timer_wheel_size: uint,
     pub timer_capacity: uint,
+    // Schedule `timeout` and `timeout_at` with kernel timers (EVFILT_TIMER on
+    // kqueue, timerfd on Linux) instead of the timer wheel, for more precise
+    // timeouts. Falls back to the wheel on other platforms.
+    pub use_native_timers: bool,
 }
 
 impl Default for EventLoopConfig {
     fn default() -> EventLoopConfig {
         EventLoopConfig {
             io_poll_timeout_ms: 1_000,
+            io_poll_capacity: 1_024,
+            io_poll_max_capacity: 1_024,
+            io_cache_readable: false,
+            io_stats: false,
+            read_budget: 0,
+            max_registrations: 0,
             notify_capacity: 1_024,
             messages_per_tick: 64,
+            resolver_threads: 4,
+            resolver_capacity: 1_024,
             timer_tick_ms: 100,
             timer_wheel_size: 1_024,
             timer_capacity: 65_536,
+            use_native_timers: false,
         }
     }
 }
 
 pub struct EventLoop<T, M: Send> {
     run: bool,
+    // Set by `trigger_repoll`, the next poll does not block
+    repoll: bool,
+    ticks: u64,
+    spurious: u64,
+    stats: IoStats,
     poll: Poll,
-    timer: Timer<T>,
-    notify: Notify<M>,
+    timer: Timer<TimerEntry<T>>,
+    // Kernel timers used for user timeouts, see `use_native_timers`
+    native_timer: Option<NativeTimer<T>>,
+    // None when notifications are disabled, see `notify_capacity`
+    notify: Option<Notify<M>>,
+    // Started by the first call to `resolve`
+    resolver: Option<Resolver>,
+    // Shared with the senders, cleared when the event loop stops
+    running: Arc<AtomicBool>,
+    // Senders that saw the event loop running and are queueing a message
+    sending: Arc<AtomicUint>,
     config: EventLoopConfig,
+    // IO handles owned by the event loop, see `register_owned`
+    owned: HashMap<Token, Box<OwnedIo + 'static>>,
+    // Idle timeouts, keyed by the token of the watched handle
+    idle: HashMap<Token, IdleTimeout>,
+    // Handles assumed to still be readable, with the tick `readable` was last
+    // called in. Only used with `io_cache_readable`.
+    readable: HashMap<Token, u64>,
+    // Handles that used up their read budget, with the tick it happened in.
+    // Only used with `read_budget`.
+    over_budget: HashMap<Token, u64>,
+    // Connections started by `connect_host`, keyed by token
+    connecting: HashMap<Token, HostConnect>,
+    // Edge triggered acceptors resumed with `resume_accept`, whose backlog
+    // is checked on the next tick
+    resumed: Vec<Token>,
+}
+
+// Rejects the tokens reserved by the event loop
+fn check_token(token: Token) -> MioResult<()> {
+    if token >= RESERVED {
+        debug!("token is reserved by the event loop; token={}", token);
+        return Err(MioError::invalid_input());
+    }
+
+    Ok(())
+}
+
+/// IO counters of an event loop, see `EventLoop::io_stats`
+#[deriving(Clone, Show, Default, PartialEq)]
+pub struct IoStats {
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    // Number of IO events handed to the handler
+    pub events_dispatched: u64,
+}
+
+// A connection started by `connect_host`
+struct HostConnect {
+    sock: TcpSocket,
+    // Addresses left to try, in reverse order
+    addrs: Vec<SockAddr>
+}
+
+// Timer entries are either scheduled by the user or track idle handles
+enum TimerEntry<T> {
+    User(T),
+    Idle(Token),
+}
+
+struct IdleTimeout {
+    delay: Duration,
+    timeout: Timeout,
 }
 
 // Token used to represent notifications
 const NOTIFY: Token = Token(uint::MAX);
 
+// Token used to represent the expiration of native timers
+const TIMER: Token = Token(uint::MAX - 1);
+
+// Token used to represent completed resolutions
+const RESOLVER: Token = Token(uint::MAX - 2);
+
+// Lowest token reserved by the event loop
+const RESERVED: Token = Token(uint::MAX - 2);
+
 impl<T, M: Send> EventLoop<T, M> {
     /// Initializes a new event loop. The event loop will not be running yet.
     pub fn new() -> MioResult<EventLoop<T, M>> {
@@ -61,7 +194,11 @@ impl<T, M: Send> EventLoop<T, M> {
 
     pub fn configured(config: EventLoopConfig) -> MioResult<EventLoop<T, M>> {
         // Create the IO poller
-        let mut poll = try!(Poll::new());
+        let mut poll = try!(Poll::with_registrations(
+            config.io_poll_capacity,
+            config.io_poll_max_capacity,
+            // Room for the notify queue, native timer and resolver handles
+            config.max_registrations + 3));
 
         // Create the timer
         let mut timer = Timer::new(
@@ -69,88 +206,622 @@ impl<T, M: Send> EventLoop<T, M> {
             config.timer_wheel_size,
             config.timer_capacity);
 
-        // Create cross thread notification queue
-        let notify = try!(Notify::with_capacity(config.notify_capacity));
+        let notify = if config.notify_capacity > 0 {
+            // Create cross thread notification queue
+            let notify = try!(Notify::with_capacity(config.notify_capacity));
 
-        // Register the notification wakeup FD with the IO poller
-        try!(poll.register(&notify, NOTIFY, event::READABLE | event::WRITABLE, event::EDGE));
+            // Register the notification wakeup FD with the IO poller
+            try!(poll.register(&notify, NOTIFY, event::READABLE | event::WRITABLE, event::EDGE));
+            Some(notify)
+        } else {
+            debug!("notifications disabled");
+            None
+        };
 
         // Set the timer's starting time reference point
         timer.setup();
 
+        let native_timer = if config.use_native_timers {
+            match NativeTimer::new(config.timer_capacity) {
+                Ok(native) => {
+                    try!(poll.register(&native, TIMER, event::READABLE, event::LEVEL));
+                    Some(native)
+                }
+                Err(e) => {
+                    if !e.is_unsupported() {
+                        return Err(e);
+                    }
+
+                    debug!("native timers not supported, using the timer wheel");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
         Ok(EventLoop {
             run: true,
+            repoll: false,
+            ticks: 0,
+            spurious: 0,
+            stats: Default::default(),
             poll: poll,
             timer: timer,
+            native_timer: native_timer,
             notify: notify,
+            resolver: None,
+            running: Arc::new(AtomicBool::new(true)),
+            sending: Arc::new(AtomicUint::new(0)),
+            owned: HashMap::with_capacity(config.max_registrations),
+            idle: HashMap::with_capacity(config.max_registrations),
+            readable: HashMap::with_capacity(config.max_registrations),
+            over_budget: HashMap::with_capacity(config.max_registrations),
+            connecting: HashMap::new(),
+            resumed: Vec::new(),
             config: config,
         })
     }
 
     /// Returns a sender that allows sending messages to the event loop in a
     /// thread-safe way, waking up the event loop if needed.
-    pub fn channel(&self) -> EventLoopSender<M> {
-        EventLoopSender::new(self.notify.clone())
+    ///
+    /// `M` is usually an enum describing the requests other threads can make
+    /// of the event loop. Each message is moved to the handler's `notify`.
+    ///
+    /// Fails with an `Unsupported` error if notifications were disabled with
+    /// a `notify_capacity` of 0.
+    pub fn channel(&self) -> MioResult<EventLoopSender<M>> {
+        match self.notify {
+            Some(ref notify) => Ok(EventLoopSender::new(notify.clone(), self.running.clone(), self.sending.clone())),
+            None => Err(MioError::unsupported())
+        }
+    }
+
+    /// Queues a message for the handler's `notify`, as `channel().send(msg)`
+    /// would. Useful for deferring work to a later tick from within a
+    /// handler callback. If the queue is full, or notifications are
+    /// disabled, the message is handed back.
+    pub fn send_notify(&self, msg: M) -> Result<(), M> {
+        match self.notify {
+            Some(ref notify) => notify.notify(msg),
+            None => Err(msg)
+        }
+    }
+
+    /// Resolves `host` on a worker thread, as `getaddrinfo` blocks, and calls
+    /// the handler's `resolved` with `token` and the addresses, all using
+    /// `port`, once done. The worker threads are started by the first call,
+    /// see `EventLoopConfig::resolver_threads`.
+    ///
+    /// Fails with a `BufOverflow` error if `resolver_capacity` resolutions
+    /// are already pending.
+    pub fn resolve(&mut self, host: &str, port: Port, token: Token) -> MioResult<()> {
+        if self.resolver.is_none() {
+            let resolver = try!(Resolver::new(
+                self.config.resolver_threads,
+                self.config.resolver_capacity));
+
+            try!(self.poll.register(&resolver, RESOLVER, event::READABLE | event::WRITABLE, event::EDGE));
+            self.resolver = Some(resolver);
+        }
+
+        self.resolver.as_mut().unwrap().resolve(host, port, token)
+    }
+
+    /// Resolves `host` and connects to the first address, falling through
+    /// to the next one if connecting fails. The handler's `connected` is
+    /// called with `token` once a connection is established, or with the
+    /// last error once all addresses failed. The socket is registered with
+    /// `token` while connecting, so `token` must not be in use.
+    ///
+    /// The resolution blocks the event loop, use `resolve` and connect to the
+    /// addresses directly to avoid it. Resolution errors, and errors for all
+    /// addresses failing right away, are returned rather than reported to
+    /// the handler.
+    pub fn connect_host(&mut self, host: &str, port: Port, token: Token) -> MioResult<()> {
+        try!(check_token(token));
+
+        let mut addrs = try!(resolver::lookup(host, port));
+
+        // Addresses are tried in order, popping from the back
+        addrs.reverse();
+
+        self.connect_next(token, addrs)
     }
 
     /// After the requested time interval, the handler's `timeout` function
     /// will be called with the supplied token.
     pub fn timeout(&mut self, token: T, delay: Duration) -> TimerResult<Timeout> {
-        self.timer.timeout(token, delay)
+        match self.native_timer {
+            Some(ref mut native) => native.timeout(token, delay),
+            None => self.timer.timeout(User(token), delay)
+        }
+    }
+
+    /// Schedules a timeout against an absolute deadline. The deadline is
+    /// expressed on the event loop's monotonic clock, see `now`. Deadlines
+    /// that have already passed trigger on the next timer tick.
+    pub fn timeout_at(&mut self, token: T, deadline: Timespec) -> TimerResult<Timeout> {
+        if self.native_timer.is_some() {
+            let delay = deadline - self.timer.now_ts();
+            return self.timeout(token, delay);
+        }
+
+        self.timer.timeout_at(User(token), deadline)
+    }
+
+    /// Returns the current time of the monotonic clock used to schedule
+    /// timeouts. This is not wall clock time.
+    pub fn now(&self) -> Timespec {
+        self.timer.now_ts()
     }
 
     /// If the supplied timeout has not been triggered, cancel it such that it
     /// will not be triggered in the future.
     pub fn clear_timeout(&mut self, timeout: Timeout) -> bool {
-        self.timer.clear(timeout)
+        match self.native_timer {
+            Some(ref mut native) => native.clear(timeout),
+            None => self.timer.clear(timeout)
+        }
+    }
+
+    /// Returns the number of timeouts scheduled with `timeout` or
+    /// `timeout_at` that neither fired nor were cleared yet. Idle timeouts
+    /// are not included. A count that keeps growing usually means timeouts
+    /// are scheduled but never cleared.
+    pub fn pending_timeouts(&self) -> uint {
+        match self.native_timer {
+            Some(ref native) => native.count(),
+            None => self.timer.count() - self.idle.len()
+        }
+    }
+
+    /// Returns the number of messages sent through `channel` that were not
+    /// dispatched to the handler yet, see `EventLoopSender::pending`.
+    pub fn notify_len(&self) -> uint {
+        match self.notify {
+            Some(ref notify) => notify.len(),
+            None => 0
+        }
+    }
+
+    /// Arms an idle timeout for the handle registered with `token`. The
+    /// timeout is pushed back by `delay` whenever the handle has IO events,
+    /// and once `delay` passes without any, the handler's `idle_timeout` is
+    /// called. Setting the idle timeout again replaces the previous one.
+    ///
+    /// Idle timeouts are not cleared when the handle is deregistered, call
+    /// `clear_idle_timeout` when closing a connection early.
+    pub fn set_idle_timeout(&mut self, token: Token, delay: Duration) -> TimerResult<()> {
+        self.clear_idle_timeout(token);
+
+        let timeout = try!(self.timer.timeout(Idle(token), delay));
+
+        self.idle.insert(token, IdleTimeout {
+            delay: delay,
+            timeout: timeout
+        });
+
+        Ok(())
+    }
+
+    /// Clears the idle timeout for `token`. Returns false if none was set.
+    pub fn clear_idle_timeout(&mut self, token: Token) -> bool {
+        match self.idle.remove(&token) {
+            Some(idle) => {
+                self.timer.clear(idle.timeout);
+                true
+            }
+            None => false
+        }
+    }
+
+    /// Returns the number of iterations (poll and dispatch cycles) the event
+    /// loop has executed. A rapidly growing count without any IO progress is a
+    /// sign of a busy loop.
+    pub fn tick_count(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Returns the timer's current tick. Timeouts are scheduled in units of
+    /// `EventLoopConfig::timer_tick_ms`, so comparing this with `tick_count`
+    /// helps correlate timeout latency with loop iterations.
+    pub fn timer_tick(&self) -> u64 {
+        self.timer.current_tick()
+    }
+
+    /// Records that a readiness notification for `token` turned out to be
+    /// spurious, i.e. the resulting read or write returned `WouldBlock`. This
+    /// is legal, especially with edge triggering, but a high rate usually
+    /// points at redundant registrations or reads racing the event loop.
+    pub fn spurious_wakeup(&mut self, token: Token) {
+        debug!("spurious wakeup; token={}", token);
+        self.spurious += 1;
+    }
+
+    /// Returns the number of spurious wakeups reported with `spurious_wakeup`.
+    pub fn spurious_wakeups(&self) -> u64 {
+        self.spurious
+    }
+
+    /// Returns the number of bytes a handler should read at most from a
+    /// handle per `readable` call, 0 if there is no limit. When a handler
+    /// reads that many bytes, the handle may have more data pending, so
+    /// `readable` is called again on the next tick, giving other handles a
+    /// chance to be served first. Reads are counted as for `io_stats`.
+    pub fn read_budget(&self) -> uint {
+        self.config.read_budget
+    }
+
+    /// Returns the IO counters accumulated so far. Only maintained with
+    /// `EventLoopConfig::io_stats`, otherwise all counters stay at zero.
+    ///
+    /// Bytes are counted when moved with the `read`, `write`, `recv`, `send`
+    /// and `send_file` helpers (and the `IoReader` / `IoWriter`
+    /// implementations based on them) from within handler callbacks.
+    pub fn io_stats(&self) -> IoStats {
+        self.stats.clone()
+    }
+
+    /// Reports that the handle registered with `token` has no more data to
+    /// read, i.e. a read returned `WouldBlock`. With
+    /// `EventLoopConfig::io_cache_readable` set, the event loop remembers
+    /// handles that were reported readable and dispatches a readable event
+    /// for them, through `Handler::ready` as any other, on every iteration
+    /// until this is called, without waiting for the selector. This lets
+    /// handlers read in bounded chunks under edge triggering without losing
+    /// the remaining data.
+    ///
+    /// Must also be called when a handle is closed while still readable,
+    /// deregistering it is enough otherwise.
+    pub fn drained(&mut self, token: Token) {
+        self.readable.remove(&token);
+    }
+
+    /// Makes the next iteration poll for IO events without blocking, so that
+    /// work handed off during this iteration, e.g. data moved to another
+    /// connection's buffer, is picked up right away instead of after the
+    /// poll timeout. Only affects the next iteration.
+    pub fn trigger_repoll(&mut self) {
+        self.repoll = true;
     }
 
     /// Tells the event loop to exit after it is done handling all events in the
     /// current iteration.
+    /// Messages still queued on the notify channel are then handed to the
+    /// handler's `notify_drained`.
     pub fn shutdown(&mut self) {
         self.run = false;
+        self.running.store(false, SeqCst);
     }
 
-    /// Registers an IO handle with the event loop.
+    /// Returns false once `shutdown` was called or `run` returned, until the
+    /// event loop is run again. A new event loop counts as running, so that
+    /// messages can be queued before `run` is called, and so does one that
+    /// `run_ticks` returned from without a shutdown. Messages sent while it
+    /// is not running are rejected, see `EventLoopSender::send`.
+    pub fn is_running(&self) -> bool {
+        self.running.load(SeqCst)
+    }
+
+    /// Token of the awakener used to deliver notifications, see `channel`.
+    pub fn awakener_token(&self) -> Token {
+        NOTIFY
+    }
+
+    /// Token of the native timers, see `EventLoopConfig::use_native_timers`.
+    pub fn timer_token(&self) -> Token {
+        TIMER
+    }
+
+    /// Returns true for the tokens reserved by the event loop, which are all
+    /// tokens from `uint::MAX - 2` up. Tokens of registered handles must be
+    /// below that.
+    pub fn is_reserved_token(&self, token: Token) -> bool {
+        token >= RESERVED
+    }
+
+    /// Iterates over the tokens of the handles currently registered with the
+    /// event loop, in no particular order. Handles registered with
+    /// `register_raw` are not included.
+    pub fn registered_tokens<'a>(&'a self) -> RegisteredTokens<'a> {
+        RegisteredTokens { tokens: self.poll.tokens() }
+    }
+
+    /// Registers an IO handle with the event loop. Registering a handle that
+    /// is already registered replaces its registration, as `reregister`
+    /// does.
+    ///
+    /// The handle is registered for readable and error interest, level
+    /// triggered, which is what an acceptor needs: the handler's `readable`
+    /// is called for pending connections and `error` if the socket fails.
+    ///
+    /// Returns a `RegistrationLimit` error if `max_registrations` handles are
+    /// already registered, the same goes for the other registration methods.
     pub fn register<H: IoHandle>(&mut self, io: &H, token: Token) -> MioResult<()> {
-        self.poll.register(io, token, event::READABLE, event::LEVEL)
+        try!(check_token(token));
+        try!(self.check_registrations(io));
+        self.poll.register(io, token, event::READABLE | event::ERROR, event::LEVEL)
     }
 
     /// Registers an IO handle with the event loop.
     pub fn register_opt<H: IoHandle>(&mut self, io: &H, token: Token, interest: event::Interest, opt: event::PollOpt) -> MioResult<()> {
+        try!(check_token(token));
+        try!(self.check_registrations(io));
         self.poll.register(io, token, interest, opt)
     }
 
+    /// Registers an IO handle with the event loop, attaching `data` to the
+    /// registration. Events for the handle are then dispatched to
+    /// `Handler::ready_with_data` along with `data`, sparing a lookup by
+    /// token for small per-handle state. The data is kept by the event loop
+    /// in a side table, not by the selector, and is limited to a `uint`:
+    /// store an index, or a pointer to larger state the caller owns. It is
+    /// kept when the handle is reregistered and dropped on `deregister`.
+    pub fn register_with_data<H: IoHandle>(&mut self, io: &H, token: Token, interest: event::Interest, opt: event::PollOpt, data: uint) -> MioResult<()> {
+        try!(check_token(token));
+        try!(self.check_registrations(io));
+        self.poll.register_with_data(io, token, interest, opt, data)
+    }
+
     /// Re-Registers an IO handle with the event loop.
     pub fn reregister<H: IoHandle>(&mut self, io: &H, token: Token, interest: event::Interest, opt: event::PollOpt) -> MioResult<()> {
+        try!(check_token(token));
         self.poll.reregister(io, token, interest, opt)
     }
 
+    /// Returns the interest the handle registered with `token` was last
+    /// registered or reregistered with, or `None` if no handle is registered
+    /// with `token`.
+    pub fn interest(&self, token: Token) -> Option<event::Interest> {
+        self.poll.interest(token)
+    }
+
+    /// Takes the result of a non-blocking operation on `io` and, if it would
+    /// have blocked, (re)registers `io` with `token` for `interest` so that
+    /// the handler is called once the operation can be retried. The result
+    /// is returned unchanged, e.g.:
+    ///
+    /// ```ignore
+    /// let res = sock.write(&mut buf);
+    ///
+    /// match try!(event_loop.retry_on_block(&sock, token, event::WRITABLE, res)) {
+    ///     Ready(cnt) => ...,
+    ///     // `writable` is called when the socket can be written to again
+    ///     WouldBlock => return Ok(())
+    /// }
+    /// ```
+    ///
+    /// A registered handle keeps its poll options, an unregistered one is
+    /// registered edge triggered.
+    pub fn retry_on_block<H: IoHandle, R>(&mut self, io: &H, token: Token, interest: event::Interest, res: MioResult<NonBlock<R>>) -> MioResult<NonBlock<R>> {
+        match res {
+            Ok(WouldBlock) => {}
+            res => return res
+        }
+
+        match self.poll.opts(io) {
+            Some(opts) => try!(self.reregister(io, token, interest, opts)),
+            None => try!(self.register_opt(io, token, interest, event::EDGE))
+        }
+
+        Ok(WouldBlock)
+    }
+
+    /// Stops watching the acceptor `io`, registered with `token`, for
+    /// pending connections, e.g. while the connection slab is full. New
+    /// connections wait in the backlog, up to its size, instead of being
+    /// accepted and closed right away. The other interests and the poll
+    /// options are kept. Resume with `resume_accept`:
+    ///
+    /// ```ignore
+    /// fn readable(&mut self, event_loop: &mut EventLoop<..>, token: Token, _: ReadHint) {
+    ///     if token == SERVER {
+    ///         while self.conns.has_remaining() {
+    ///             match self.server.accept().unwrap() { ... }
+    ///         }
+    ///
+    ///         if !self.conns.has_remaining() {
+    ///             event_loop.pause_accept(&self.server, SERVER).unwrap();
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// // Once a connection is closed
+    /// event_loop.resume_accept(&self.server, SERVER).unwrap();
+    /// ```
+    ///
+    /// Fails with an `InvalidInput` error if `io` is not registered with
+    /// `token`.
+    pub fn pause_accept<H: IoHandle>(&mut self, io: &H, token: Token) -> MioResult<()> {
+        let (interest, opts) = try!(self.accept_registration(io, token));
+
+        // Readiness reported before pausing does not carry over
+        self.forget_token(token);
+
+        self.reregister(io, token, interest - event::READABLE, opts)
+    }
+
+    /// Watches the acceptor paused with `pause_accept` for pending
+    /// connections again. Connections that queued up in the meantime are
+    /// not reported by an edge triggered selector until a new one arrives,
+    /// so for edge triggered acceptors a readable event is dispatched on the
+    /// next tick regardless.
+    pub fn resume_accept<H: IoHandle>(&mut self, io: &H, token: Token) -> MioResult<()> {
+        let (interest, opts) = try!(self.accept_registration(io, token));
+
+        try!(self.reregister(io, token, interest | event::READABLE, opts));
+
+        if opts.contains(event::EDGE) && !self.resumed.contains(&token) {
+            self.resumed.push(token);
+        }
+
+        Ok(())
+    }
+
+    // The interest and options of the acceptor `io` registered with `token`
+    fn accept_registration<H: IoHandle>(&self, io: &H, token: Token) -> MioResult<(event::Interest, event::PollOpt)> {
+        match (self.poll.interest(token), self.poll.opts(io)) {
+            (Some(interest), Some(opts)) => Ok((interest, opts)),
+            _ => Err(MioError::invalid_input())
+        }
+    }
+
+    /// Registers a raw kqueue filter, for event sources that have no
+    /// `Interest` equivalent: `EVFILT_VNODE` with `NOTE_*` flags to watch a
+    /// file, `EVFILT_TIMER` with the period in ms in `data`, ... `ident` is
+    /// interpreted by the filter. Events for raw filters are reported as
+    /// readable to `Handler::raw_ready`, which can access `IoEvent::raw_data`
+    /// and `IoEvent::raw_fflags`.
+    ///
+    /// Raw registrations are not tracked by the event loop, remove them with
+    /// `deregister_raw`.
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    pub fn register_raw(&mut self, ident: uint, token: Token, filter: event::EventFilter, fflags: event::FilterFlag, data: i64, opt: event::PollOpt) -> MioResult<()> {
+        try!(check_token(token));
+        self.poll.register_raw(ident, token, filter, fflags, data, opt)
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    pub fn deregister_raw(&mut self, ident: uint, filter: event::EventFilter) -> MioResult<()> {
+        self.poll.deregister_raw(ident, filter)
+    }
+
+    /// Registers an IO handle with raw epoll event flags (such as
+    /// `EPOLLPRI`), for conditions that have no `Interest` equivalent. The
+    /// reported epoll flags are available from `IoEvent::raw_data` in
+    /// `Handler::raw_ready`. The handle is removed with `deregister` as usual.
+    #[cfg(target_os = "linux")]
+    pub fn register_raw<H: IoHandle>(&mut self, io: &H, token: Token, events: u32) -> MioResult<()> {
+        try!(check_token(token));
+        self.poll.register_raw(io, token, events)
+    }
+
+    /// Registers an IO handle and transfers its ownership to the event loop.
+    /// The handle can be accessed by token with `connection` and
+    /// `connection_mut`, and is deregistered and closed by `remove_owned`, or
+    /// when the event loop is dropped.
+    ///
+    /// Unlike `register`, fails with an `AlreadyRegistered` error if the
+    /// event loop already owns a handle registered with `token`, as replacing
+    /// it would close that handle behind the caller's back.
+    pub fn register_owned<H: IoHandle + 'static>(&mut self, io: H, token: Token, interest: event::Interest, opt: event::PollOpt) -> MioResult<()> {
+        try!(check_token(token));
+
+        if self.owned.contains_key(&token) {
+            return Err(MioError::already_registered());
+        }
+
+        try!(self.check_registrations(&io));
+
+        try!(self.poll.register(&io, token, interest, opt));
+        self.owned.insert(token, box io as Box<OwnedIo>);
+
+        Ok(())
+    }
+
+    /// Returns the owned handle registered with `token`, or `None` if there is
+    /// no such handle or it is not of type `H`.
+    pub fn connection<H: IoHandle + 'static>(&self, token: Token) -> Option<&H> {
+        self.owned.get(&token).and_then(|io| io.as_any().downcast_ref::<H>())
+    }
+
+    /// Mutable variant of `connection`.
+    pub fn connection_mut<H: IoHandle + 'static>(&mut self, token: Token) -> Option<&mut H> {
+        self.owned.get_mut(&token).and_then(|io| io.as_any_mut().downcast_mut::<H>())
+    }
+
+    /// Deregisters and closes the owned handle registered with `token`.
+    /// Returns false if no handle is owned for the token.
+    pub fn remove_owned(&mut self, token: Token) -> MioResult<bool> {
+        match self.owned.remove(&token) {
+            Some(io) => {
+                try!(self.poll.deregister(io.desc()));
+                self.forget_token(token);
+                Ok(true)
+            }
+            None => Ok(false)
+        }
+    }
+
+    /// Deregisters the owned handle registered with `token` and returns
+    /// ownership of it to the caller. Returns `None`, leaving the handle
+    /// registered, if there is no such handle or it is not of type `H`.
+    pub fn take_owned<H: IoHandle + 'static>(&mut self, token: Token) -> MioResult<Option<H>> {
+        if self.connection::<H>(token).is_none() {
+            return Ok(None);
+        }
+
+        let io = self.owned.remove(&token).unwrap();
+        try!(self.poll.deregister(io.desc()));
+        self.forget_token(token);
+
+        match io.into_any().downcast::<H>() {
+            Ok(io) => Ok(Some(*io)),
+            Err(_) => panic!("[BUG] owned handle changed type")
+        }
+    }
+
     /// Keep spinning the event loop indefinitely, and notify the handler whenever
     /// any of the registered handles are ready.
-    pub fn run<H: Handler<T, M>>(&mut self, mut handler: H) -> EventLoopResult<H> {
+    pub fn run<H: Handler<Timeout=T, Message=M>>(&mut self, mut handler: H) -> EventLoopResult<H> {
         self.run = true;
+        self.running.store(true, SeqCst);
 
         while self.run {
             // Execute ticks as long as the event loop is running
             match self.tick(&mut handler) {
-                Err(e) => return Err(EventLoopError::new(handler, e)),
+                Err(e) => {
+                    self.running.store(false, SeqCst);
+                    return Err(EventLoopError::new(handler, e));
+                }
                 _ => {}
             }
         }
 
+        self.close_senders();
+        self.notify_drain(&mut handler);
+
         Ok(handler)
     }
 
+    // Rejects further messages, then waits for the senders that saw the
+    // event loop running to queue theirs, so that they are drained
+    fn close_senders(&self) {
+        self.running.store(false, SeqCst);
+
+        while self.sending.load(SeqCst) > 0 {
+            task::deschedule();
+        }
+    }
+
     /// Deregisters an IO handle with the event loop.
     pub fn deregister<H: IoHandle>(&mut self, io: &H) -> MioResult<()> {
-        self.poll.deregister(io)
+        let token = self.poll.token(io);
+
+        try!(self.poll.deregister(io));
+
+        match token {
+            Some(token) => self.forget_token(token),
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    // Drops the readiness carried over for `token`, so that `readable` is
+    // not called for a handle that is gone
+    fn forget_token(&mut self, token: Token) {
+        self.readable.remove(&token);
+        self.over_budget.remove(&token);
+        self.resumed.retain(|&tok| tok != token);
     }
 
     /// Spin the event loop once, with a timeout of one second, and notify the
     /// handler if any of the registered handles become ready during that
     /// time.
-    pub fn run_once<H: Handler<T, M>>(&mut self, mut handler: H) -> EventLoopResult<H> {
+    pub fn run_once<H: Handler<Timeout=T, Message=M>>(&mut self, mut handler: H) -> EventLoopResult<H> {
         // Execute a single tick
         match self.tick(&mut handler) {
             Err(e) => return Err(EventLoopError::new(handler, e)),
@@ -160,18 +831,55 @@ impl<T, M: Send> EventLoop<T, M> {
         Ok(handler)
     }
 
+    /// Spins the event loop `n` times, as with `run_once`, whether or not any
+    /// events were dispatched, then returns the handler. Each tick blocks for
+    /// up to `EventLoopConfig::io_poll_timeout_ms` when nothing is ready, so
+    /// tests usually lower it. Returns early if the handler calls `shutdown`.
+    pub fn run_ticks<H: Handler<Timeout=T, Message=M>>(&mut self, mut handler: H, n: uint) -> EventLoopResult<H> {
+        self.run = true;
+        self.running.store(true, SeqCst);
+
+        for _ in range(0, n) {
+            if !self.run {
+                break;
+            }
+
+            match self.tick(&mut handler) {
+                Err(e) => {
+                    self.running.store(false, SeqCst);
+                    return Err(EventLoopError::new(handler, e));
+                }
+                _ => {}
+            }
+        }
+
+        // More ticks may follow, senders are only closed by a shutdown
+        if !self.run {
+            self.close_senders();
+            self.notify_drain(&mut handler);
+        }
+
+        Ok(handler)
+    }
+
     // Executes a single run of the event loop loop
-    fn tick<H: Handler<T, M>>(&mut self, handler: &mut H) -> MioResult<()> {
+    fn tick<H: Handler<Timeout=T, Message=M>>(&mut self, handler: &mut H) -> MioResult<()> {
         let mut messages;
+        let mut resolved;
         let mut pending;
 
-        debug!("event loop tick");
+        self.ticks += 1;
+
+        debug!("event loop tick; ticks={}", self.ticks);
 
         // Check the notify channel for any pending messages. If there are any,
         // avoid blocking when polling for IO events. Messages will be
         // processed after IO events.
-        messages = self.notify.check(self.config.messages_per_tick, true);
-        pending = messages > 0;
+        messages = self.notify_check(self.config.messages_per_tick, true);
+        resolved = self.resolver_check(true);
+        pending = messages > 0 || resolved > 0 || !self.readable.is_empty() || !self.over_budget.is_empty() || !self.resumed.is_empty() || self.repoll;
+
+        self.repoll = false;
 
         // Check the registered IO handles for any new events. Each poll
         // is for one second, so a shutdown request can last as long as
@@ -182,22 +890,79 @@ impl<T, M: Send> EventLoop<T, M> {
             // Indicate that the sleep period is over, also grab any additional
             // messages
             let remaining = self.config.messages_per_tick - messages;
-            messages += self.notify.check(remaining, false);
+            messages += self.notify_check(remaining, false);
+            resolved += self.resolver_check(false);
+        }
+
+        let tracked = self.io_stats_begin();
+        let mut action = self.io_process(handler, events);
+
+        if action == Continue {
+            action = self.io_cached(handler);
+        }
+
+        if action == Continue {
+            action = self.io_over_budget(handler);
+        }
+
+        if action == Continue {
+            action = self.io_resumed(handler);
         }
 
-        self.io_process(handler, events);
         self.notify(handler, messages);
+        self.resolved(handler, resolved);
+
+        if action == Shutdown {
+            debug!("handler requested shutdown");
+            self.io_stats_end(tracked);
+            self.run = false;
+            return Ok(());
+        }
+
         self.timer_process(handler);
+        self.io_stats_end(tracked);
 
         Ok(())
     }
 
+    // Starts counting the bytes moved by the handler, returning the totals
+    // to compute the tick's share from
+    #[inline]
+    fn io_stats_begin(&self) -> Option<(u64, u64)> {
+        if !self.config.io_stats {
+            return None;
+        }
+
+        io::track_bytes(true);
+        Some(io::tracked_bytes())
+    }
+
+    #[inline]
+    fn io_stats_end(&mut self, tracked: Option<(u64, u64)>) {
+        match tracked {
+            Some((read, written)) => {
+                let (read_now, written_now) = io::tracked_bytes();
+                io::track_bytes(false);
+
+                self.stats.bytes_read += read_now - read;
+                self.stats.bytes_written += written_now - written;
+            }
+            None => {}
+        }
+    }
+
     #[inline]
     fn io_poll(&mut self, immediate: bool) -> MioResult<uint> {
         if immediate {
             self.poll.poll(0)
         } else {
-            let mut sleep = self.timer.next_tick_in_ms() as uint;
+            // Block until the timer tick the next timeout can fire on, or
+            // for the configured timeout if there are no pending timeouts
+            let mut sleep = if self.timer.count() > 0 {
+                self.timer.next_tick_in_ms() as uint
+            } else {
+                self.config.io_poll_timeout_ms
+            };
 
             if sleep > self.config.io_poll_timeout_ms {
                 sleep = self.config.io_poll_timeout_ms;
@@ -208,7 +973,7 @@ impl<T, M: Send> EventLoop<T, M> {
     }
 
     // Process IO events that have been previously polled
-    fn io_process<H: Handler<T, M>>(&mut self, handler: &mut H, cnt: uint) {
+    fn io_process<H: Handler<Timeout=T, Message=M>>(&mut self, handler: &mut H, cnt: uint) -> HandlerAction {
         let mut i = 0u;
 
         // Iterate over the notifications. Each event provides the token
@@ -221,137 +986,1359 @@ impl<T, M: Send> EventLoop<T, M> {
             debug!("event={}", evt);
 
             match evt.token() {
-                NOTIFY => self.notify.cleanup(),
-                _      => self.io_event(handler, evt)
+                NOTIFY => {
+                    // Only registered when notifications are enabled
+                    match self.notify {
+                        Some(ref notify) => notify.cleanup(),
+                        None => {}
+                    }
+                }
+                TIMER  => self.native_timer_process(handler),
+                RESOLVER => {
+                    // Results are delivered along with the messages
+                    match self.resolver {
+                        Some(ref resolver) => resolver.cleanup(),
+                        None => {}
+                    }
+                }
+                _      => {
+                    if self.io_event(handler, evt) == Shutdown {
+                        return Shutdown;
+                    }
+                }
             }
 
             i += 1;
         }
+
+        Continue
     }
 
-    fn io_event<H: Handler<T, M>>(&mut self, handler: &mut H, evt: event::IoEvent) {
+    fn io_event<H: Handler<Timeout=T, Message=M>>(&mut self, handler: &mut H, evt: event::IoEvent) -> HandlerAction {
         let tok = evt.token();
 
-        if evt.is_readable() {
-            handler.readable(self, tok, evt.read_hint());
+        if self.connecting.contains_key(&tok) {
+            self.connect_event(handler, tok);
+            return Continue;
         }
 
-        if evt.is_writable() {
-            handler.writable(self, tok);
+        self.idle_rearm(tok);
+
+        if self.config.io_cache_readable && evt.is_readable() {
+            self.readable.insert(tok, self.ticks);
         }
 
-        if evt.is_error() {
-            println!(" + ERROR");
+        if self.config.io_stats {
+            self.stats.events_dispatched += 1;
         }
-    }
 
-    fn notify<H: Handler<T, M>>(&mut self, handler: &mut H, mut cnt: uint) {
-        while cnt > 0 {
-            let msg = self.notify.poll()
-                .expect("[BUG] at this point there should always be a message");
+        // Raw registrations are not tracked
+        if !self.poll.is_live(tok) {
+            return handler.raw_ready(self, tok, evt);
+        }
 
-            handler.notify(self, msg);
-            cnt -= 1;
+        if self.config.read_budget == 0 || !evt.is_readable() {
+            return self.io_dispatch(handler, tok, evt.kind());
         }
-    }
 
-    fn timer_process<H: Handler<T, M>>(&mut self, handler: &mut H) {
-        let now = self.timer.now();
+        // The new readiness replaces the one carried over
+        self.over_budget.remove(&tok);
 
-        loop {
-            match self.timer.tick_to(now) {
-                Some(t) => handler.timeout(self, t),
-                _ => return
-            }
-        }
-    }
-}
+        let tracking = io::track_bytes(true);
+        let (read, _) = io::tracked_bytes();
 
-#[deriving(Clone)]
-pub struct EventLoopSender<M: Send> {
-    notify: Notify<M>
-}
+        let action = self.io_dispatch(handler, tok, evt.kind());
 
-impl<M: Send> EventLoopSender<M> {
-    fn new(notify: Notify<M>) -> EventLoopSender<M> {
-        EventLoopSender { notify: notify }
+        self.budget_check(tok, read, tracking);
+        action
     }
 
-    pub fn send(&self, msg: M) -> Result<(), M> {
-        self.notify.notify(msg)
+    // Hands the readiness to the handler, along with the data attached to
+    // the registration if there is any
+    fn io_dispatch<H: Handler<Timeout=T, Message=M>>(&mut self, handler: &mut H, tok: Token, events: event::EventSet) -> HandlerAction {
+        match self.poll.data(tok) {
+            Some(data) => handler.ready_with_data(self, tok, events, data),
+            None => handler.ready(self, tok, events)
+        }
     }
-}
 
-pub type EventLoopResult<H> = Result<H, EventLoopError<H>>;
+    // Dispatches readable events again for the handles that used up their
+    // read budget in a previous tick
+    fn io_over_budget<H: Handler<Timeout=T, Message=M>>(&mut self, handler: &mut H) -> HandlerAction {
+        let ticks = self.ticks;
 
-pub struct EventLoopError<H> {
-    pub handler: H,
-    pub error: MioError
-}
+        let tokens: Vec<Token> = self.over_budget.iter()
+            .filter(|&(_, &tick)| tick < ticks)
+            .map(|(&tok, _)| tok)
+            .collect();
 
-impl<H> EventLoopError<H> {
-    fn new(handler: H, error: MioError) -> EventLoopError<H> {
-        EventLoopError {
-            handler: handler,
-            error: error
+        for tok in tokens.into_iter() {
+            self.over_budget.remove(&tok);
+
+            // No longer registered with this token
+            if !self.poll.is_live(tok) {
+                continue;
+            }
+
+            let tracking = io::track_bytes(true);
+            let (read, _) = io::tracked_bytes();
+
+            debug!("readable after read budget; token={}", tok);
+            let action = self.io_dispatch(handler, tok, event::EventSet::readable());
+
+            self.budget_check(tok, read, tracking);
+
+            if action == Shutdown {
+                return Shutdown;
+            }
         }
+
+        Continue
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::str;
-    use std::sync::Arc;
-    use std::sync::atomic::{AtomicInt, SeqCst};
-    use super::EventLoop;
-    use io::{IoWriter, IoReader};
-    use {io, buf, Buf, Handler, Token};
-    use os::event;
+    // Dispatches readable events for the acceptors resumed since the last
+    // tick, as connections may already be pending
+    fn io_resumed<H: Handler<Timeout=T, Message=M>>(&mut self, handler: &mut H) -> HandlerAction {
+        let tokens = mem::replace(&mut self.resumed, Vec::new());
 
-    type TestEventLoop = EventLoop<uint, ()>;
+        for tok in tokens.into_iter() {
+            // No longer registered with this token
+            if !self.poll.is_live(tok) {
+                continue;
+            }
 
-    struct Funtimes {
-        rcount: Arc<AtomicInt>,
-        wcount: Arc<AtomicInt>
-    }
+            debug!("resumed accept; token={}", tok);
 
-    impl Funtimes {
-        fn new(rcount: Arc<AtomicInt>, wcount: Arc<AtomicInt>) -> Funtimes {
-            Funtimes {
-                rcount: rcount,
-                wcount: wcount
+            if self.io_dispatch(handler, tok, event::EventSet::readable()) == Shutdown {
+                return Shutdown;
             }
         }
+
+        Continue
     }
 
-    impl Handler<uint, ()> for Funtimes {
-        fn readable(&mut self, _event_loop: &mut TestEventLoop, token: Token, _hint: event::ReadHint) {
-            (*self.rcount).fetch_add(1, SeqCst);
-            assert_eq!(token, Token(10));
+    // Carries the readiness of the handle over to the next tick if the
+    // handler read at least the budget since `read` was sampled
+    fn budget_check(&mut self, tok: Token, read: u64, tracking: bool) {
+        let (read_now, _) = io::tracked_bytes();
+        io::track_bytes(tracking);
+
+        if read_now - read >= self.config.read_budget as u64 {
+            debug!("read budget used up; token={}", tok);
+            self.over_budget.insert(tok, self.ticks);
         }
     }
 
-    #[test]
-    fn test_readable() {
-        let mut event_loop = EventLoop::new().ok().expect("Couldn't make event loop");
+    // Dispatches readable events for the handles that are not drained yet
+    // and did not get an IO event this tick
+    fn io_cached<H: Handler<Timeout=T, Message=M>>(&mut self, handler: &mut H) -> HandlerAction {
+        let ticks = self.ticks;
+
+        let tokens: Vec<Token> = self.readable.iter()
+            .filter(|&(_, &tick)| tick < ticks)
+            .map(|(&tok, _)| tok)
+            .collect();
+
+        for tok in tokens.into_iter() {
+            // May have been drained by the handler in the meantime
+            match self.readable.get_mut(&tok) {
+                Some(tick) => *tick = ticks,
+                None => continue
+            }
 
-        let (mut reader, mut writer) = io::pipe().unwrap();
+            // No longer registered with this token
+            if !self.poll.is_live(tok) {
+                self.readable.remove(&tok);
+                continue;
+            }
 
-        let rcount = Arc::new(AtomicInt::new(0));
-        let wcount = Arc::new(AtomicInt::new(0));
-        let handler = Funtimes::new(rcount.clone(), wcount.clone());
+            debug!("cached readable; token={}", tok);
 
-        writer.write(&mut buf::wrap("hello".as_bytes())).unwrap();
-        event_loop.register(&reader, Token(10)).unwrap();
+            if self.io_dispatch(handler, tok, event::EventSet::readable()) == Shutdown {
+                return Shutdown;
+            }
+        }
+
+        Continue
+    }
+
+    // Number of pending messages, up to `max`, see `Notify::check`
+    #[inline]
+    fn notify_check(&self, max: uint, will_sleep: bool) -> uint {
+        match self.notify {
+            Some(ref notify) => notify.check(max, will_sleep),
+            None => 0
+        }
+    }
+
+    fn notify<H: Handler<Timeout=T, Message=M>>(&mut self, handler: &mut H, mut cnt: uint) {
+        while cnt > 0 {
+            let msg = self.notify.as_ref().and_then(|notify| notify.poll())
+                .expect("[BUG] at this point there should always be a message");
+
+            handler.notify(self, msg);
+            cnt -= 1;
+        }
+    }
+
+    // Hands the messages still queued after a shutdown to the handler
+    fn notify_drain<H: Handler<Timeout=T, Message=M>>(&mut self, handler: &mut H) {
+        let cnt = self.notify_check(uint::MAX, false);
+
+        if cnt == 0 {
+            return;
+        }
+
+        debug!("draining notify queue; cnt={}", cnt);
+
+        let msgs = match self.notify {
+            Some(ref notify) => Vec::from_fn(cnt, |_| {
+                notify.poll().expect("[BUG] at this point there should always be a message")
+            }),
+            None => return
+        };
+
+        handler.notify_drained(self, msgs);
+    }
+
+    // Number of resolutions completed, see `notify_check`
+    #[inline]
+    fn resolver_check(&self, will_sleep: bool) -> uint {
+        match self.resolver {
+            Some(ref resolver) => resolver.check(uint::MAX, will_sleep),
+            None => 0
+        }
+    }
+
+    fn resolved<H: Handler<Timeout=T, Message=M>>(&mut self, handler: &mut H, mut cnt: uint) {
+        while cnt > 0 {
+            let (token, res) = self.resolver.as_mut().and_then(|resolver| resolver.poll())
+                .expect("[BUG] at this point there should always be a result");
+
+            handler.resolved(self, token, res);
+            cnt -= 1;
+        }
+    }
+
+    // Fails if `max_registrations` handles are registered, not counting the
+    // handles registered by the event loop itself. Registering `io` again
+    // replaces its entry, so it is not counted twice.
+    fn check_registrations<H: IoHandle>(&self, io: &H) -> MioResult<()> {
+        let max = self.config.max_registrations;
+
+        if max == 0 || self.poll.is_registered(io) {
+            return Ok(());
+        }
+
+        let internal = [self.notify.is_some(), self.native_timer.is_some(), self.resolver.is_some()]
+            .iter().filter(|&&registered| registered).count();
+
+        if self.poll.count() - internal >= max {
+            return Err(MioError::registration_limit());
+        }
+
+        Ok(())
+    }
+
+    // Connects to the next address left, returning the last error if
+    // connecting to all of them failed right away
+    fn connect_next(&mut self, token: Token, mut addrs: Vec<SockAddr>) -> MioResult<()> {
+        let mut err = MioError::resolve_failed();
+
+        loop {
+            let addr = match addrs.pop() {
+                Some(addr) => addr,
+                None => return Err(err)
+            };
+
+            match self.connect_start(&addr, token) {
+                Ok(sock) => {
+                    self.connecting.insert(token, HostConnect {
+                        sock: sock,
+                        addrs: addrs
+                    });
+
+                    return Ok(());
+                }
+                Err(e) => {
+                    debug!("failed to connect; addr={}; err={}", addr, e);
+                    err = e;
+                }
+            }
+        }
+    }
+
+    fn connect_start(&mut self, addr: &SockAddr, token: Token) -> MioResult<TcpSocket> {
+        let sock: TcpSocket = try!(Connect::connect_to(addr));
+
+        try!(self.check_registrations(&sock));
+        try!(self.poll.register(&sock, token, event::WRITABLE | event::ERROR | event::HUP, event::EDGE));
+
+        Ok(sock)
+    }
+
+    // The socket of a `connect_host` connection became writable, the connect
+    // either completed or failed
+    fn connect_event<H: Handler<Timeout=T, Message=M>>(&mut self, handler: &mut H, token: Token) {
+        let HostConnect { sock, addrs } = self.connecting.remove(&token)
+            .expect("[BUG] at this point there should always be a connection");
+
+        if self.poll.deregister(&sock).is_err() {
+            debug!("failed to deregister connecting socket; token={}", token);
+        }
+
+        let err = match os::connect_result(sock.desc()) {
+            Ok(()) => return handler.connected(self, token, Ok(sock)),
+            Err(e) => e
+        };
+
+        debug!("failed to connect; token={}; err={}", token, err);
+        drop(sock);
+
+        if addrs.is_empty() {
+            return handler.connected(self, token, Err(err));
+        }
+
+        match self.connect_next(token, addrs) {
+            Ok(()) => {}
+            Err(e) => handler.connected(self, token, Err(e))
+        }
+    }
+
+    // Pushes back the idle timeout of a handle that just had activity
+    fn idle_rearm(&mut self, token: Token) {
+        let delay = match self.idle.get(&token) {
+            Some(idle) => idle.delay,
+            None => return
+        };
+
+        if self.set_idle_timeout(token, delay).is_err() {
+            debug!("failed to rearm idle timeout; token={}", token);
+        }
+    }
+
+    fn native_timer_process<H: Handler<Timeout=T, Message=M>>(&mut self, handler: &mut H) {
+        let expired = match self.native_timer {
+            Some(ref mut native) => native.expired(),
+            None => return
+        };
+
+        match expired {
+            Ok(tokens) => {
+                for t in tokens.into_iter() {
+                    handler.timeout(self, t);
+                }
+            }
+            Err(e) => debug!("failed to read native timers; err={}", e)
+        }
+    }
+
+    fn timer_process<H: Handler<Timeout=T, Message=M>>(&mut self, handler: &mut H) {
+        let now = self.timer.now();
+
+        loop {
+            match self.timer.tick_to(now) {
+                Some(User(t)) => handler.timeout(self, t),
+                Some(Idle(tok)) => {
+                    if self.idle.remove(&tok).is_some() {
+                        handler.idle_timeout(self, tok);
+                    }
+                }
+                _ => return
+            }
+        }
+    }
+}
+
+// An IO handle owned by the event loop. Allows getting back to the concrete
+// type while still being able to deregister the handle without knowing it.
+trait OwnedIo : IoHandle {
+    fn as_any(&self) -> &Any;
+
+    fn as_any_mut(&mut self) -> &mut Any;
+
+    fn into_any(self: Box<Self>) -> Box<Any>;
+}
+
+impl<H: IoHandle + 'static> OwnedIo for H {
+    fn as_any(&self) -> &Any {
+        self as &Any
+    }
+
+    fn as_any_mut(&mut self) -> &mut Any {
+        self as &mut Any
+    }
+
+    fn into_any(self: Box<H>) -> Box<Any> {
+        self as Box<Any>
+    }
+}
+
+/// Iterator over the tokens registered with an event loop, see
+/// `EventLoop::registered_tokens`
+pub struct RegisteredTokens<'a> {
+    tokens: Tokens<'a>
+}
+
+impl<'a> Iterator<Token> for RegisteredTokens<'a> {
+    fn next(&mut self) -> Option<Token> {
+        loop {
+            match self.tokens.next() {
+                // Registrations internal to the event loop
+                Some(NOTIFY) | Some(TIMER) | Some(RESOLVER) => continue,
+                next => return next
+            }
+        }
+    }
+}
+
+#[deriving(Clone)]
+pub struct EventLoopSender<M: Send> {
+    notify: Notify<M>,
+    running: Arc<AtomicBool>,
+    sending: Arc<AtomicUint>
+}
+
+impl<M: Send> EventLoopSender<M> {
+    fn new(notify: Notify<M>, running: Arc<AtomicBool>, sending: Arc<AtomicUint>) -> EventLoopSender<M> {
+        EventLoopSender {
+            notify: notify,
+            running: running,
+            sending: sending
+        }
+    }
+
+    /// Queues the message for the handler's `notify`. Fails with `Closed`,
+    /// handing the message back, if the event loop was shut down, or with
+    /// `Full` if `EventLoopConfig::notify_capacity` messages are already
+    /// queued.
+    pub fn send(&self, msg: M) -> Result<(), NotifyError<M>> {
+        // Counted before checking, so that an event loop stopping in the
+        // meantime waits for the message to be queued before draining
+        self.sending.fetch_add(1, SeqCst);
+
+        let res = if self.running.load(SeqCst) {
+            self.notify.notify(msg).map_err(|msg| Full(msg))
+        } else {
+            Err(Closed(msg))
+        };
+
+        self.sending.fetch_sub(1, SeqCst);
+        res
+    }
+
+    /// Returns true once the event loop was shut down or stopped running,
+    /// see `EventLoop::is_running`.
+    pub fn is_closed(&self) -> bool {
+        !self.running.load(SeqCst)
+    }
+
+    /// Returns the number of messages queued but not dispatched to the
+    /// handler yet, letting producers slow down when the queue gets deep.
+    pub fn pending(&self) -> uint {
+        self.notify.len()
+    }
+}
+
+/// Returned by `EventLoopSender::send` along with the message that could not
+/// be sent.
+#[deriving(Show)]
+pub enum NotifyError<M> {
+    // The notification queue is full
+    Full(M),
+    // The event loop was shut down and will not process the message
+    Closed(M),
+}
+
+pub type EventLoopResult<H> = Result<H, EventLoopError<H>>;
+
+pub struct EventLoopError<H> {
+    pub handler: H,
+    pub error: MioError
+}
+
+impl<H> EventLoopError<H> {
+    fn new(handler: H, error: MioError) -> EventLoopError<H> {
+        EventLoopError {
+            handler: handler,
+            error: error
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicInt, SeqCst};
+    use std::time::duration::Duration;
+    use std::default::Default;
+    use super::{EventLoop, EventLoopConfig};
+    use io::{IoWriter, IoReader, PipeReader, PipeWriter};
+    use std::uint;
+    use {io, buf, Buf, Handler, HandlerAction, MioErrorKind, NonBlock, Token};
+    use os::event;
+
+    type TestEventLoop = EventLoop<uint, ()>;
+
+    struct Funtimes {
+        rcount: Arc<AtomicInt>,
+        wcount: Arc<AtomicInt>
+    }
+
+    impl Funtimes {
+        fn new(rcount: Arc<AtomicInt>, wcount: Arc<AtomicInt>) -> Funtimes {
+            Funtimes {
+                rcount: rcount,
+                wcount: wcount
+            }
+        }
+    }
+
+    impl Handler for Funtimes {
+        type Timeout = uint;
+        type Message = ();
+
+        fn readable(&mut self, _event_loop: &mut TestEventLoop, token: Token, _hint: event::ReadHint) {
+            (*self.rcount).fetch_add(1, SeqCst);
+            assert_eq!(token, Token(10));
+        }
+    }
+
+    #[test]
+    fn test_readable() {
+        let mut event_loop = EventLoop::new().ok().expect("Couldn't make event loop");
+
+        let (mut reader, mut writer) = io::pipe().unwrap();
+
+        let rcount = Arc::new(AtomicInt::new(0));
+        let wcount = Arc::new(AtomicInt::new(0));
+        let handler = Funtimes::new(rcount.clone(), wcount.clone());
+
+        writer.write(&mut buf::wrap("hello".as_bytes())).unwrap();
+        event_loop.register(&reader, Token(10)).unwrap();
 
         let _ = event_loop.run_once(handler);
         let mut b = buf::ByteBuf::new(16);
 
         assert_eq!((*rcount).load(SeqCst), 1);
+        assert_eq!(event_loop.tick_count(), 1);
 
         reader.read(&mut b).unwrap();
         b.flip();
 
         assert_eq!(str::from_utf8(b.bytes()).unwrap(), "hello");
     }
+
+    #[test]
+    fn test_read_all() {
+        let (reader, writer) = io::pipe().unwrap();
+        let mut b = buf::ByteBuf::new(16);
+
+        writer.write(&mut buf::wrap("hello".as_bytes())).unwrap();
+        writer.write(&mut buf::wrap(" world".as_bytes())).unwrap();
+
+        assert_eq!(reader.read_all(&mut b).unwrap(), (11, false));
+
+        drop(writer);
+        assert_eq!(reader.read_all(&mut b).unwrap(), (0, true));
+
+        b.flip();
+        assert_eq!(str::from_utf8(b.bytes()).unwrap(), "hello world");
+    }
+
+    #[test]
+    fn test_duplicate_registration() {
+        let mut event_loop: TestEventLoop = EventLoop::new().ok().expect("Couldn't make event loop");
+        let (reader, _) = io::pipe().unwrap();
+
+        event_loop.register(&reader, Token(10)).unwrap();
+
+        // Registering again replaces the registration
+        event_loop.register_opt(&reader, Token(11), event::READABLE, event::EDGE).unwrap();
+        assert_eq!(event_loop.interest(Token(10)), None);
+        assert_eq!(event_loop.interest(Token(11)), Some(event::READABLE));
+
+        // Once deregistered, the handle can be registered again
+        event_loop.deregister(&reader).unwrap();
+        event_loop.register(&reader, Token(12)).unwrap();
+    }
+
+    #[test]
+    fn test_max_registrations() {
+        let config = EventLoopConfig {
+            max_registrations: 2,
+            .. Default::default()
+        };
+
+        let mut event_loop: TestEventLoop = EventLoop::configured(config).ok().expect("Couldn't make event loop");
+        let (reader, writer) = io::pipe().unwrap();
+        let (reader2, _) = io::pipe().unwrap();
+
+        // The notify queue registered by the event loop is not counted
+        event_loop.register(&reader, Token(10)).unwrap();
+        event_loop.register_opt(&writer, Token(11), event::WRITABLE, event::LEVEL).unwrap();
+
+        let err = event_loop.register(&reader2, Token(12)).unwrap_err();
+        assert!(err.is_registration_limit());
+
+        // Registering a registered handle again replaces its entry
+        event_loop.register(&reader, Token(13)).unwrap();
+
+        // Deregistering frees a slot
+        event_loop.deregister(&writer).unwrap();
+        event_loop.register_owned(reader2, Token(12), event::READABLE, event::LEVEL).unwrap();
+    }
+
+    #[test]
+    fn test_interest() {
+        let mut event_loop: TestEventLoop = EventLoop::new().ok().expect("Couldn't make event loop");
+        let (reader, _) = io::pipe().unwrap();
+
+        assert_eq!(event_loop.interest(Token(10)), None);
+
+        event_loop.register_opt(&reader, Token(10), event::READABLE, event::EDGE).unwrap();
+        assert_eq!(event_loop.interest(Token(10)), Some(event::READABLE));
+
+        event_loop.reregister(&reader, Token(11), event::READABLE | event::WRITABLE, event::EDGE).unwrap();
+        assert_eq!(event_loop.interest(Token(10)), None);
+        assert_eq!(event_loop.interest(Token(11)), Some(event::READABLE | event::WRITABLE));
+
+        event_loop.deregister(&reader).unwrap();
+        assert_eq!(event_loop.interest(Token(11)), None);
+    }
+
+    #[test]
+    fn test_reserved_tokens() {
+        let mut event_loop: TestEventLoop = EventLoop::new().ok().expect("Couldn't make event loop");
+        let (reader, _) = io::pipe().unwrap();
+
+        let notify = event_loop.awakener_token();
+
+        assert!(event_loop.is_reserved_token(notify));
+        assert!(event_loop.is_reserved_token(event_loop.timer_token()));
+        assert!(!event_loop.is_reserved_token(Token(uint::MAX - 3)));
+
+        let err = event_loop.register(&reader, notify).unwrap_err();
+        assert_eq!(err.kind, MioErrorKind::InvalidInput);
+
+        event_loop.register(&reader, Token(uint::MAX - 3)).unwrap();
+    }
+
+    #[test]
+    fn test_registered_tokens() {
+        let mut event_loop: TestEventLoop = EventLoop::new().ok().expect("Couldn't make event loop");
+        let (reader, writer) = io::pipe().unwrap();
+        let (reader2, _) = io::pipe().unwrap();
+
+        event_loop.register(&reader, Token(10)).unwrap();
+        event_loop.register_opt(&writer, Token(11), event::WRITABLE, event::LEVEL).unwrap();
+        event_loop.register_owned(reader2, Token(12), event::READABLE, event::LEVEL).unwrap();
+
+        let mut tokens: Vec<Token> = event_loop.registered_tokens().collect();
+        tokens.sort();
+        assert_eq!(tokens, vec![Token(10), Token(11), Token(12)]);
+
+        event_loop.deregister(&writer).unwrap();
+        assert!(event_loop.remove_owned(Token(12)).unwrap());
+
+        let tokens: Vec<Token> = event_loop.registered_tokens().collect();
+        assert_eq!(tokens, vec![Token(10)]);
+    }
+
+    #[test]
+    fn test_owned_registration() {
+        let mut event_loop: TestEventLoop = EventLoop::new().ok().expect("Couldn't make event loop");
+        let (reader, writer) = io::pipe().unwrap();
+
+        event_loop.register_owned(reader, Token(10), event::READABLE, event::LEVEL).unwrap();
+
+        writer.write(&mut buf::wrap("hello".as_bytes())).unwrap();
+
+        {
+            let reader = event_loop.connection_mut::<PipeReader>(Token(10)).unwrap();
+            let mut b = buf::ByteBuf::new(16);
+
+            reader.read(&mut b).unwrap();
+            b.flip();
+
+            assert_eq!(str::from_utf8(b.bytes()).unwrap(), "hello");
+        }
+
+        // Wrong type or unknown token
+        assert!(event_loop.connection::<io::PipeWriter>(Token(10)).is_none());
+        assert!(event_loop.connection::<PipeReader>(Token(11)).is_none());
+
+        let reader = event_loop.take_owned::<PipeReader>(Token(10)).unwrap().unwrap();
+        assert!(event_loop.connection::<PipeReader>(Token(10)).is_none());
+
+        // Deregistered when taken back
+        event_loop.register(&reader, Token(11)).unwrap();
+        event_loop.deregister(&reader).unwrap();
+
+        event_loop.register_owned(reader, Token(12), event::READABLE, event::LEVEL).unwrap();
+        assert!(event_loop.remove_owned(Token(12)).unwrap());
+        assert!(!event_loop.remove_owned(Token(12)).unwrap());
+    }
+
+    struct IdleHandler {
+        idle: Vec<Token>
+    }
+
+    impl Handler for IdleHandler {
+        type Timeout = uint;
+        type Message = ();
+
+        fn idle_timeout(&mut self, event_loop: &mut TestEventLoop, token: Token) {
+            self.idle.push(token);
+            event_loop.shutdown();
+        }
+    }
+
+    #[test]
+    fn test_idle_timeout() {
+        let mut event_loop: TestEventLoop = EventLoop::new().ok().expect("Couldn't make event loop");
+        let (reader, _writer) = io::pipe().unwrap();
+
+        event_loop.register(&reader, Token(10)).unwrap();
+        event_loop.set_idle_timeout(Token(10), Duration::milliseconds(200)).unwrap();
+
+        // Cleared idle timeouts do not fire
+        event_loop.set_idle_timeout(Token(11), Duration::milliseconds(100)).unwrap();
+        assert!(event_loop.clear_idle_timeout(Token(11)));
+
+        let handler = event_loop.run(IdleHandler { idle: vec![] }).ok().unwrap();
+
+        assert_eq!(handler.idle, vec![Token(10)]);
+        assert!(!event_loop.clear_idle_timeout(Token(10)));
+    }
+
+    #[test]
+    fn test_events_capacity_grows() {
+        let config = EventLoopConfig {
+            io_poll_capacity: 1,
+            io_poll_max_capacity: 4,
+            .. Default::default()
+        };
+
+        let mut event_loop: TestEventLoop = EventLoop::configured(config).ok().expect("Couldn't make event loop");
+        let mut pipes = vec![];
+
+        for i in range(0u, 4) {
+            let (reader, writer) = io::pipe().unwrap();
+
+            writer.write(&mut buf::wrap("hello".as_bytes())).unwrap();
+            event_loop.register(&reader, Token(i)).unwrap();
+
+            pipes.push((reader, writer));
+        }
+
+        assert_eq!(event_loop.poll.capacity(), 1);
+
+        // Each tick that fills the buffer doubles it, up to the max
+        for _ in range(0u, 4) {
+            let _ = event_loop.run_once(IdleHandler { idle: vec![] });
+        }
+
+        assert_eq!(event_loop.poll.capacity(), 4);
+    }
+
+    struct ReadyHandler {
+        events: Vec<(Token, bool, bool)>
+    }
+
+    impl Handler for ReadyHandler {
+        type Timeout = uint;
+        type Message = ();
+
+        fn ready(&mut self, _event_loop: &mut TestEventLoop, token: Token, events: event::EventSet) -> HandlerAction {
+            self.events.push((token, events.is_readable(), events.is_writable()));
+            HandlerAction::Continue
+        }
+
+        fn readable(&mut self, _event_loop: &mut TestEventLoop, _token: Token, _hint: event::ReadHint) {
+            panic!("readable should not be called when ready is overridden");
+        }
+    }
+
+    #[test]
+    fn test_ready_single_dispatch() {
+        let mut event_loop: TestEventLoop = EventLoop::new().ok().expect("Couldn't make event loop");
+        let (reader, writer) = io::pipe().unwrap();
+
+        writer.write(&mut buf::wrap("hello".as_bytes())).unwrap();
+        event_loop.register(&reader, Token(10)).unwrap();
+
+        let handler = event_loop.run_once(ReadyHandler { events: vec![] }).ok().unwrap();
+
+        assert_eq!(handler.events, vec![(Token(10), true, false)]);
+    }
+
+    #[test]
+    fn test_trigger_repoll() {
+        let config = EventLoopConfig {
+            io_poll_timeout_ms: 2_000,
+            .. Default::default()
+        };
+
+        let mut event_loop: TestEventLoop = EventLoop::configured(config).ok().expect("Couldn't make event loop");
+        let start = event_loop.now();
+
+        // Nothing is registered, the tick would otherwise block for the
+        // whole poll timeout
+        event_loop.trigger_repoll();
+        event_loop.run_ticks(IdleHandler { idle: vec![] }, 1).ok().unwrap();
+
+        assert!(event_loop.now() - start < Duration::milliseconds(1_000));
+    }
+
+    #[test]
+    fn test_run_ticks() {
+        let mut event_loop: TestEventLoop = EventLoop::new().ok().expect("Couldn't make event loop");
+        let (_reader, writer) = io::pipe().unwrap();
+
+        // A level triggered pipe stays writable, so every tick dispatches it
+        event_loop.register_opt(&writer, Token(10), event::WRITABLE, event::LEVEL).unwrap();
+
+        let handler = event_loop.run_ticks(ReadyHandler { events: vec![] }, 3).ok().unwrap();
+
+        assert_eq!(handler.events, vec![(Token(10), false, true), (Token(10), false, true), (Token(10), false, true)]);
+        assert_eq!(event_loop.tick_count(), 3);
+
+        // Not shut down, messages are accepted until the next batch of ticks
+        assert!(event_loop.is_running());
+        event_loop.channel().unwrap().send(()).unwrap();
+    }
+
+    struct DataHandler {
+        events: Vec<(Token, Option<uint>)>
+    }
+
+    impl Handler for DataHandler {
+        type Timeout = uint;
+        type Message = ();
+
+        fn ready(&mut self, _event_loop: &mut TestEventLoop, token: Token, _events: event::EventSet) -> HandlerAction {
+            self.events.push((token, None));
+            HandlerAction::Continue
+        }
+
+        fn ready_with_data(&mut self, _event_loop: &mut TestEventLoop, token: Token, _events: event::EventSet, data: uint) -> HandlerAction {
+            self.events.push((token, Some(data)));
+            HandlerAction::Continue
+        }
+    }
+
+    #[test]
+    fn test_register_with_data() {
+        let mut event_loop: TestEventLoop = EventLoop::new().ok().expect("Couldn't make event loop");
+        let (_r1, w1) = io::pipe().unwrap();
+        let (_r2, w2) = io::pipe().unwrap();
+
+        event_loop.register_with_data(&w1, Token(10), event::WRITABLE, event::LEVEL, 42).unwrap();
+        event_loop.register_opt(&w2, Token(11), event::WRITABLE, event::LEVEL).unwrap();
+
+        let mut handler = event_loop.run_once(DataHandler { events: vec![] }).ok().unwrap();
+        handler.events.sort();
+
+        assert_eq!(handler.events, vec![(Token(10), Some(42)), (Token(11), None)]);
+
+        // The data survives reregistration
+        event_loop.reregister(&w1, Token(12), event::WRITABLE, event::LEVEL).unwrap();
+        event_loop.deregister(&w2).unwrap();
+
+        let handler = event_loop.run_once(DataHandler { events: vec![] }).ok().unwrap();
+
+        assert_eq!(handler.events, vec![(Token(12), Some(42))]);
+    }
+
+    struct ShutdownHandler {
+        seen: uint
+    }
+
+    impl Handler for ShutdownHandler {
+        type Timeout = uint;
+        type Message = ();
+
+        fn ready(&mut self, _event_loop: &mut TestEventLoop, _token: Token, _events: event::EventSet) -> HandlerAction {
+            self.seen += 1;
+            HandlerAction::Shutdown
+        }
+    }
+
+    #[test]
+    fn test_ready_shutdown_skips_remaining_events() {
+        let mut event_loop: TestEventLoop = EventLoop::new().ok().expect("Couldn't make event loop");
+        let mut pipes = vec![];
+
+        for i in range(0u, 3) {
+            let (reader, writer) = io::pipe().unwrap();
+
+            writer.write(&mut buf::wrap("hello".as_bytes())).unwrap();
+            event_loop.register(&reader, Token(i)).unwrap();
+
+            pipes.push((reader, writer));
+        }
+
+        let handler = event_loop.run(ShutdownHandler { seen: 0 }).ok().unwrap();
+
+        assert_eq!(handler.seen, 1);
+    }
+
+    struct RawHandler {
+        events: Vec<(Token, i64)>
+    }
+
+    impl Handler for RawHandler {
+        type Timeout = uint;
+        type Message = ();
+
+        fn raw_ready(&mut self, _event_loop: &mut TestEventLoop, token: Token, event: event::IoEvent) -> HandlerAction {
+            self.events.push((token, event.raw_data()));
+            HandlerAction::Continue
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_register_raw() {
+        // EPOLLIN
+        const EPOLLIN: u32 = 0x001;
+
+        let mut event_loop: TestEventLoop = EventLoop::new().ok().expect("Couldn't make event loop");
+        let (reader, writer) = io::pipe().unwrap();
+
+        writer.write(&mut buf::wrap("hello".as_bytes())).unwrap();
+        event_loop.register_raw(&reader, Token(10), EPOLLIN).unwrap();
+
+        let handler = event_loop.run_once(RawHandler { events: vec![] }).ok().unwrap();
+
+        assert_eq!(handler.events, vec![(Token(10), EPOLLIN as i64)]);
+    }
+
+    #[test]
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    fn test_register_raw() {
+        let mut event_loop: TestEventLoop = EventLoop::new().ok().expect("Couldn't make event loop");
+
+        // One shot 10ms kernel timer
+        event_loop.register_raw(1, Token(10), event::EventFilter::EVFILT_TIMER,
+                                event::FilterFlag::empty(), 10, event::ONESHOT).unwrap();
+
+        let handler = event_loop.run_once(RawHandler { events: vec![] }).ok().unwrap();
+
+        // The data of a timer event is the number of expirations
+        assert_eq!(handler.events, vec![(Token(10), 1)]);
+    }
+
+    struct TimeoutHandler {
+        fired: Vec<uint>
+    }
+
+    impl Handler for TimeoutHandler {
+        type Timeout = uint;
+        type Message = ();
+
+        fn timeout(&mut self, event_loop: &mut TestEventLoop, timeout: uint) {
+            self.fired.push(timeout);
+            event_loop.shutdown();
+        }
+    }
+
+    #[test]
+    fn test_native_timers() {
+        let config = EventLoopConfig {
+            use_native_timers: true,
+            .. Default::default()
+        };
+
+        let mut event_loop: TestEventLoop = EventLoop::configured(config).ok().expect("Couldn't make event loop");
+
+        let cleared = event_loop.timeout(1, Duration::milliseconds(10)).unwrap();
+        assert!(event_loop.clear_timeout(cleared));
+
+        event_loop.timeout(2, Duration::milliseconds(50)).unwrap();
+
+        let start = event_loop.now();
+        let handler = event_loop.run(TimeoutHandler { fired: vec![] }).ok().unwrap();
+
+        assert_eq!(handler.fired, vec![2]);
+        assert!(event_loop.now() - start >= Duration::milliseconds(50));
+    }
+
+    #[test]
+    fn test_timeout_fires_on_time() {
+        let config = EventLoopConfig {
+            timer_tick_ms: 10,
+            .. Default::default()
+        };
+
+        let mut event_loop: TestEventLoop = EventLoop::configured(config).ok().expect("Couldn't make event loop");
+
+        event_loop.timeout(1, Duration::milliseconds(50)).unwrap();
+
+        let start = event_loop.now();
+        let handler = event_loop.run(TimeoutHandler { fired: vec![] }).ok().unwrap();
+        let elapsed = event_loop.now() - start;
+
+        assert_eq!(handler.fired, vec![1]);
+        assert!(elapsed >= Duration::milliseconds(50), "fired early; elapsed={}", elapsed);
+        assert!(elapsed < Duration::milliseconds(150), "fired late; elapsed={}", elapsed);
+    }
+
+    #[test]
+    fn test_pending_timeouts() {
+        let mut event_loop: TestEventLoop = EventLoop::new().ok().expect("Couldn't make event loop");
+
+        let a = event_loop.timeout(1, Duration::milliseconds(10)).unwrap();
+        event_loop.timeout(2, Duration::milliseconds(50)).unwrap();
+        event_loop.set_idle_timeout(Token(10), Duration::milliseconds(500)).unwrap();
+
+        assert_eq!(event_loop.pending_timeouts(), 2);
+
+        event_loop.clear_timeout(a);
+        assert_eq!(event_loop.pending_timeouts(), 1);
+
+        let handler = event_loop.run(TimeoutHandler { fired: vec![] }).ok().unwrap();
+
+        assert_eq!(handler.fired, vec![2]);
+        assert_eq!(event_loop.pending_timeouts(), 0);
+    }
+
+    struct ChunkedReader {
+        reader: PipeReader,
+        received: Vec<u8>,
+        reads: uint
+    }
+
+    impl Handler for ChunkedReader {
+        type Timeout = uint;
+        type Message = ();
+
+        fn readable(&mut self, event_loop: &mut TestEventLoop, token: Token, _hint: event::ReadHint) {
+            // Read a single byte per notification
+            let mut b = [0u8, ..1];
+
+            self.reads += 1;
+
+            match self.reader.read_slice(&mut b).unwrap() {
+                NonBlock::Ready(_) => self.received.push(b[0]),
+                NonBlock::WouldBlock => {
+                    event_loop.drained(token);
+                    event_loop.shutdown();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_cached_readable_until_drained() {
+        let config = EventLoopConfig {
+            io_cache_readable: true,
+            .. Default::default()
+        };
+
+        let mut event_loop: TestEventLoop = EventLoop::configured(config).ok().expect("Couldn't make event loop");
+        let (reader, writer) = io::pipe().unwrap();
+
+        writer.write(&mut buf::wrap("hello".as_bytes())).unwrap();
+
+        // Edge triggered, the selector only reports the data once
+        event_loop.register_opt(&reader, Token(10), event::READABLE, event::EDGE).unwrap();
+
+        let handler = event_loop.run(ChunkedReader {
+            reader: reader,
+            received: vec![],
+            reads: 0
+        }).ok().unwrap();
+
+        assert_eq!(handler.received.as_slice(), "hello".as_bytes());
+        assert_eq!(handler.reads, 6);
+    }
+
+    // Same as `ChunkedReader`, handling the events in `ready`
+    struct ChunkedReadyReader {
+        reader: PipeReader,
+        received: Vec<u8>
+    }
+
+    impl Handler for ChunkedReadyReader {
+        type Timeout = uint;
+        type Message = ();
+
+        fn ready(&mut self, event_loop: &mut TestEventLoop, token: Token, events: event::EventSet) -> HandlerAction {
+            let mut b = [0u8, ..1];
+
+            assert!(events.is_readable());
+
+            match self.reader.read_slice(&mut b).unwrap() {
+                NonBlock::Ready(_) => {
+                    self.received.push(b[0]);
+                    HandlerAction::Continue
+                }
+                NonBlock::WouldBlock => {
+                    event_loop.drained(token);
+                    HandlerAction::Shutdown
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_cached_readable_dispatched_to_ready() {
+        let config = EventLoopConfig {
+            io_cache_readable: true,
+            .. Default::default()
+        };
+
+        let mut event_loop: TestEventLoop = EventLoop::configured(config).ok().expect("Couldn't make event loop");
+        let (reader, writer) = io::pipe().unwrap();
+
+        writer.write(&mut buf::wrap("hello".as_bytes())).unwrap();
+        event_loop.register_opt(&reader, Token(10), event::READABLE, event::EDGE).unwrap();
+
+        let handler = event_loop.run(ChunkedReadyReader {
+            reader: reader,
+            received: vec![]
+        }).ok().unwrap();
+
+        assert_eq!(handler.received.as_slice(), "hello".as_bytes());
+    }
+
+    // Copies everything read from one pipe to another
+    struct Forwarder {
+        reader: PipeReader,
+        writer: PipeWriter
+    }
+
+    impl Handler for Forwarder {
+        type Timeout = uint;
+        type Message = ();
+
+        fn readable(&mut self, event_loop: &mut TestEventLoop, _token: Token, _hint: event::ReadHint) {
+            let mut b = [0u8, ..16];
+            let cnt = self.reader.read_slice(&mut b).unwrap().unwrap();
+
+            self.writer.write_slice(b.slice_to(cnt)).unwrap();
+            event_loop.shutdown();
+        }
+    }
+
+    #[test]
+    fn test_io_stats() {
+        let config = EventLoopConfig {
+            io_stats: true,
+            .. Default::default()
+        };
+
+        let mut event_loop: TestEventLoop = EventLoop::configured(config).ok().expect("Couldn't make event loop");
+        let (reader, writer) = io::pipe().unwrap();
+        let (_out_reader, out_writer) = io::pipe().unwrap();
+
+        // Not counted, the event loop is not dispatching events
+        writer.write_slice("hello".as_bytes()).unwrap();
+
+        event_loop.register(&reader, Token(10)).unwrap();
+        event_loop.run(Forwarder { reader: reader, writer: out_writer }).ok().unwrap();
+
+        let stats = event_loop.io_stats();
+
+        assert_eq!(stats.bytes_read, 5);
+        assert_eq!(stats.bytes_written, 5);
+        assert_eq!(stats.events_dispatched, 1);
+    }
+
+    // Reads at most the read budget per call
+    struct BudgetReader {
+        reader: PipeReader,
+        reads: Vec<(uint, u64)>,
+        total: uint
+    }
+
+    impl Handler for BudgetReader {
+        type Timeout = uint;
+        type Message = ();
+
+        fn readable(&mut self, event_loop: &mut TestEventLoop, _token: Token, _hint: event::ReadHint) {
+            let mut b = [0u8, ..16];
+            let budget = event_loop.read_budget();
+
+            match self.reader.read_slice(b.slice_to_mut(budget)).unwrap() {
+                NonBlock::Ready(cnt) => {
+                    self.reads.push((cnt, event_loop.tick_count()));
+                    self.total += cnt;
+                }
+                NonBlock::WouldBlock => {}
+            }
+
+            if self.total == 10 {
+                event_loop.shutdown();
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_budget() {
+        let config = EventLoopConfig {
+            read_budget: 4,
+            .. Default::default()
+        };
+
+        let mut event_loop: TestEventLoop = EventLoop::configured(config).ok().expect("Couldn't make event loop");
+        let (reader, writer) = io::pipe().unwrap();
+
+        writer.write_slice("0123456789".as_bytes()).unwrap();
+
+        // Edge triggered, the remaining data is only read because the
+        // readiness is carried over
+        event_loop.register_opt(&reader, Token(10), event::READABLE, event::EDGE).unwrap();
+
+        let handler = event_loop.run(BudgetReader {
+            reader: reader,
+            reads: vec![],
+            total: 0
+        }).ok().unwrap();
+
+        assert_eq!(handler.reads, vec![(4, 1), (4, 2), (2, 3)]);
+    }
+
+    // Deregisters the reader after using up the read budget once
+    struct DeregisterReader {
+        reader: PipeReader,
+        calls: uint
+    }
+
+    impl Handler for DeregisterReader {
+        type Timeout = uint;
+        type Message = ();
+
+        fn readable(&mut self, event_loop: &mut TestEventLoop, _token: Token, _hint: event::ReadHint) {
+            let mut b = [0u8, ..4];
+
+            self.calls += 1;
+            self.reader.read_slice(&mut b).unwrap();
+            event_loop.deregister(&self.reader).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_read_budget_deregistered() {
+        let config = EventLoopConfig {
+            read_budget: 4,
+            io_poll_timeout_ms: 10,
+            .. Default::default()
+        };
+
+        let mut event_loop: TestEventLoop = EventLoop::configured(config).ok().expect("Couldn't make event loop");
+        let (reader, writer) = io::pipe().unwrap();
+
+        writer.write_slice("0123456789".as_bytes()).unwrap();
+        event_loop.register_opt(&reader, Token(10), event::READABLE, event::EDGE).unwrap();
+
+        let handler = event_loop.run_ticks(DeregisterReader {
+            reader: reader,
+            calls: 0
+        }, 3).ok().unwrap();
+
+        // The used up budget is not carried over once deregistered
+        assert_eq!(handler.calls, 1);
+    }
+
+    // Same as `BudgetReader`, handling the events in `ready`
+    struct BudgetReadyReader {
+        reader: PipeReader,
+        total: uint
+    }
+
+    impl Handler for BudgetReadyReader {
+        type Timeout = uint;
+        type Message = ();
+
+        fn ready(&mut self, event_loop: &mut TestEventLoop, _token: Token, _events: event::EventSet) -> HandlerAction {
+            let mut b = [0u8, ..16];
+            let budget = event_loop.read_budget();
+
+            match self.reader.read_slice(b.slice_to_mut(budget)).unwrap() {
+                NonBlock::Ready(cnt) => self.total += cnt,
+                NonBlock::WouldBlock => {}
+            }
+
+            if self.total == 10 {
+                return HandlerAction::Shutdown;
+            }
+
+            HandlerAction::Continue
+        }
+    }
+
+    #[test]
+    fn test_read_budget_dispatched_to_ready() {
+        let config = EventLoopConfig {
+            read_budget: 4,
+            .. Default::default()
+        };
+
+        let mut event_loop: TestEventLoop = EventLoop::configured(config).ok().expect("Couldn't make event loop");
+        let (reader, writer) = io::pipe().unwrap();
+
+        writer.write_slice("0123456789".as_bytes()).unwrap();
+        event_loop.register_opt(&reader, Token(10), event::READABLE, event::EDGE).unwrap();
+
+        let handler = event_loop.run(BudgetReadyReader {
+            reader: reader,
+            total: 0
+        }).ok().unwrap();
+
+        assert_eq!(handler.total, 10);
+        assert_eq!(event_loop.tick_count(), 3);
+    }
+
+    struct WritableHandler {
+        writable: uint
+    }
+
+    impl Handler for WritableHandler {
+        type Timeout = uint;
+        type Message = ();
+
+        fn writable(&mut self, event_loop: &mut TestEventLoop, token: Token) {
+            assert_eq!(token, Token(20));
+
+            self.writable += 1;
+            event_loop.shutdown();
+        }
+    }
+
+    #[test]
+    fn test_retry_on_block() {
+        let mut event_loop: TestEventLoop = EventLoop::new().ok().expect("Couldn't make event loop");
+        let (reader, writer) = io::pipe().unwrap();
+        let chunk = [0u8, ..4096];
+
+        // Ready results are passed through without registering
+        let res = writer.write_slice(&chunk);
+        assert!(event_loop.retry_on_block(&writer, Token(20), event::WRITABLE, res).unwrap().is_ready());
+        assert_eq!(event_loop.interest(Token(20)), None);
+
+        let mut res;
+
+        loop {
+            res = writer.write_slice(&chunk);
+
+            if res.as_ref().unwrap().would_block() {
+                break;
+            }
+        }
+
+        assert!(event_loop.retry_on_block(&writer, Token(20), event::WRITABLE, res).unwrap().is_would_block());
+        assert_eq!(event_loop.interest(Token(20)), Some(event::WRITABLE));
+
+        // Make room for the retry
+        let mut buf = [0u8, ..4096];
+
+        while reader.read_slice(&mut buf).unwrap().is_ready() {}
+
+        let handler = event_loop.run(WritableHandler { writable: 0 }).ok().unwrap();
+        assert_eq!(handler.writable, 1);
+    }
 }
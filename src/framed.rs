@@ -0,0 +1,192 @@
+use std::cmp;
+use buf::{Buf, MutBuf, RingBuf};
+use error::{MioResult, MioError};
+use io::{IoReader, IoWriter, NonBlock};
+use io::NonBlock::{Ready, WouldBlock};
+
+// Size of the big-endian length prefix of each frame
+const PREFIX_LEN: uint = 4;
+
+/// Splits a byte stream into frames, each preceded by its length as a
+/// big-endian u32. Data is read into an internal ring buffer, so partial
+/// frames are kept across readable events until the rest arrives.
+pub struct FramedReader {
+    buf: RingBuf,
+    max_frame: uint,
+    // Length of the frame being received, once its prefix has been consumed
+    len: Option<uint>,
+}
+
+impl FramedReader {
+    /// Creates a reader accepting frames of up to `max_frame` bytes. The
+    /// buffer is sized to hold at least one complete frame.
+    pub fn new(max_frame: uint) -> FramedReader {
+        FramedReader {
+            buf: RingBuf::new(max_frame + PREFIX_LEN),
+            max_frame: max_frame,
+            len: None
+        }
+    }
+
+    /// Reads from `io` until the read would block, the buffer is full or the
+    /// end of the stream is reached. Returns the number of bytes read and
+    /// whether EOF was hit, as `IoReader::read_all` does.
+    ///
+    /// When the buffer fills up, there may still be data pending. Take the
+    /// buffered frames with `next_frame` and call `read` again before waiting
+    /// for the next edge triggered notification.
+    pub fn read<R: IoReader>(&mut self, io: &R) -> MioResult<(uint, bool)> {
+        io.read_all(&mut self.buf.writer())
+    }
+
+    /// Returns the next complete frame, without its length prefix, or `None`
+    /// if it has not been fully received yet. Returns a `MessageTooBig` error
+    /// if the peer announced a frame larger than `max_frame`, after which the
+    /// stream cannot be resynchronized and should be closed.
+    pub fn next_frame(&mut self) -> MioResult<Option<Vec<u8>>> {
+        let len = match self.len {
+            Some(len) => len,
+            None => {
+                match self.buf.reader().get_be_u32() {
+                    Some(len) => len as uint,
+                    None => return Ok(None)
+                }
+            }
+        };
+
+        self.len = Some(len);
+
+        if len > self.max_frame {
+            debug!("frame too big; len={}; max={}", len, self.max_frame);
+            return Err(MioError::message_too_big());
+        }
+
+        let mut reader = self.buf.reader();
+
+        if reader.remaining() < len {
+            return Ok(None);
+        }
+
+        let mut frame = Vec::from_elem(len, 0u8);
+        reader.get_bytes(frame.as_mut_slice());
+
+        self.len = None;
+
+        Ok(Some(frame))
+    }
+}
+
+/// Buffers frames for writing, prefixing each with its length as a
+/// big-endian u32, and writes them out as the handle becomes writable.
+pub struct FramedWriter {
+    buf: RingBuf,
+    max_frame: uint,
+}
+
+impl FramedWriter {
+    /// Creates a writer accepting frames of up to `max_frame` bytes, with a
+    /// buffer of at least `capacity` bytes for frames waiting to be written.
+    pub fn new(max_frame: uint, capacity: uint) -> FramedWriter {
+        FramedWriter {
+            buf: RingBuf::new(cmp::max(capacity, max_frame + PREFIX_LEN)),
+            max_frame: max_frame
+        }
+    }
+
+    /// Queues `frame` for writing. Returns a `MessageTooBig` error if the
+    /// frame is larger than `max_frame`, or a `BufOverflow` error if there is
+    /// not enough buffer space left, in which case `flush` must make progress
+    /// first. Nothing is queued on error.
+    pub fn push(&mut self, frame: &[u8]) -> MioResult<()> {
+        if frame.len() > self.max_frame {
+            return Err(MioError::message_too_big());
+        }
+
+        let mut writer = self.buf.writer();
+
+        if writer.remaining() < PREFIX_LEN + frame.len() {
+            return Err(MioError::buf_overflow());
+        }
+
+        try!(writer.put_be_u32(frame.len() as u32));
+        writer.put_bytes(frame)
+    }
+
+    /// Writes buffered frames to `io` until the buffer is empty, returning
+    /// `WouldBlock` if data is still pending, in which case `flush` should be
+    /// called again on the next writable event.
+    pub fn flush<W: IoWriter>(&mut self, io: &W) -> MioResult<NonBlock<()>> {
+        while !self.buf.is_empty() {
+            match try!(io.write(&mut self.buf.reader())) {
+                Ready(_) => {}
+                WouldBlock => return Ok(WouldBlock)
+            }
+        }
+
+        Ok(Ready(()))
+    }
+
+    /// Returns true if there is no buffered data left to write.
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use io;
+    use io::IoWriter;
+    use super::{FramedReader, FramedWriter};
+
+    #[test]
+    pub fn test_frames_across_reads() {
+        let (reader, writer) = io::pipe().unwrap();
+        let mut framed = FramedReader::new(16);
+
+        // A complete frame followed by the start of a second one
+        writer.write_slice(b"\x00\x00\x00\x03abc\x00\x00").unwrap();
+        assert_eq!(framed.read(&reader).unwrap(), (9, false));
+
+        assert!(framed.next_frame().unwrap() == Some(b"abc".to_vec()));
+        assert!(framed.next_frame().unwrap().is_none());
+
+        writer.write_slice(b"\x00\x02hi").unwrap();
+        framed.read(&reader).unwrap();
+
+        assert!(framed.next_frame().unwrap() == Some(b"hi".to_vec()));
+        assert!(framed.next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    pub fn test_oversize_frame() {
+        let (reader, writer) = io::pipe().unwrap();
+        let mut framed = FramedReader::new(16);
+
+        writer.write_slice(b"\x00\x00\x01\x00").unwrap();
+        framed.read(&reader).unwrap();
+
+        assert!(framed.next_frame().unwrap_err().is_message_too_big());
+
+        let mut framed = FramedWriter::new(16, 64);
+        assert!(framed.push(&[0u8, ..17]).unwrap_err().is_message_too_big());
+        assert!(framed.is_empty());
+    }
+
+    #[test]
+    pub fn test_write_then_read() {
+        let (reader, writer) = io::pipe().unwrap();
+        let mut out = FramedWriter::new(16, 32);
+        let mut inp = FramedReader::new(16);
+
+        out.push(b"hello").unwrap();
+        out.push(b"world").unwrap();
+        assert!(!out.flush(&writer).unwrap().would_block());
+        assert!(out.is_empty());
+
+        inp.read(&reader).unwrap();
+
+        assert!(inp.next_frame().unwrap() == Some(b"hello".to_vec()));
+        assert!(inp.next_frame().unwrap() == Some(b"world".to_vec()));
+        assert!(inp.next_frame().unwrap().is_none());
+    }
+}
@@ -1,18 +1,149 @@
+use error::MioResult;
 use event_loop::EventLoop;
+use net::SockAddr;
+use net::tcp::TcpSocket;
 use os::token::Token;
 use os::event;
 
+/// Returned by `Handler::ready` to tell the event loop how to proceed.
+///
+/// `readable`, `writable` and `error` return nothing, to stop the event loop
+/// early from them override `ready` instead, calling them and returning
+/// `Shutdown` as needed. `EventLoop::shutdown` called from any callback only
+/// takes effect once the current iteration is done.
+#[deriving(Show, PartialEq, Clone)]
+pub enum HandlerAction {
+    // Keep dispatching events
+    Continue,
+    // Stop the event loop right away, skipping the remaining IO events and
+    // timeouts of the current iteration. Messages already taken off the notify
+    // queue during the iteration are still delivered so that none are lost.
+    Shutdown,
+}
+
 #[allow(unused_variables)]
-pub trait Handler<T, M: Send> {
-    fn readable(&mut self, event_loop: &mut EventLoop<T, M>, token: Token, hint: event::ReadHint) {
+pub trait Handler {
+    /// The value handed back to `timeout` when a timeout set with
+    /// `EventLoop::timeout` fires.
+    type Timeout;
+
+    /// The messages delivered to `notify`.
+    type Message: Send;
+
+    /// Invoked once per IO event with everything the OS reported for the
+    /// handle registered with `token`, e.g. both read and write readiness
+    /// after a connect completes with data already buffered. Override this
+    /// to handle all directions in one place. `events` is the reported
+    /// readiness, which may include hangups and errors the handle was not
+    /// registered for.
+    ///
+    /// The default implementation calls `readable`, `writable` and `error`,
+    /// in that order, for the kinds of readiness contained in the event.
+    ///
+    /// Returning `HandlerAction::Shutdown` stops the event loop immediately,
+    /// unlike `EventLoop::shutdown` which lets the current iteration finish.
+    fn ready(&mut self, event_loop: &mut EventLoop<Self::Timeout, Self::Message>, token: Token, events: event::EventSet) -> HandlerAction {
+        if events.is_readable() {
+            self.readable(event_loop, token, events.read_hint());
+        }
+
+        if events.is_writable() {
+            self.writable(event_loop, token);
+        }
+
+        if events.is_error() {
+            self.error(event_loop, token);
+        }
+
+        HandlerAction::Continue
+    }
+
+    /// Invoked instead of `ready` for handles registered with
+    /// `EventLoop::register_with_data`, passing the attached `data`.
+    ///
+    /// The default implementation ignores `data` and calls `ready`.
+    fn ready_with_data(&mut self, event_loop: &mut EventLoop<Self::Timeout, Self::Message>, token: Token, events: event::EventSet, data: uint) -> HandlerAction {
+        self.ready(event_loop, token, events)
+    }
+
+    /// Invoked instead of `ready` for handles registered with
+    /// `EventLoop::register_raw`, passing the event as reported by the
+    /// selector so that `IoEvent::raw_data` and `IoEvent::raw_fflags` can be
+    /// inspected.
+    ///
+    /// The default implementation calls `ready` with the event's readiness.
+    fn raw_ready(&mut self, event_loop: &mut EventLoop<Self::Timeout, Self::Message>, token: Token, event: event::IoEvent) -> HandlerAction {
+        self.ready(event_loop, token, event.kind())
+    }
+
+    /// Invoked when the handle registered with `token` is ready for reading.
+    ///
+    /// Readiness is a hint, not a guarantee that data is available. Under
+    /// edge triggering in particular, a read following this notification may
+    /// legitimately return `WouldBlock` (for example when a previous read
+    /// already drained the data that triggered the event). Handlers must treat
+    /// that case as "nothing to do" and may report it with
+    /// `EventLoop::spurious_wakeup` to track how often it happens.
+    ///
+    /// Readiness is also reported when the peer goes away. A read failing
+    /// with an error for which `is_eof` is true means it closed the
+    /// connection cleanly, while one for which `is_connection_reset` is true
+    /// means it aborted it.
+    ///
+    /// Cannot stop the event loop early, see `HandlerAction`.
+    fn readable(&mut self, event_loop: &mut EventLoop<Self::Timeout, Self::Message>, token: Token, hint: event::ReadHint) {
+    }
+
+    /// Invoked when the handle registered with `token` is ready for writing.
+    /// As with `readable`, a subsequent write may still return `WouldBlock`.
+    fn writable(&mut self, event_loop: &mut EventLoop<Self::Timeout, Self::Message>, token: Token) {
+    }
+
+    /// Invoked when the OS reports an error condition on the handle
+    /// registered with `token`. For a listening socket this signals problems
+    /// such as descriptor exhaustion, giving the handler a chance to back off
+    /// instead of spinning on failing accepts.
+    fn error(&mut self, event_loop: &mut EventLoop<Self::Timeout, Self::Message>, token: Token) {
+    }
+
+    /// Invoked with each message sent through an `EventLoopSender` (see
+    /// `EventLoop::channel`) or `EventLoop::send_notify`. The message is moved
+    /// into the handler, in the order it was sent by each sender.
+    fn notify(&mut self, event_loop: &mut EventLoop<Self::Timeout, Self::Message>, msg: Self::Message) {
+    }
+
+    /// Invoked once the event loop was shut down, with the messages that
+    /// were still queued, so that they can be persisted or rejected instead
+    /// of being lost. Messages sent from this callback are kept queued for
+    /// the next `run`.
+    ///
+    /// The default implementation calls `notify` with each message, so a
+    /// handler that shuts down from `notify` must expect further calls.
+    fn notify_drained(&mut self, event_loop: &mut EventLoop<Self::Timeout, Self::Message>, msgs: Vec<Self::Message>) {
+        for msg in msgs.into_iter() {
+            self.notify(event_loop, msg);
+        }
+    }
+
+    /// Invoked when the resolution started by `EventLoop::resolve` with
+    /// `token` completes, with the resolved addresses or a `ResolveFailed`
+    /// error.
+    fn resolved(&mut self, event_loop: &mut EventLoop<Self::Timeout, Self::Message>, token: Token, addrs: MioResult<Vec<SockAddr>>) {
     }
 
-    fn writable(&mut self, event_loop: &mut EventLoop<T, M>, token: Token) {
+    /// Invoked when the connection started by `EventLoop::connect_host` with
+    /// `token` is established, with the connected socket, or once connecting
+    /// to every resolved address failed, with the last error. The socket is
+    /// not registered with the event loop.
+    fn connected(&mut self, event_loop: &mut EventLoop<Self::Timeout, Self::Message>, token: Token, sock: MioResult<TcpSocket>) {
     }
 
-    fn notify(&mut self, event_loop: &mut EventLoop<T, M>, msg: M) {
+    fn timeout(&mut self, event_loop: &mut EventLoop<Self::Timeout, Self::Message>, timeout: Self::Timeout) {
     }
 
-    fn timeout(&mut self, event_loop: &mut EventLoop<T, M>, timeout: T) {
+    /// Invoked when the handle registered with `token` saw no IO events for
+    /// the interval set with `EventLoop::set_idle_timeout`. The idle timeout
+    /// is cleared before this is called.
+    fn idle_timeout(&mut self, event_loop: &mut EventLoop<Self::Timeout, Self::Message>, token: Token) {
     }
 }
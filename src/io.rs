@@ -1,8 +1,62 @@
+use std::cell::Cell;
+use std::slice;
+use std::time::Duration;
 use buf::{Buf, MutBuf};
 use os;
-use error::MioResult;
+use os::event::{EventSet, Interest};
+use error::{MioResult, MioError};
 use self::NonBlock::{Ready, WouldBlock};
 use error::MioErrorKind as mek;
+use net::MSG_NOSIGNAL;
+
+// Bytes moved by the IO helpers below on the current thread. Only counted
+// while an event loop with `io_stats` enabled dispatches events, see
+// `EventLoop::io_stats`.
+struct TrackedBytes {
+    enabled: Cell<bool>,
+    read: Cell<u64>,
+    written: Cell<u64>
+}
+
+thread_local!(static TRACKED: TrackedBytes = TrackedBytes {
+    enabled: Cell::new(false),
+    read: Cell::new(0),
+    written: Cell::new(0)
+})
+
+/// Starts or stops counting the bytes moved by the IO helpers on the current
+/// thread, returning whether they were counted before.
+pub fn track_bytes(enabled: bool) -> bool {
+    TRACKED.with(|t| {
+        let prev = t.enabled.get();
+        t.enabled.set(enabled);
+        prev
+    })
+}
+
+/// Total bytes read and written while tracking was enabled on the current
+/// thread.
+pub fn tracked_bytes() -> (u64, u64) {
+    TRACKED.with(|t| (t.read.get(), t.written.get()))
+}
+
+#[inline]
+fn count_read(cnt: uint) {
+    TRACKED.with(|t| {
+        if t.enabled.get() {
+            t.read.set(t.read.get() + cnt as u64);
+        }
+    });
+}
+
+#[inline]
+fn count_written(cnt: uint) {
+    TRACKED.with(|t| {
+        if t.enabled.get() {
+            t.written.set(t.written.get() + cnt as u64);
+        }
+    });
+}
 
 #[deriving(Show)]
 pub enum NonBlock<T> {
@@ -18,29 +72,191 @@ impl<T> NonBlock<T> {
         }
     }
 
+    pub fn is_would_block(&self) -> bool {
+        self.would_block()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        !self.would_block()
+    }
+
     pub fn unwrap(self) -> T {
         match self {
             Ready(v) => v,
             _ => panic!("would have blocked, no result to take")
         }
     }
+
+    pub fn unwrap_or(self, def: T) -> T {
+        match self {
+            Ready(v) => v,
+            WouldBlock => def
+        }
+    }
+
+    pub fn map<U>(self, f: |T| -> U) -> NonBlock<U> {
+        match self {
+            Ready(v) => Ready(f(v)),
+            WouldBlock => WouldBlock
+        }
+    }
+
+    /// Converts into an `Option`, mapping `WouldBlock` to `None`.
+    pub fn into_option(self) -> Option<T> {
+        match self {
+            Ready(v) => Some(v),
+            WouldBlock => None
+        }
+    }
 }
 
 pub trait IoHandle {
     fn desc(&self) -> &os::IoDesc;
+
+    /// Checks the readiness of the handle for `interest` with a single
+    /// `poll(2)` call, without registering it with an event loop. Waits up
+    /// to `timeout`, or indefinitely if `None`, and returns the ready set,
+    /// which is empty if the timeout expired. Errors and hangups are always
+    /// reported.
+    fn poll_ready(&self, interest: Interest, timeout: Option<Duration>) -> MioResult<EventSet> {
+        os::poll_ready(self.desc(), interest, timeout)
+    }
+
+    /// Closes the handle, returning the error reported by `close(2)`, e.g.
+    /// `EIO`, which is lost when the handle is simply dropped. The
+    /// descriptor is released either way. Deregister the handle from the
+    /// event loop first.
+    ///
+    /// The default only drops the handle and reports no error, for handles
+    /// that do not own a single descriptor.
+    fn close(self) -> MioResult<()> {
+        drop(self);
+        Ok(())
+    }
+}
+
+impl IoHandle for os::IoDesc {
+    fn desc(&self) -> &os::IoDesc {
+        self
+    }
+
+    fn close(self) -> MioResult<()> {
+        os::IoDesc::close(self)
+    }
 }
 
 pub trait IoReader {
     fn read(&self, buf: &mut MutBuf) -> MioResult<NonBlock<uint>>;
     fn read_slice(&self, buf: &mut [u8]) -> MioResult<NonBlock<uint>>;
+
+    /// Drains the handle into the buffer, reading until the read would block,
+    /// the buffer is full or the end of the stream is reached. Returns the
+    /// number of bytes read and whether EOF was hit.
+    ///
+    /// This is the loop required to correctly consume an edge triggered
+    /// readable notification. If the buffer fills up first, there may still
+    /// be data pending and no further notification will be delivered for it.
+    fn read_all(&self, buf: &mut MutBuf) -> MioResult<(uint, bool)> {
+        let mut cnt = 0;
+
+        while buf.has_remaining() {
+            match self.read(buf) {
+                Ok(Ready(n)) => cnt += n,
+                Ok(WouldBlock) => break,
+                Err(e) => {
+                    if e.is_eof() {
+                        return Ok((cnt, true));
+                    }
+
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok((cnt, false))
+    }
+
+    /// Reads into the spare capacity of `buf`, avoiding the cost of zeroing
+    /// the memory beforehand, and extends the length of `buf` by the number
+    /// of bytes read. Only that many bytes are ever exposed; nothing is read
+    /// if `buf` has no spare capacity.
+    fn read_uninit(&self, buf: &mut Vec<u8>) -> MioResult<NonBlock<uint>> {
+        let len = buf.len();
+        let spare = buf.capacity() - len;
+
+        let res = unsafe {
+            let ptr = buf.as_mut_ptr().offset(len as int);
+            self.read_slice(slice::from_raw_mut_buf(&ptr, spare))
+        };
+
+        match res {
+            Ok(Ready(cnt)) => unsafe { buf.set_len(len + cnt) },
+            _ => {}
+        }
+
+        res
+    }
 }
 
 pub trait IoWriter {
     fn write(&self, buf: &mut Buf) -> MioResult<NonBlock<uint>>;
     fn write_slice(&self, buf: &[u8]) -> MioResult<NonBlock<uint>>;
+
+    /// Writes up to `count` bytes of `file`, starting at `offset`, using
+    /// `sendfile(2)` so the data does not pass through userspace. Returns the
+    /// number of bytes written, which may be less than `count`.
+    ///
+    /// Neither `offset` nor the file's own position is advanced, to resume a
+    /// partial transfer on the next writable event call again with `offset`
+    /// increased and `count` decreased by the number of bytes written.
+    ///
+    /// The default implementation fails with an `Unsupported` error, writers
+    /// backed by a descriptor forward to `io::send_file`.
+    fn send_file(&self, file: &os::IoDesc, offset: u64, count: uint) -> MioResult<NonBlock<uint>> {
+        Err(MioError::unsupported())
+    }
+
+    /// Writes the buffer until it is drained or the write would block. The
+    /// buffer is advanced by the bytes written, so on `WouldBlock` call again
+    /// with the same buffer on the next writable event to resume where the
+    /// previous call left off.
+    fn write_all(&self, buf: &mut Buf) -> MioResult<NonBlock<()>> {
+        while buf.has_remaining() {
+            match try!(self.write(buf)) {
+                Ready(_) => {}
+                WouldBlock => return Ok(WouldBlock)
+            }
+        }
+
+        Ok(Ready(()))
+    }
+
+    /// Writes `data` starting at `offset`, returning the number of bytes
+    /// written. As nothing is advanced, the same payload can be written to
+    /// many handles, e.g. when fanning out a message to subscribers: each one
+    /// only keeps its own offset, increased by the bytes written, to resume
+    /// on its next writable event. An `offset` past the end of `data` is an
+    /// `InvalidInput` error.
+    fn write_from_slice_at(&self, data: &[u8], offset: uint) -> MioResult<NonBlock<uint>> {
+        if offset > data.len() {
+            return Err(MioError::invalid_input());
+        }
+
+        self.write_slice(data.slice_from(offset))
+    }
 }
 
 pub trait IoAcceptor<T> {
+    /// Accepts a pending connection.
+    ///
+    /// When the process or system runs out of file descriptors, an error
+    /// for which `MioError::is_fd_limit` returns true is returned. The
+    /// pending connection stays in the backlog, so a level triggered acceptor
+    /// will be notified again immediately and an edge triggered one never
+    /// will. The usual mitigation is to keep a spare descriptor open (for
+    /// example a handle on `/dev/null`): close it, accept and immediately drop
+    /// the connection, then reopen the spare. Alternatively, stop watching the
+    /// acceptor until connections are closed.
     fn accept(&mut self) -> MioResult<NonBlock<T>>;
 }
 
@@ -57,6 +273,10 @@ impl IoHandle for PipeReader {
     fn desc(&self) -> &os::IoDesc {
         &self.desc
     }
+
+    fn close(self) -> MioResult<()> {
+        self.desc.close()
+    }
 }
 
 pub struct PipeWriter {
@@ -67,6 +287,10 @@ impl IoHandle for PipeWriter {
     fn desc(&self) -> &os::IoDesc {
         &self.desc
     }
+
+    fn close(self) -> MioResult<()> {
+        self.desc.close()
+    }
 }
 
 impl IoReader for PipeReader {
@@ -87,6 +311,10 @@ impl IoWriter for PipeWriter {
     fn write_slice(&self, buf: &[u8]) -> MioResult<NonBlock<uint>> {
         write_slice(self, buf)
     }
+
+    fn send_file(&self, file: &os::IoDesc, offset: u64, count: uint) -> MioResult<NonBlock<uint>> {
+        send_file(self, file, offset, count)
+    }
 }
 
 /// Reads the length of the slice supplied by buf.mut_bytes into the buffer
@@ -123,6 +351,7 @@ pub fn write<O: IoHandle>(io: &O, buf: &mut Buf) -> MioResult<NonBlock<uint>> {
 pub fn read_slice<I: IoHandle>(io: & I, buf: &mut [u8]) -> MioResult<NonBlock<uint>> {
     match os::read(io.desc(), buf) {
         Ok(cnt) => {
+            count_read(cnt);
             Ok(Ready(cnt))
         }
         Err(e) => {
@@ -138,7 +367,10 @@ pub fn read_slice<I: IoHandle>(io: & I, buf: &mut [u8]) -> MioResult<NonBlock<ui
 #[inline]
 pub fn write_slice<I: IoHandle>(io: & I, buf: & [u8]) -> MioResult<NonBlock<uint>> {
     match os::write(io.desc(), buf) {
-        Ok(cnt) => { Ok(Ready(cnt)) }
+        Ok(cnt) => {
+            count_written(cnt);
+            Ok(Ready(cnt))
+        }
         Err(e) => {
             match e.kind {
                 mek::WouldBlock => Ok(WouldBlock),
@@ -147,3 +379,193 @@ pub fn write_slice<I: IoHandle>(io: & I, buf: & [u8]) -> MioResult<NonBlock<uint
         }
     }
 }
+
+///writes up to count bytes of the file starting at offset into the handle
+#[inline]
+pub fn send_file<O: IoHandle>(io: &O, file: &os::IoDesc, offset: u64, count: uint) -> MioResult<NonBlock<uint>> {
+    match os::sendfile(io.desc(), file, offset, count) {
+        Ok(cnt) => {
+            count_written(cnt);
+            Ok(Ready(cnt))
+        }
+        Err(e) => {
+            match e.kind {
+                mek::WouldBlock => Ok(WouldBlock),
+                _               => Err(e)
+            }
+        }
+    }
+}
+
+///sends the length of the slice supplied by Buf.bytes on the socket then
+///advances the buffer that many bytes. Unlike `write`, a connection closed by
+///the peer results in an EPIPE error rather than SIGPIPE.
+#[inline]
+pub fn send<O: IoHandle>(io: &O, buf: &mut Buf) -> MioResult<NonBlock<uint>> {
+    let res = send_slice(io, buf.bytes());
+    match res {
+        Ok(Ready(cnt)) => buf.advance(cnt),
+        _              => {}
+    }
+    res
+}
+
+///sends the supplied slice on the socket, see `send`
+#[inline]
+pub fn send_slice<O: IoHandle>(io: &O, buf: &[u8]) -> MioResult<NonBlock<uint>> {
+    match os::send(io.desc(), buf, MSG_NOSIGNAL) {
+        Ok(cnt) => {
+            count_written(cnt);
+            Ok(Ready(cnt))
+        }
+        Err(e) => {
+            match e.kind {
+                mek::WouldBlock => Ok(WouldBlock),
+                _               => Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use buf;
+    use buf::Buf;
+    use os::event;
+    use os::event::EventSet;
+    use error::{MioResult, MioErrorKind};
+    use super::{pipe, IoHandle, IoReader, IoWriter, NonBlock};
+    use super::NonBlock::{Ready, WouldBlock};
+
+    #[test]
+    pub fn test_nonblock_combinators() {
+        assert!(Ready(2u).is_ready());
+        assert!(!Ready(2u).is_would_block());
+        assert!(WouldBlock::<uint>.is_would_block());
+
+        assert_eq!(Ready(2u).map(|n| n * 2).unwrap(), 4);
+        assert!(WouldBlock::<uint>.map(|n| n * 2).is_would_block());
+
+        assert_eq!(Ready(2u).unwrap_or(7), 2);
+        assert_eq!(WouldBlock.unwrap_or(7u), 7);
+
+        assert_eq!(Ready(2u).into_option(), Some(2));
+        assert_eq!(WouldBlock::<uint>.into_option(), None);
+    }
+
+    #[test]
+    pub fn test_write_all_resumes_after_would_block() {
+        let (reader, writer) = pipe().unwrap();
+        let data = Vec::from_elem(256 * 1024, 7u8);
+        let mut buf = buf::wrap(data.as_slice());
+
+        // The pipe fills up before the buffer is drained
+        assert!(writer.write_all(&mut buf).unwrap().is_would_block());
+        assert!(buf.has_remaining());
+
+        let mut rd = [0u8, ..4096];
+        let mut received = 0;
+
+        loop {
+            match reader.read_slice(&mut rd).unwrap() {
+                Ready(cnt) => received += cnt,
+                WouldBlock => {}
+            }
+
+            if writer.write_all(&mut buf).unwrap().is_ready() {
+                break;
+            }
+        }
+
+        assert!(!buf.has_remaining());
+
+        while received < data.len() {
+            received += reader.read_slice(&mut rd).unwrap().unwrap();
+        }
+
+        assert_eq!(received, data.len());
+    }
+
+    #[test]
+    pub fn test_read_uninit() {
+        let (reader, writer) = pipe().unwrap();
+        let mut buf = Vec::with_capacity(16);
+
+        buf.push(b'>');
+        writer.write_slice(b"hello").unwrap();
+
+        assert_eq!(reader.read_uninit(&mut buf).unwrap().unwrap(), 5);
+        assert!(buf.as_slice() == b">hello");
+    }
+
+    #[test]
+    pub fn test_poll_ready() {
+        let (reader, writer) = pipe().unwrap();
+        let timeout = Some(Duration::milliseconds(0));
+
+        assert!(reader.poll_ready(event::READABLE, timeout).unwrap().is_empty());
+        assert_eq!(writer.poll_ready(event::WRITABLE, timeout).unwrap(), EventSet::writable());
+
+        writer.write_slice(b"hello").unwrap();
+
+        assert_eq!(reader.poll_ready(event::READABLE, None).unwrap(), EventSet::readable());
+    }
+
+    #[test]
+    pub fn test_write_from_slice_at() {
+        let (r1, w1) = pipe().unwrap();
+        let (r2, w2) = pipe().unwrap();
+        let payload = b"hello world";
+        let mut buf = [0u8, ..16];
+
+        // The payload is shared, each writer tracks its own offset
+        assert_eq!(w1.write_from_slice_at(payload, 0).unwrap().unwrap(), 11);
+        assert_eq!(w2.write_from_slice_at(payload, 6).unwrap().unwrap(), 5);
+
+        assert_eq!(r1.read_slice(&mut buf).unwrap().unwrap(), 11);
+        assert!(buf.slice_to(11) == payload);
+
+        assert_eq!(r2.read_slice(&mut buf).unwrap().unwrap(), 5);
+        assert!(buf.slice_to(5) == b"world");
+
+        assert!(w1.write_from_slice_at(payload, 12).unwrap_err().kind == MioErrorKind::InvalidInput);
+    }
+
+    // Implements only the required writer methods
+    struct NullWriter;
+
+    impl IoWriter for NullWriter {
+        fn write(&self, buf: &mut Buf) -> MioResult<NonBlock<uint>> {
+            let cnt = buf.remaining();
+            buf.advance(cnt);
+            Ok(Ready(cnt))
+        }
+
+        fn write_slice(&self, buf: &[u8]) -> MioResult<NonBlock<uint>> {
+            Ok(Ready(buf.len()))
+        }
+    }
+
+    #[test]
+    pub fn test_send_file_unsupported() {
+        let (reader, _writer) = pipe().unwrap();
+
+        assert!(NullWriter.send_file(reader.desc(), 0, 16).unwrap_err().is_unsupported());
+    }
+
+    #[test]
+    pub fn test_close() {
+        let (reader, writer) = pipe().unwrap();
+        let mut buf = [0u8, ..8];
+
+        writer.write_slice(b"bye").unwrap();
+        writer.close().unwrap();
+
+        // The data written before closing is still delivered, then EOF
+        assert_eq!(reader.read_slice(&mut buf).unwrap().unwrap(), 3);
+        assert!(reader.read_slice(&mut buf).unwrap_err().is_eof());
+
+        reader.close().unwrap();
+    }
+}
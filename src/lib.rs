@@ -1,11 +1,13 @@
 #![crate_name = "mio"]
 #![feature(globs)]
 #![feature(phase)]
+#![feature(associated_types)]
 #![feature(unsafe_destructor)]
 // While in active dev
 #![allow(dead_code)]
 
 extern crate alloc;
+extern crate libc;
 extern crate nix;
 extern crate time;
 
@@ -23,6 +25,11 @@ pub use error::{
 };
 pub use handler::{
     Handler,
+    HandlerAction,
+};
+pub use framed::{
+    FramedReader,
+    FramedWriter,
 };
 pub use io::{
     pipe,
@@ -41,6 +48,9 @@ pub use event_loop::{
     EventLoopConfig,
     EventLoopResult,
     EventLoopSender,
+    IoStats,
+    NotifyError,
+    RegisteredTokens,
 };
 pub use timer::{
     Timeout,
@@ -50,6 +60,11 @@ pub use os::token::{
 };
 
 pub use os::event;
+pub use os::event::{
+    EventSet,
+    Interest,
+    PollOpt,
+};
 
 pub mod buf;
 pub mod net;
@@ -57,9 +72,11 @@ pub mod util;
 
 mod error;
 mod event_loop;
+mod framed;
 mod handler;
 mod io;
 mod notify;
 mod os;
 mod poll;
+mod resolver;
 mod timer;
@@ -1,11 +1,15 @@
 use std::fmt;
 use std::str::FromStr;
 use std::io::net::ip::SocketAddr as StdSocketAddr;
+use time::Timespec;
 use io::{IoHandle, NonBlock};
-use error::MioResult;
+use io::NonBlock::{Ready, WouldBlock};
+use error::{MioResult, MioError};
 use buf::{Buf, MutBuf};
 use os;
 
+// All uses of the std IP types go through these re-exports so that moving
+// to a different std networking module only touches this file.
 pub use std::io::net::ip::{IpAddr, Port};
 pub use std::io::net::ip::Ipv4Addr as IPv4Addr;
 pub use std::io::net::ip::Ipv6Addr as IPv6Addr;
@@ -14,6 +18,19 @@ use self::SockAddr::{InetAddr,UnixAddr};
 use self::AddressFamily::{Unix,Inet,Inet6};
 
 pub trait Socket : IoHandle {
+    /// Returns the local address of the socket. After `connect`, this shows
+    /// the source address and port of the connection, which are the ones
+    /// passed to `bind_in_place` if the socket was bound first.
+    fn local_addr(&self) -> MioResult<SockAddr> {
+        os::local_addr(self.desc())
+    }
+
+    /// Returns the address family the socket was created with, so that
+    /// family specific options can be picked without tracking it separately.
+    fn address_family(&self) -> MioResult<AddressFamily> {
+        os::socket_family(self.desc())
+    }
+
     fn linger(&self) -> MioResult<uint> {
         os::linger(self.desc())
     }
@@ -22,6 +39,14 @@ pub trait Socket : IoHandle {
         os::set_linger(self.desc(), dur_s)
     }
 
+    fn reuseaddr(&self) -> MioResult<bool> {
+        os::reuseaddr(self.desc())
+    }
+
+    /// Sets `SO_REUSEADDR`. When binding fails with an `AddrInUse` error
+    /// (see `MioError::is_addr_in_use`) because a previous instance left
+    /// connections in TIME_WAIT, setting this before binding allows the
+    /// address to be reused.
     fn set_reuseaddr(&self, val: bool) -> MioResult<()> {
         os::set_reuseaddr(self.desc(), val)
     }
@@ -29,6 +54,101 @@ pub trait Socket : IoHandle {
     fn set_reuseport(&self, val: bool) -> MioResult<()> {
         os::set_reuseport(self.desc(), val)
     }
+
+    fn recv_lowat(&self) -> MioResult<uint> {
+        os::recv_lowat(self.desc())
+    }
+
+    /// Sets `SO_RCVLOWAT`, the minimum number of bytes that must be queued
+    /// for the socket to be reported readable, which saves wakeups for
+    /// partial records. It is advisory: the socket is still readable with
+    /// less data pending once the peer closes the connection or an error
+    /// occurs, and blocking reads may return less when a receive timeout
+    /// expires. Linux honors it for TCP, but not for UDP or Unix sockets.
+    fn set_recv_lowat(&self, bytes: uint) -> MioResult<()> {
+        os::set_recv_lowat(self.desc(), bytes)
+    }
+
+    fn only_v6(&self) -> MioResult<bool> {
+        os::only_v6(self.desc())
+    }
+
+    /// Sets `IPV6_V6ONLY` on an IPv6 socket. When disabled, a socket bound to
+    /// the unspecified address `::` also accepts IPv4 traffic, using
+    /// IPv4-mapped addresses. The default differs between platforms (Linux
+    /// is dual-stack, the BSDs are not), so set it explicitly before binding
+    /// for consistent behavior.
+    fn set_only_v6(&self, val: bool) -> MioResult<()> {
+        os::set_only_v6(self.desc(), val)
+    }
+
+    /// Binds the socket to a network interface (e.g. `eth1`) so that traffic
+    /// is sent and received on it regardless of the routing table. Uses
+    /// `SO_BINDTODEVICE`, which is only available on Linux; an `Unsupported`
+    /// error is returned elsewhere.
+    fn bind_to_device(&self, ifname: &str) -> MioResult<()> {
+        os::bind_to_device(self.desc(), ifname)
+    }
+
+    fn broadcast(&self) -> MioResult<bool> {
+        os::broadcast(self.desc())
+    }
+
+    fn set_broadcast(&self, val: bool) -> MioResult<()> {
+        os::set_broadcast(self.desc(), val)
+    }
+
+    /// Receives data with the given per-call flags, e.g. `MSG_PEEK` to look
+    /// at pending data without consuming it.
+    fn recv_flags(&self, buf: &mut [u8], flags: MsgFlags) -> MioResult<NonBlock<uint>> {
+        match os::recv(self.desc(), buf, flags) {
+            Ok(cnt) => Ok(Ready(cnt)),
+            Err(e) => {
+                if e.is_would_block() {
+                    Ok(WouldBlock)
+                } else {
+                    Err(e)
+                }
+            }
+        }
+    }
+
+    /// Sends data with the given per-call flags. With `MSG_NOSIGNAL`, writing
+    /// to a connection closed by the peer fails with `EPIPE` instead of
+    /// raising `SIGPIPE` (the flag is ignored on OS X, which relies on
+    /// `SO_NOSIGPIPE` instead).
+    fn send_flags(&self, buf: &[u8], flags: MsgFlags) -> MioResult<NonBlock<uint>> {
+        match os::send(self.desc(), buf, flags) {
+            Ok(cnt) => Ok(Ready(cnt)),
+            Err(e) => {
+                if e.is_would_block() {
+                    Ok(WouldBlock)
+                } else {
+                    Err(e)
+                }
+            }
+        }
+    }
+
+    fn tos(&self) -> MioResult<u8> {
+        os::tos(self.desc())
+    }
+
+    /// Sets the TOS byte (DSCP and ECN bits) on outgoing packets. Uses
+    /// `IP_TOS` for IPv4 sockets and `IPV6_TCLASS` for IPv6 sockets, picked
+    /// based on the socket's address family. Unix sockets return an
+    /// `InvalidInput` error.
+    fn set_tos(&self, tos: u8) -> MioResult<()> {
+        os::set_tos(self.desc(), tos)
+    }
+
+    /// Enables kernel receive timestamps, reported in the `timestamp` field
+    /// of `UdpSocket::recv_msg_info`. Uses `SO_TIMESTAMPNS` on Linux and the
+    /// microsecond resolution `SO_TIMESTAMP` elsewhere. The timestamps are
+    /// taken on the `CLOCK_REALTIME` clock.
+    fn set_timestamping(&self, val: bool) -> MioResult<()> {
+        os::set_timestamping(self.desc(), val)
+    }
 }
 
 pub trait MulticastSocket : Socket {
@@ -50,7 +170,105 @@ pub trait UnconnectedSocket {
     fn recv_from(&mut self, buf: &mut MutBuf) -> MioResult<NonBlock<SockAddr>>;
 }
 
+/// Acceptors that can be created from an address, so that servers can be
+/// written once for both TCP and Unix sockets:
+///
+/// ```ignore
+/// fn serve<A: Listen + IoAcceptor<S>, S>(addr: &SockAddr) -> MioResult<()> {
+///     let acceptor: A = try!(Listen::listen_on(addr, 128));
+///     ...
+/// }
+/// ```
+pub trait Listen : IoHandle {
+    /// Binds a new socket to `addr` and starts listening with the given
+    /// backlog. Fails with an `InvalidInput` error if `addr` is not of the
+    /// transport's family.
+    fn listen_on(addr: &SockAddr, backlog: uint) -> MioResult<Self>;
+}
+
+/// Stream sockets that can be created by connecting to an address, the
+/// client side counterpart of `Listen`.
+pub trait Connect : IoHandle {
+    /// Creates a new socket and starts connecting it to `addr`. As with
+    /// `connect`, the connection completes once the socket is writable.
+    /// Fails with an `InvalidInput` error if `addr` is not of the
+    /// transport's family.
+    fn connect_to(addr: &SockAddr) -> MioResult<Self>;
+}
+
+bitflags!(
+    #[deriving(Show)]
+    flags RecvFlags: uint {
+        // The datagram was larger than the buffer and was cut short
+        const TRUNCATED         = 0x001,
+        // Some ancillary data was discarded for lack of space
+        const CONTROL_TRUNCATED = 0x002
+    }
+)
+
+impl RecvFlags {
+    /// Returns true if the received datagram did not fit in the buffer and
+    /// the excess was discarded.
+    #[inline]
+    pub fn truncated(&self) -> bool {
+        self.contains(TRUNCATED)
+    }
+}
+
+bitflags!(
+    #[deriving(Show)]
+    flags MsgFlags: uint {
+        // Do not block, even if the socket is in blocking mode
+        const MSG_DONTWAIT = 0x001,
+        // Do not raise SIGPIPE when the peer closed the connection
+        const MSG_NOSIGNAL = 0x002,
+        // Return data without removing it from the receive queue
+        const MSG_PEEK     = 0x004,
+        // Send or receive out-of-band data
+        const MSG_OOB      = 0x008
+    }
+)
+
+/// Details of a datagram received with `UdpSocket::recv_msg_info`
+#[deriving(Show)]
+pub struct RecvInfo {
+    // Number of bytes received
+    pub len: uint,
+    // Address of the sender
+    pub src: SockAddr,
+    pub flags: RecvFlags,
+    // Local address the datagram was sent to, requires `set_pktinfo`
+    pub dst: Option<IpAddr>,
+    // Index of the interface the datagram arrived on, requires `set_pktinfo`
+    pub ifindex: Option<uint>,
+    // Time the kernel received the datagram, requires `set_timestamping`
+    pub timestamp: Option<Timespec>,
+}
+
+/// State of a TCP connection, see `TcpSocket::connection_state`
+#[deriving(Show, PartialEq, Clone)]
+pub enum ConnState {
+    // The handshake is in progress
+    Connecting,
+    Established,
+    // Not connected, failed to connect or shut down
+    Closed,
+}
+
+/// Halves of a connection closed by `TcpSocket::shutdown` and
+/// `UnixSocket::shutdown`
+#[deriving(Show, PartialEq, Clone)]
+pub enum Shutdown {
+    // Further reads return end of file
+    Read,
+    // Further writes fail, the peer reads end of file once the buffered data
+    // was sent
+    Write,
+    Both,
+}
+
 // Types of sockets
+#[deriving(Show, PartialEq, Clone)]
 pub enum AddressFamily {
     Inet,
     Inet6,
@@ -68,6 +286,31 @@ impl SockAddr {
         addr.map(|a| InetAddr(a.ip, a.port))
     }
 
+    /// Same as `parse`, reporting a malformed address as an `InvalidInput`
+    /// error so that it composes with the rest of the crate's `MioResult`
+    /// based API.
+    pub fn parse_mio(s: &str) -> MioResult<SockAddr> {
+        match SockAddr::parse(s) {
+            Some(addr) => Ok(addr),
+            None => Err(MioError::invalid_input())
+        }
+    }
+
+    /// Builds an IPv4 address from its octets, in network order.
+    pub fn inet4(octets: [u8, ..4], port: Port) -> SockAddr {
+        InetAddr(IPv4Addr(octets[0], octets[1], octets[2], octets[3]), port)
+    }
+
+    /// Builds an IPv6 address from its eight 16 bit segments.
+    pub fn inet6(segments: [u16, ..8], port: Port) -> SockAddr {
+        let s = segments;
+        InetAddr(IPv6Addr(s[0], s[1], s[2], s[3], s[4], s[5], s[6], s[7]), port)
+    }
+
+    pub fn from_ip(ip: IpAddr, port: Port) -> SockAddr {
+        InetAddr(ip, port)
+    }
+
     pub fn family(&self) -> AddressFamily {
         match *self {
             UnixAddr(..) => Unix,
@@ -129,18 +372,22 @@ impl fmt::Show for SockAddr {
 pub enum SocketType {
     Dgram,
     Stream,
+    // Connection oriented, preserving message boundaries
+    SeqPacket,
 }
 
 pub mod tcp {
+    use std::io::timer;
+    use std::time::Duration;
     use os;
-    use error::MioResult;
+    use error::{MioResult, MioError};
     use buf::{Buf, MutBuf};
     use io;
     use io::{IoHandle, IoAcceptor, IoReader, IoWriter, NonBlock};
     use io::NonBlock::{Ready, WouldBlock};
-    use net::{AddressFamily, Socket, SockAddr};
+    use net::{AddressFamily, ConnState, Connect, Listen, Shutdown, Socket, SockAddr};
     use net::SocketType::Stream;
-    use net::AddressFamily::{Inet, Inet6};
+    use net::AddressFamily::{Inet, Inet6, Unix};
 
     #[deriving(Show)]
     pub struct TcpSocket {
@@ -185,12 +432,88 @@ pub mod tcp {
             try!(os::bind(&self.desc, addr))
             Ok(TcpListener { desc: self.desc })
         }
+
+        /// Connects to `addr` using TCP Fast Open, carrying `initial` in the
+        /// SYN. Returns how many bytes of `initial` were accepted by the
+        /// kernel; any remainder (all of it if no Fast Open cookie is cached
+        /// for the peer yet) must be written normally once the socket becomes
+        /// writable. Only supported on Linux, other platforms return an
+        /// `Unsupported` error.
+        pub fn connect_with_data(&self, addr: &SockAddr, initial: &[u8]) -> MioResult<uint> {
+            debug!("socket connect with data; addr={}; len={}", addr, initial.len());
+            os::connect_fastopen(&self.desc, addr, initial)
+        }
+
+        /// Binds the socket without consuming it, so that further options can
+        /// be set before calling `listen` (or `connect`, to pin the source
+        /// address of an outbound connection).
+        pub fn bind_in_place(&self, addr: &SockAddr) -> MioResult<()> {
+            os::bind(&self.desc, addr)
+        }
+
+        /// Starts listening on a socket previously bound with
+        /// `bind_in_place`. The backlog must be non-zero and is clamped to
+        /// the kernel's `SOMAXCONN`.
+        pub fn listen(self, backlog: uint) -> MioResult<TcpAcceptor> {
+            try!(os::listen(&self.desc, backlog));
+            Ok(TcpAcceptor { desc: self.desc })
+        }
+
+        /// Same as `listen`, using the kernel's `SOMAXCONN` as the backlog.
+        pub fn listen_default(self) -> MioResult<TcpAcceptor> {
+            self.listen(os::somaxconn())
+        }
+
+        /// Returns whether the connection is still being established, is
+        /// established or is closed, which helps when debugging connects.
+        /// Derived from `TCP_INFO` on Linux. Elsewhere it is derived from
+        /// `getpeername` and `SO_ERROR`, so a pending socket error is
+        /// consumed, and a socket that was never connected is reported as
+        /// connecting.
+        pub fn connection_state(&self) -> MioResult<ConnState> {
+            os::connection_state(&self.desc)
+        }
+
+        /// Closes one or both halves of the connection without closing the
+        /// socket. Shutting down the write half sends a FIN, signalling the
+        /// end of the request while the response can still be read.
+        pub fn shutdown(&self, how: Shutdown) -> MioResult<()> {
+            os::shutdown(&self.desc, how)
+        }
+
+        /// Closes the socket abortively: any unsent data is discarded and the
+        /// peer receives an RST instead of a FIN, so the connection does not
+        /// linger in TIME_WAIT. Dropping the socket still closes gracefully.
+        pub fn abort(self) -> MioResult<()> {
+            debug!("aborting socket; desc={}", self.desc);
+            try!(os::set_linger_abort(&self.desc));
+
+            // The descriptor is closed when `self` is dropped
+            Ok(())
+        }
+    }
+
+    impl Connect for TcpSocket {
+        fn connect_to(addr: &SockAddr) -> MioResult<TcpSocket> {
+            let sock = match addr.family() {
+                Inet => try!(TcpSocket::v4()),
+                Inet6 => try!(TcpSocket::v6()),
+                Unix => return Err(MioError::invalid_input())
+            };
+
+            try!(sock.connect(addr));
+            Ok(sock)
+        }
     }
 
     impl IoHandle for TcpSocket {
         fn desc(&self) -> &os::IoDesc {
             &self.desc
         }
+
+        fn close(self) -> MioResult<()> {
+            self.desc.close()
+        }
     }
 
     impl IoReader for TcpSocket {
@@ -205,11 +528,15 @@ pub mod tcp {
 
     impl IoWriter for TcpSocket {
         fn write(&self, buf: &mut Buf) -> MioResult<NonBlock<(uint)>> {
-            io::write(self, buf)
+            io::send(self, buf)
         }
 
         fn write_slice(&self, buf: &[u8]) -> MioResult<NonBlock<uint>> {
-            io::write_slice(self, buf)
+            io::send_slice(self, buf)
+        }
+
+        fn send_file(&self, file: &os::IoDesc, offset: u64, count: uint) -> MioResult<NonBlock<uint>> {
+            io::send_file(self, file, offset, count)
         }
     }
 
@@ -222,16 +549,47 @@ pub mod tcp {
     }
 
     impl TcpListener {
+        /// Enables TCP Fast Open for connections accepted by this listener,
+        /// allowing clients to send data with their SYN. `qlen` is the
+        /// maximum number of pending Fast Open requests (Linux); OS X only
+        /// supports turning the feature on or off. Must be called before
+        /// `listen`.
+        pub fn set_fastopen(&self, qlen: uint) -> MioResult<()> {
+            os::set_tcp_fastopen(&self.desc, qlen)
+        }
+
+        /// Defers accepting connections until the client has sent data, so
+        /// that `accept` returns connections with the request already
+        /// readable. On Linux, connections silent for `secs` seconds are
+        /// accepted anyway; FreeBSD installs the "dataready" accept filter,
+        /// which has no timeout, and only allows it once listening (see
+        /// `TcpAcceptor::set_defer_accept`). A value of 0 disables deferral.
+        /// Other platforms return an `Unsupported` error.
+        pub fn set_defer_accept(&self, secs: u32) -> MioResult<()> {
+            os::set_defer_accept(&self.desc, secs)
+        }
+
+        /// Starts listening for connections. The backlog must be non-zero
+        /// and is clamped to the kernel's `SOMAXCONN`.
         pub fn listen(self, backlog: uint) -> MioResult<TcpAcceptor> {
             try!(os::listen(self.desc(), backlog));
             Ok(TcpAcceptor { desc: self.desc })
         }
+
+        /// Same as `listen`, using the kernel's `SOMAXCONN` as the backlog.
+        pub fn listen_default(self) -> MioResult<TcpAcceptor> {
+            self.listen(os::somaxconn())
+        }
     }
 
     impl IoHandle for TcpListener {
         fn desc(&self) -> &os::IoDesc {
             &self.desc
         }
+
+        fn close(self) -> MioResult<()> {
+            self.desc.close()
+        }
     }
 
     #[deriving(Show)]
@@ -240,22 +598,109 @@ pub mod tcp {
     }
 
     impl TcpAcceptor {
+        /// Binds a socket to `addr` and listens on it. Bind to port 0 to let
+        /// the kernel pick a free port, then read it back with
+        /// `Socket::local_addr`.
         pub fn new(addr: &SockAddr, backlog: uint) -> MioResult<TcpAcceptor> {
             let sock = try!(TcpSocket::new(addr.family()));
             let listener = try!(sock.bind(addr));
             listener.listen(backlog)
         }
+
+        /// Creates an acceptor with `SO_REUSEADDR` and `SO_REUSEPORT` set, so
+        /// that one acceptor can be created per event loop for the same
+        /// address. The kernel then balances incoming connections across
+        /// them (Linux); other platforms may deliver all connections to a
+        /// single acceptor.
+        pub fn bind_reuseport(addr: &SockAddr, backlog: uint) -> MioResult<TcpAcceptor> {
+            let sock = try!(TcpSocket::new(addr.family()));
+            try!(sock.set_reuseaddr(true));
+            try!(sock.set_reuseport(true));
+
+            let listener = try!(sock.bind(addr));
+            listener.listen(backlog)
+        }
+
+        /// Creates an acceptor with `SO_REUSEADDR` set, retrying up to
+        /// `attempts` times, `delay_ms` apart, while the address is in use.
+        /// This covers restarts where the previous process still holds the
+        /// listening socket for a moment; `SO_REUSEADDR` alone only allows
+        /// binding over connections left in TIME_WAIT. The thread sleeps
+        /// between attempts, so this is meant for setup rather than for use
+        /// from within an event loop. Other errors are returned right away.
+        pub fn bind_retry(addr: &SockAddr, backlog: uint, attempts: uint, delay_ms: u64) -> MioResult<TcpAcceptor> {
+            if attempts == 0 {
+                return Err(MioError::invalid_input());
+            }
+
+            let mut attempt = 1;
+
+            loop {
+                let sock = try!(TcpSocket::new(addr.family()));
+                try!(sock.set_reuseaddr(true));
+
+                match sock.bind(addr) {
+                    Ok(listener) => return listener.listen(backlog),
+                    Err(e) => {
+                        if !e.is_addr_in_use() || attempt == attempts {
+                            return Err(e);
+                        }
+                    }
+                }
+
+                debug!("address in use, retrying bind; addr={}; attempt={}", addr, attempt);
+
+                timer::sleep(Duration::milliseconds(delay_ms as i64));
+                attempt += 1;
+            }
+        }
+
+        /// Same as `TcpListener::set_defer_accept`, for platforms requiring
+        /// the socket to be listening.
+        pub fn set_defer_accept(&self, secs: u32) -> MioResult<()> {
+            os::set_defer_accept(&self.desc, secs)
+        }
+
+        /// Accepts a connection along with the address of the peer. The
+        /// address is captured by the accept call itself, avoiding the extra
+        /// `getpeername` syscall when it is needed for logging or ACLs.
+        pub fn accept_from(&mut self) -> MioResult<NonBlock<(TcpSocket, SockAddr)>> {
+            match os::accept_from(self.desc()) {
+                Ok((sock, addr)) => Ok(Ready((TcpSocket { desc: sock }, addr))),
+                Err(e) => {
+                    if e.is_would_block() {
+                        return Ok(WouldBlock);
+                    }
+
+                    return Err(e);
+                }
+            }
+        }
     }
 
     impl IoHandle for TcpAcceptor {
         fn desc(&self) -> &os::IoDesc {
             &self.desc
         }
+
+        fn close(self) -> MioResult<()> {
+            self.desc.close()
+        }
     }
 
     impl Socket for TcpAcceptor {
     }
 
+    impl Listen for TcpAcceptor {
+        fn listen_on(addr: &SockAddr, backlog: uint) -> MioResult<TcpAcceptor> {
+            if addr.family() == Unix {
+                return Err(MioError::invalid_input());
+            }
+
+            TcpAcceptor::new(addr, backlog)
+        }
+    }
+
     impl IoAcceptor<TcpSocket> for TcpAcceptor {
         fn accept(&mut self) -> MioResult<NonBlock<TcpSocket>> {
             match os::accept(self.desc()) {
@@ -274,12 +719,14 @@ pub mod tcp {
 
 pub mod udp {
     use os;
-    use error::MioResult;
+    use error::{MioResult, MioError};
     use buf::{Buf, MutBuf};
     use io::{IoHandle, IoReader, IoWriter, NonBlock};
     use io::NonBlock::{Ready, WouldBlock};
     use io;
-    use net::{AddressFamily, Socket, MulticastSocket, SockAddr};
+    use net::{AddressFamily, Socket, MulticastSocket, SockAddr, IpAddr, IPv4Addr, Port};
+    use net::{RecvFlags, RecvInfo};
+    use net::SockAddr::InetAddr;
     use net::SocketType::Dgram;
     use net::AddressFamily::Inet;
     use super::UnconnectedSocket;
@@ -307,17 +754,131 @@ pub mod udp {
             os::connect(&self.desc, addr)
         }
 
+        /// Undoes `connect`, so that the socket receives datagrams from any
+        /// peer again and sending requires a destination. The socket stays
+        /// bound to its local address.
+        pub fn disconnect(&self) -> MioResult<()> {
+            os::disconnect(&self.desc)
+        }
+
         pub fn bound(addr: &SockAddr) -> MioResult<UdpSocket> {
             let sock = try!(UdpSocket::new(addr.family()));
             try!(sock.bind(addr));
             Ok(sock)
         }
+
+        /// Sends the buffer to the limited broadcast address
+        /// (255.255.255.255) on the given port. `SO_BROADCAST` must have been
+        /// enabled with `set_broadcast`, otherwise a `BroadcastDisabled` error
+        /// is returned instead of the opaque `EACCES` the OS would report.
+        pub fn broadcast_to(&mut self, buf: &mut Buf, port: Port) -> MioResult<NonBlock<()>> {
+            if !try!(self.broadcast()) {
+                return Err(MioError::broadcast_disabled());
+            }
+
+            self.send_to(buf, &InetAddr(IPv4Addr(255, 255, 255, 255), port))
+        }
+
+        /// Receives a single datagram into `buf`, returning its length, the
+        /// source address and the message flags, all with one `recvmsg` call.
+        /// Check `RecvFlags::truncated` to detect datagrams that were larger
+        /// than `buf`; the returned length is then `buf.len()`.
+        pub fn recv_msg(&mut self, buf: &mut [u8]) -> MioResult<NonBlock<(uint, SockAddr, RecvFlags)>> {
+            match os::recvmsg(&self.desc, buf) {
+                Ok(res) => Ok(Ready(res)),
+                Err(e) => {
+                    if e.is_would_block() {
+                        Ok(WouldBlock)
+                    } else {
+                        Err(e)
+                    }
+                }
+            }
+        }
+
+        /// Enables reporting of the local address and interface on which
+        /// datagrams are received, see `recv_msg_info`. Uses `IP_PKTINFO` or
+        /// `IPV6_RECVPKTINFO` depending on the socket's address family.
+        pub fn set_pktinfo(&self, val: bool) -> MioResult<()> {
+            os::set_pktinfo(&self.desc, val)
+        }
+
+        /// Same as `recv_msg`, also returning the local destination address
+        /// and interface index of the datagram when `set_pktinfo` is enabled.
+        /// A server bound to the wildcard address can pass `dst` as the
+        /// source of `send_msg` to reply from the address that was contacted.
+        pub fn recv_msg_info(&mut self, buf: &mut [u8]) -> MioResult<NonBlock<RecvInfo>> {
+            match os::recvmsg_info(&self.desc, buf) {
+                Ok(info) => Ok(Ready(info)),
+                Err(e) => {
+                    if e.is_would_block() {
+                        Ok(WouldBlock)
+                    } else {
+                        Err(e)
+                    }
+                }
+            }
+        }
+
+        /// Sends a datagram to `tgt`, optionally from the local address
+        /// `src`. Returns the number of bytes sent.
+        pub fn send_msg(&mut self, buf: &[u8], tgt: &SockAddr, src: Option<&IpAddr>) -> MioResult<NonBlock<uint>> {
+            match os::sendmsg(&self.desc, buf, tgt, src) {
+                Ok(cnt) => Ok(Ready(cnt)),
+                Err(e) => {
+                    if e.is_would_block() {
+                        Ok(WouldBlock)
+                    } else {
+                        Err(e)
+                    }
+                }
+            }
+        }
+
+        /// Sends each buffer as a separate datagram to the address it is
+        /// paired with, in a single `sendmmsg` call. Returns the number of
+        /// datagrams sent, which is less than `msgs.len()` when the socket's
+        /// send buffer fills up part way through. Only supported on Linux.
+        pub fn send_batch(&mut self, msgs: &[(SockAddr, &[u8])]) -> MioResult<NonBlock<uint>> {
+            match os::sendmmsg(&self.desc, msgs) {
+                Ok(cnt) => Ok(Ready(cnt)),
+                Err(e) => {
+                    if e.is_would_block() {
+                        Ok(WouldBlock)
+                    } else {
+                        Err(e)
+                    }
+                }
+            }
+        }
+
+        /// Receives up to `bufs.len()` datagrams, one into each buffer, in a
+        /// single `recvmmsg` call. For every datagram received, its length,
+        /// source address and flags are appended to `out`, in the same order
+        /// as `bufs`. Returns the number of datagrams received. Only
+        /// supported on Linux.
+        pub fn recv_batch(&mut self, bufs: &mut [&mut [u8]], out: &mut Vec<(uint, SockAddr, RecvFlags)>) -> MioResult<NonBlock<uint>> {
+            match os::recvmmsg(&self.desc, bufs, out) {
+                Ok(cnt) => Ok(Ready(cnt)),
+                Err(e) => {
+                    if e.is_would_block() {
+                        Ok(WouldBlock)
+                    } else {
+                        Err(e)
+                    }
+                }
+            }
+        }
     }
 
     impl IoHandle for UdpSocket {
         fn desc(&self) -> &os::IoDesc {
             &self.desc
         }
+
+        fn close(self) -> MioResult<()> {
+            self.desc.close()
+        }
     }
 
     impl Socket for UdpSocket {
@@ -338,19 +899,164 @@ pub mod udp {
 
     impl IoWriter for UdpSocket {
         fn write(&self, buf: &mut Buf) -> MioResult<NonBlock<(uint)>> {
-            io::write(self, buf)
+            io::send(self, buf)
         }
 
         fn write_slice(&self, buf: &[u8]) -> MioResult<NonBlock<uint>> {
-            io::write_slice(self, buf)
+            io::send_slice(self, buf)
+        }
+
+        fn send_file(&self, file: &os::IoDesc, offset: u64, count: uint) -> MioResult<NonBlock<uint>> {
+            io::send_file(self, file, offset, count)
         }
     }
 
     // Unconnected socket sender -- trait unique to sockets
     impl UnconnectedSocket for UdpSocket {
+        /// Sends the buffered bytes as a single datagram. Datagrams are sent
+        /// whole or not at all, so a datagram that does not fit returns a
+        /// `MessageTooBig` error and leaves the buffer untouched.
+        fn send_to(&mut self, buf: &mut Buf, tgt: &SockAddr) -> MioResult<NonBlock<()>> {
+            let len = buf.bytes().len();
+
+            match os::sendto(&self.desc, buf.bytes(), tgt) {
+                Ok(cnt) => {
+                    // Resuming a partially sent datagram would send the rest
+                    // as a separate one
+                    if cnt < len {
+                        return Err(MioError::message_too_big());
+                    }
+
+                    buf.advance(cnt);
+                    Ok(Ready(()))
+                }
+                Err(e) => {
+                    if e.is_would_block() {
+                        Ok(WouldBlock)
+                    } else {
+                        Err(e)
+                    }
+                }
+            }
+        }
+
+        fn recv_from(&mut self, buf: &mut MutBuf) -> MioResult<NonBlock<SockAddr>> {
+            match os::recvfrom(&self.desc, buf.mut_bytes()) {
+                Ok((cnt, saddr)) => {
+                    buf.advance(cnt);
+                    Ok(Ready(saddr))
+                }
+                Err(e) => {
+                    if e.is_would_block() {
+                        Ok(WouldBlock)
+                    } else {
+                        Err(e)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Raw sockets, for protocols other than TCP and UDP, e.g. ICMP.
+///
+/// Creating a raw socket requires privileges, root or `CAP_NET_RAW` on Linux,
+/// otherwise `RawSocket::new` fails with a permission error.
+pub mod raw {
+    use os;
+    use error::{MioResult, MioError};
+    use buf::{Buf, MutBuf};
+    use io::{IoHandle, IoReader, IoWriter, NonBlock};
+    use io::NonBlock::{Ready, WouldBlock};
+    use io;
+    use net::{AddressFamily, Socket, SockAddr};
+    use super::UnconnectedSocket;
+
+    #[deriving(Show)]
+    pub struct RawSocket {
+        desc: os::IoDesc
+    }
+
+    impl RawSocket {
+        /// Creates a raw socket of the given family for the IP protocol
+        /// number `protocol`, e.g. 1 for ICMP. Only `Inet` and `Inet6` are
+        /// supported.
+        ///
+        /// On IPv4, datagrams read from the socket start with the IP header,
+        /// while datagrams written to it only contain the protocol payload.
+        pub fn new(family: AddressFamily, protocol: i32) -> MioResult<RawSocket> {
+            Ok(RawSocket { desc: try!(os::raw_socket(family, protocol)) })
+        }
+
+        pub fn bind(&self, addr: &SockAddr) -> MioResult<()> {
+            os::bind(&self.desc, addr)
+        }
+
+        /// Sets the default destination and restricts received datagrams to
+        /// the ones sent from `addr`. There is no handshake, so this completes
+        /// right away.
+        pub fn connect(&self, addr: &SockAddr) -> MioResult<()> {
+            try!(os::connect(&self.desc, addr));
+            Ok(())
+        }
+
+        /// When enabled, datagrams written to the socket include the IP
+        /// header, e.g. to set the TTL per datagram, and the kernel does not
+        /// prepend its own. Only IPv4 sockets support it, other sockets and
+        /// platforms return an `Unsupported` error.
+        pub fn set_hdrincl(&self, on: bool) -> MioResult<()> {
+            os::set_hdrincl(&self.desc, on)
+        }
+    }
+
+    impl IoHandle for RawSocket {
+        fn desc(&self) -> &os::IoDesc {
+            &self.desc
+        }
+
+        fn close(self) -> MioResult<()> {
+            self.desc.close()
+        }
+    }
+
+    impl Socket for RawSocket {
+    }
+
+    impl IoReader for RawSocket {
+        fn read(&self, buf: &mut MutBuf) -> MioResult<NonBlock<(uint)>> {
+            io::read(self, buf)
+        }
+
+        fn read_slice(&self, buf: &mut[u8]) -> MioResult<NonBlock<uint>> {
+            io::read_slice(self, buf)
+        }
+    }
+
+    impl IoWriter for RawSocket {
+        fn write(&self, buf: &mut Buf) -> MioResult<NonBlock<(uint)>> {
+            io::send(self, buf)
+        }
+
+        fn write_slice(&self, buf: &[u8]) -> MioResult<NonBlock<uint>> {
+            io::send_slice(self, buf)
+        }
+
+        fn send_file(&self, file: &os::IoDesc, offset: u64, count: uint) -> MioResult<NonBlock<uint>> {
+            io::send_file(self, file, offset, count)
+        }
+    }
+
+    impl UnconnectedSocket for RawSocket {
+        /// Sends the buffered bytes as a single datagram, see `UdpSocket`.
         fn send_to(&mut self, buf: &mut Buf, tgt: &SockAddr) -> MioResult<NonBlock<()>> {
+            let len = buf.bytes().len();
+
             match os::sendto(&self.desc, buf.bytes(), tgt) {
                 Ok(cnt) => {
+                    if cnt < len {
+                        return Err(MioError::message_too_big());
+                    }
+
                     buf.advance(cnt);
                     Ok(Ready(()))
                 }
@@ -383,14 +1089,17 @@ pub mod udp {
 }
 
 pub mod pipe {
+    use std::fmt;
+    use std::io::fs;
     use os;
-    use error::MioResult;
+    use error::{MioResult, MioError};
     use buf::{Buf, MutBuf};
     use io;
     use io::{IoHandle, IoAcceptor, IoReader, IoWriter, NonBlock};
     use io::NonBlock::{Ready, WouldBlock};
-    use net::{Socket, SockAddr, SocketType};
-    use net::SocketType::Stream;
+    use net::{Connect, Listen, Shutdown, Socket, SockAddr, SocketType};
+    use net::SockAddr::UnixAddr;
+    use net::SocketType::{Stream, SeqPacket};
     use net::AddressFamily::Unix;
 
     #[deriving(Show)]
@@ -403,6 +1112,14 @@ pub mod pipe {
             UnixSocket::new(Stream)
         }
 
+        /// Creates a `SOCK_SEQPACKET` socket. Like a stream socket it is
+        /// connection oriented, but each write is delivered as a single
+        /// message: a read never returns parts of two messages, and the part
+        /// of a message that does not fit in the read buffer is discarded.
+        pub fn seqpacket() -> MioResult<UnixSocket> {
+            UnixSocket::new(SeqPacket)
+        }
+
         fn new(socket_type: SocketType) -> MioResult<UnixSocket> {
             Ok(UnixSocket { desc: try!(os::socket(Unix, socket_type)) })
         }
@@ -421,9 +1138,94 @@ pub mod pipe {
             Ok(())
         }
 
+        /// Closes one or both halves of the connection without closing the
+        /// socket. Shutting down the write half lets the peer read end of
+        /// file, delimiting a request while the response can still be read.
+        pub fn shutdown(&self, how: Shutdown) -> MioResult<()> {
+            os::shutdown(&self.desc, how)
+        }
+
+        /// Returns the credentials of the connected peer, using
+        /// `SO_PEERCRED` on Linux and `getpeereid` elsewhere. As they are
+        /// captured by the kernel when the connection is established, they
+        /// can be trusted for authorizing local clients.
+        pub fn peer_cred(&self) -> MioResult<PeerCred> {
+            os::peer_cred(&self.desc)
+        }
+
+        /// Binds the socket. When bound to a filesystem path, the socket file
+        /// is removed once the resulting listener (or the acceptor created
+        /// from it) is dropped, see `UnixListener::take_path`.
         pub fn bind(self, addr: &SockAddr) -> MioResult<UnixListener> {
             try!(os::bind(&self.desc, addr))
-            Ok(UnixListener { desc: self.desc })
+
+            let path = match *addr {
+                UnixAddr(ref path) => Some(path.clone()),
+                _ => None
+            };
+
+            Ok(UnixListener {
+                desc: self.desc,
+                path: BoundPath { path: path }
+            })
+        }
+
+        /// Binds the socket, first removing a socket file left at the path by
+        /// a previous process that did not clean up after itself. The file is
+        /// only removed if it is a socket nobody is listening on, so binding
+        /// still fails with `AddrInUse` if another instance is running or the
+        /// path is taken by a regular file.
+        pub fn bind_unlink(self, addr: &SockAddr) -> MioResult<UnixListener> {
+            match *addr {
+                UnixAddr(ref path) => {
+                    if try!(os::unlink_stale_socket(path)) {
+                        debug!("removed stale socket file; path={}", path.display());
+                    }
+                }
+                _ => {}
+            }
+
+            self.bind(addr)
+        }
+
+        /// Binds the socket without consuming it, so that options can still
+        /// be set before calling `listen`. Unlike `bind`, the socket file is
+        /// not removed when the socket is dropped.
+        pub fn bind_in_place(&self, addr: &SockAddr) -> MioResult<()> {
+            os::bind(&self.desc, addr)
+        }
+
+        /// Starts listening on a socket previously bound with
+        /// `bind_in_place`. The backlog must be non-zero and is clamped to
+        /// the kernel's `SOMAXCONN`.
+        pub fn listen(self, backlog: uint) -> MioResult<UnixAcceptor> {
+            try!(os::listen(&self.desc, backlog));
+
+            Ok(UnixAcceptor {
+                desc: self.desc,
+                path: BoundPath { path: None }
+            })
+        }
+    }
+
+    /// Credentials of the process at the other end of a Unix socket
+    #[deriving(Show, Clone, PartialEq)]
+    pub struct PeerCred {
+        // Not available on OS X and the BSDs
+        pub pid: Option<i32>,
+        pub uid: u32,
+        pub gid: u32,
+    }
+
+    impl Connect for UnixSocket {
+        fn connect_to(addr: &SockAddr) -> MioResult<UnixSocket> {
+            if addr.family() != Unix {
+                return Err(MioError::invalid_input());
+            }
+
+            let sock = try!(UnixSocket::stream());
+            try!(sock.connect(addr));
+            Ok(sock)
         }
     }
 
@@ -431,6 +1233,10 @@ pub mod pipe {
         fn desc(&self) -> &os::IoDesc {
             &self.desc
         }
+
+        fn close(self) -> MioResult<()> {
+            self.desc.close()
+        }
     }
 
     impl IoReader for UnixSocket {
@@ -445,26 +1251,75 @@ pub mod pipe {
 
     impl IoWriter for UnixSocket {
         fn write(&self, buf: &mut Buf) -> MioResult<NonBlock<uint>> {
-            io::write(self, buf)
+            io::send(self, buf)
         }
 
         fn write_slice(&self, buf: &[u8]) -> MioResult<NonBlock<uint>> {
-            io::write_slice(self, buf)
+            io::send_slice(self, buf)
+        }
+
+        fn send_file(&self, file: &os::IoDesc, offset: u64, count: uint) -> MioResult<NonBlock<uint>> {
+            io::send_file(self, file, offset, count)
         }
     }
 
     impl Socket for UnixSocket {
     }
 
+    // Path a Unix socket is bound to, the socket file is removed on drop
+    struct BoundPath {
+        path: Option<Path>
+    }
+
+    impl Drop for BoundPath {
+        fn drop(&mut self) {
+            match self.path {
+                Some(ref path) => {
+                    debug!("removing socket file; path={}", path.display());
+                    let _ = fs::unlink(path);
+                }
+                None => {}
+            }
+        }
+    }
+
+    impl fmt::Show for BoundPath {
+        fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+            match self.path {
+                Some(ref path) => write!(fmt, "{}", path.display()),
+                None => write!(fmt, "None")
+            }
+        }
+    }
+
     #[deriving(Show)]
     pub struct UnixListener {
         desc: os::IoDesc,
+        path: BoundPath,
     }
 
     impl UnixListener {
+        /// Returns the path the socket is bound to and stops it from being
+        /// removed when the listener, or the acceptor created from it, is
+        /// dropped.
+        pub fn take_path(&mut self) -> Option<Path> {
+            self.path.path.take()
+        }
+
+        /// Starts listening for connections. The backlog must be non-zero
+        /// and is clamped to the kernel's `SOMAXCONN`.
         pub fn listen(self, backlog: uint) -> MioResult<UnixAcceptor> {
             try!(os::listen(self.desc(), backlog));
-            Ok(UnixAcceptor { desc: self.desc })
+
+            Ok(UnixAcceptor {
+                desc: self.desc,
+                path: self.path
+            })
+        }
+
+        /// Same as `listen`, using the kernel's `SOMAXCONN` as the backlog.
+        pub fn listen_default(self) -> MioResult<UnixAcceptor> {
+            self.listen(os::somaxconn())
         }
     }
 
@@ -472,14 +1327,24 @@ pub mod pipe {
         fn desc(&self) -> &os::IoDesc {
             &self.desc
         }
+
+        fn close(self) -> MioResult<()> {
+            self.desc.close()
+        }
     }
 
     #[deriving(Show)]
     pub struct UnixAcceptor {
         desc: os::IoDesc,
+        path: BoundPath,
     }
 
     impl UnixAcceptor {
+        /// Same as `UnixListener::take_path`.
+        pub fn take_path(&mut self) -> Option<Path> {
+            self.path.path.take()
+        }
+
         pub fn new(addr: &SockAddr, backlog: uint) -> MioResult<UnixAcceptor> {
             let sock = try!(UnixSocket::stream());
             let listener = try!(sock.bind(addr));
@@ -491,11 +1356,25 @@ pub mod pipe {
         fn desc(&self) -> &os::IoDesc {
             &self.desc
         }
+
+        fn close(self) -> MioResult<()> {
+            self.desc.close()
+        }
     }
 
     impl Socket for UnixAcceptor {
     }
 
+    impl Listen for UnixAcceptor {
+        fn listen_on(addr: &SockAddr, backlog: uint) -> MioResult<UnixAcceptor> {
+            if addr.family() != Unix {
+                return Err(MioError::invalid_input());
+            }
+
+            UnixAcceptor::new(addr, backlog)
+        }
+    }
+
     impl IoAcceptor<UnixSocket> for UnixAcceptor {
         fn accept(&mut self) -> MioResult<NonBlock<UnixSocket>> {
             match os::accept(self.desc()) {
@@ -512,3 +1391,21 @@ pub mod pipe {
     }
 }
 
+#[cfg(test)]
+mod test {
+    use super::{SockAddr, IPv4Addr, IPv6Addr};
+
+    #[test]
+    pub fn test_sockaddr_constructors() {
+        fn eq(a: SockAddr, b: SockAddr) -> bool {
+            a.to_std() == b.to_std()
+        }
+
+        assert!(eq(SockAddr::inet4([127, 0, 0, 1], 8080), SockAddr::parse("127.0.0.1:8080").unwrap()));
+        assert!(eq(SockAddr::inet6([0, 0, 0, 0, 0, 0, 0, 1], 8080), SockAddr::parse("[::1]:8080").unwrap()));
+
+        assert!(eq(SockAddr::from_ip(IPv4Addr(10, 0, 0, 1), 53), SockAddr::inet4([10, 0, 0, 1], 53)));
+        assert!(eq(SockAddr::from_ip(IPv6Addr(0xfe80, 0, 0, 0, 0, 0, 0, 1), 53),
+                   SockAddr::inet6([0xfe80, 0, 0, 0, 0, 0, 0, 1], 53)));
+    }
+}
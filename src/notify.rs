@@ -1,5 +1,5 @@
 use std::sync::Arc;
-use std::sync::atomic::{AtomicInt, Relaxed};
+use std::sync::atomic::{AtomicInt, AtomicUint, Relaxed};
 use error::MioResult;
 use io::IoHandle;
 use os;
@@ -11,6 +11,12 @@ const SLEEP: int = -1;
 /// event loop is not currently sleeping, avoid using an OS wake-up strategy
 /// (eventfd, pipe, ...). Backed by a pre-allocated lock free MPMC queue.
 ///
+/// Wakeups are coalesced: the state counter is set to `SLEEP` when the event
+/// loop is about to block, and only the sender that moves it out of `SLEEP`
+/// writes to the awakener. Senders in the same burst only bump the pending
+/// count, so a batch of messages costs a single wakeup syscall. The loop
+/// resets the state when it drains the pending count in `check`.
+///
 /// TODO: Use more efficient wake-up strategy if available
 pub struct Notify<M: Send> {
     inner: Arc<NotifyInner<M>>
@@ -43,6 +49,18 @@ impl<M: Send> Notify<M> {
     pub fn cleanup(&self) {
         self.inner.cleanup();
     }
+
+    // Number of times the awakener was written to
+    #[inline]
+    pub fn wakeups(&self) -> uint {
+        self.inner.wakeups.load(Relaxed)
+    }
+
+    // Number of messages queued and not polled yet
+    #[inline]
+    pub fn len(&self) -> uint {
+        self.inner.len.load(Relaxed)
+    }
 }
 
 impl<M: Send> Clone for Notify<M> {
@@ -55,6 +73,8 @@ impl<M: Send> Clone for Notify<M> {
 
 struct NotifyInner<M> {
     state: AtomicInt,
+    wakeups: AtomicUint,
+    len: AtomicUint,
     queue: BoundedQueue<M>,
     awaken: os::Awakener
 }
@@ -63,6 +83,8 @@ impl<M: Send> NotifyInner<M> {
     fn with_capacity(capacity: uint) -> MioResult<NotifyInner<M>> {
         Ok(NotifyInner {
             state: AtomicInt::new(0),
+            wakeups: AtomicUint::new(0),
+            len: AtomicUint::new(0),
             queue: BoundedQueue::with_capacity(capacity),
             awaken: try!(os::Awakener::new())
         })
@@ -108,14 +130,28 @@ impl<M: Send> NotifyInner<M> {
     }
 
     fn poll(&self) -> Option<M> {
-        self.queue.pop()
+        let ret = self.queue.pop();
+
+        if ret.is_some() {
+            self.len.fetch_sub(1, Relaxed);
+        }
+
+        ret
     }
 
     fn notify(&self, value: M) -> Result<(), M> {
-        // First, push the message onto the queue
-        if !self.queue.push(value) {
-            // TODO: Don't fail
-            panic!("queue full");
+        // Count the message before it can be polled, so that the count never
+        // goes below zero
+        self.len.fetch_add(1, Relaxed);
+
+        // First, push the message onto the queue, handing it back if the
+        // queue is full
+        match self.queue.push(value) {
+            Ok(()) => {}
+            Err(value) => {
+                self.len.fetch_sub(1, Relaxed);
+                return Err(value);
+            }
         }
 
         let mut cur = self.state.load(Relaxed);
@@ -134,6 +170,8 @@ impl<M: Send> NotifyInner<M> {
         }
 
         if cur == SLEEP {
+            self.wakeups.fetch_add(1, Relaxed);
+
             if self.awaken.wakeup().is_err() {
                 // TODO: Don't fail
                 panic!("failed to awaken event loop");
@@ -153,3 +191,66 @@ impl<M: Send> IoHandle for Notify<M> {
         self.inner.awaken.desc()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Notify;
+
+    #[test]
+    pub fn test_wakeups_are_coalesced() {
+        let notify: Notify<uint> = Notify::with_capacity(16).unwrap();
+
+        // Nothing pending, the event loop goes to sleep
+        assert_eq!(notify.check(16, true), 0);
+
+        for i in range(0u, 3) {
+            notify.notify(i).unwrap();
+        }
+
+        // Only the first message of the burst wakes the event loop
+        assert_eq!(notify.wakeups(), 1);
+        assert_eq!(notify.check(16, false), 3);
+
+        // Messages sent while the event loop is awake do not wake it
+        notify.notify(3).unwrap();
+        assert_eq!(notify.wakeups(), 1);
+        assert_eq!(notify.check(16, true), 1);
+
+        notify.cleanup();
+    }
+
+    #[test]
+    pub fn test_len_counts_queued_messages() {
+        let notify: Notify<uint> = Notify::with_capacity(16).unwrap();
+
+        notify.notify(1).unwrap();
+        notify.notify(2).unwrap();
+        assert_eq!(notify.len(), 2);
+
+        // Checking does not dequeue
+        notify.check(16, false);
+        assert_eq!(notify.len(), 2);
+
+        notify.poll().unwrap();
+        assert_eq!(notify.len(), 1);
+
+        notify.poll().unwrap();
+        assert!(notify.poll().is_none());
+        assert_eq!(notify.len(), 0);
+
+        notify.cleanup();
+    }
+
+    #[test]
+    pub fn test_full_queue_hands_back_message() {
+        let notify: Notify<uint> = Notify::with_capacity(2).unwrap();
+
+        notify.notify(1).unwrap();
+        notify.notify(2).unwrap();
+
+        assert_eq!(notify.notify(3), Err(3));
+        assert_eq!(notify.len(), 2);
+
+        notify.cleanup();
+    }
+}
@@ -1,26 +1,29 @@
-use std::mem;
-use nix::fcntl::Fd;
 use nix::sys::epoll::*;
-use nix::unistd::close;
 use error::{MioResult, MioError};
 use os::IoDesc;
 use os::event;
 
 pub struct Selector {
-    epfd: Fd
+    epfd: IoDesc
 }
 
 impl Selector {
     pub fn new() -> MioResult<Selector> {
         let epfd = try!(epoll_create().map_err(MioError::from_sys_error));
 
-        Ok(Selector { epfd: epfd })
+        Ok(Selector { epfd: IoDesc { fd: epfd } })
+    }
+
+    /// The epoll descriptor, which is readable when events are pending. This
+    /// allows a selector to be polled by another one.
+    pub fn desc(&self) -> &IoDesc {
+        &self.epfd
     }
 
     /// Wait for events from the OS
     pub fn select(&mut self, evts: &mut Events, timeout_ms: uint) -> MioResult<()> {
         // Wait for epoll events for at most timeout_ms milliseconds
-        let cnt = try!(epoll_wait(self.epfd, evts.events.as_mut_slice(), timeout_ms)
+        let cnt = try!(epoll_wait(self.epfd.fd, evts.events.as_mut_slice(), timeout_ms)
                            .map_err(MioError::from_sys_error));
 
         evts.len = cnt;
@@ -34,7 +37,7 @@ impl Selector {
             data: token as u64
         };
 
-        epoll_ctl(self.epfd, EpollOp::EpollCtlAdd, io.fd, &info)
+        epoll_ctl(self.epfd.fd, EpollOp::EpollCtlAdd, io.fd, &info)
             .map_err(MioError::from_sys_error)
     }
 
@@ -45,7 +48,19 @@ impl Selector {
             data: token as u64
         };
 
-        epoll_ctl(self.epfd, EpollOp::EpollCtlMod, io.fd, &info)
+        epoll_ctl(self.epfd.fd, EpollOp::EpollCtlMod, io.fd, &info)
+            .map_err(MioError::from_sys_error)
+    }
+
+    /// Register the IO handle with raw epoll event flags, e.g. `EPOLLPRI`,
+    /// which have no `Interest` equivalent.
+    pub fn register_raw(&mut self, io: &IoDesc, token: uint, events: u32) -> MioResult<()> {
+        let info = EpollEvent {
+            events: EpollEventKind::from_bits_truncate(events),
+            data: token as u64
+        };
+
+        epoll_ctl(self.epfd.fd, EpollOp::EpollCtlAdd, io.fd, &info)
             .map_err(MioError::from_sys_error)
     }
 
@@ -59,7 +74,7 @@ impl Selector {
             data: 0
         };
 
-        epoll_ctl(self.epfd, EpollOp::EpollCtlDel, io.fd, &info)
+        epoll_ctl(self.epfd.fd, EpollOp::EpollCtlDel, io.fd, &info)
             .map_err(MioError::from_sys_error)
     }
 }
@@ -79,6 +94,10 @@ fn ioevent_to_epoll(interest: event::Interest, opts: event::PollOpt) -> EpollEve
         kind.insert(EPOLLRDHUP);
     }
 
+    if interest.contains(event::OOB) {
+        kind.insert(EPOLLPRI);
+    }
+
     if opts.contains(event::EDGE) {
         kind.insert(EPOLLET);
     }
@@ -94,22 +113,28 @@ fn ioevent_to_epoll(interest: event::Interest, opts: event::PollOpt) -> EpollEve
     kind
 }
 
-impl Drop for Selector {
-    fn drop(&mut self) {
-        let _ = close(self.epfd);
-    }
-}
+// Number of events returned by a single select when no capacity is given
+const DEFAULT_CAPACITY: uint = 1024;
 
 pub struct Events {
     len: uint,
-    events: [EpollEvent, ..1024]
+    events: Vec<EpollEvent>
 }
 
 impl Events {
     pub fn new() -> Events {
+        Events::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    pub fn with_capacity(capacity: uint) -> Events {
+        let mut events = Vec::with_capacity(capacity);
+
+        // The entries are only read after being filled in by the OS
+        unsafe { events.set_len(capacity); }
+
         Events {
             len: 0,
-            events: unsafe { mem::uninitialized() }
+            events: events
         }
     }
 
@@ -118,6 +143,22 @@ impl Events {
         self.len
     }
 
+    /// Maximum number of events returned by a single select
+    #[inline]
+    pub fn capacity(&self) -> uint {
+        self.events.len()
+    }
+
+    /// Grows the capacity to `capacity`, discarding any pending events.
+    pub fn grow(&mut self, capacity: uint) {
+        let additional = capacity - self.events.len();
+
+        self.len = 0;
+        self.events.reserve_exact(additional);
+
+        unsafe { self.events.set_len(capacity); }
+    }
+
     #[inline]
     pub fn get(&self, idx: uint) -> event::IoEvent {
         if idx >= self.len {
@@ -135,6 +176,10 @@ impl Events {
             kind = kind | event::WRITABLE;
         }
 
+        if epoll.contains(EPOLLPRI) {
+            kind = kind | event::OOB;
+        }
+
         // EPOLLHUP - Usually means a socket error happened
         if epoll.contains(EPOLLERR) {
             kind = kind | event::ERROR;
@@ -146,6 +191,6 @@ impl Events {
 
         let token = self.events[idx].data;
 
-        event::IoEvent::new(kind, token as uint)
+        event::IoEvent::with_raw(kind, token as uint, epoll.bits() as i64, 0)
     }
 }
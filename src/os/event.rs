@@ -1,6 +1,10 @@
 use std::{fmt};
 use os::token::Token;
 
+// Raw kqueue filters and filter flags, see `EventLoop::register_raw`
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub use nix::sys::event::{EventFilter, FilterFlag};
+
 #[deriving(PartialEq, Eq, Clone, PartialOrd, Ord)]
 pub struct PollOpt(uint);
 
@@ -14,6 +18,16 @@ impl PollOpt {
         EDGE | ONESHOT
     }
 
+    #[inline]
+    pub fn level() -> PollOpt {
+        LEVEL
+    }
+
+    #[inline]
+    pub fn oneshot() -> PollOpt {
+        ONESHOT
+    }
+
     #[inline]
     pub fn empty() -> PollOpt {
         PollOpt(0)
@@ -92,6 +106,9 @@ impl fmt::Show for PollOpt {
     }
 }
 
+// ERROR interest is implicit with both epoll and kqueue (errors are reported
+// with EPOLLERR and EV_EOF respectively), registering it is a statement of
+// intent rather than a requirement.
 bitflags!(
     flags Interest: uint {
         const READABLE = 0x001,
@@ -99,10 +116,52 @@ bitflags!(
         const ERROR    = 0x004,
         const HUP      = 0x008,
         const HINTED   = 0x010,
+        // Urgent (out-of-band) data, reported as readable with `OOBHINT`
+        const OOB      = 0x020,
         const ALL      = 0x001 | 0x002 | 0x008  //epoll checks for ERROR no matter what
     }
 )
 
+impl Interest {
+    #[inline]
+    pub fn readable() -> Interest {
+        READABLE
+    }
+
+    #[inline]
+    pub fn writable() -> Interest {
+        WRITABLE
+    }
+
+    #[inline]
+    pub fn error() -> Interest {
+        ERROR
+    }
+
+    /// Interest in the peer hanging up, reported as readable with
+    /// `HUPHINT`. Registered alone, it detects the peer shutting down its
+    /// write half even when no data is pending, without a read returning 0.
+    /// Maps to `EPOLLRDHUP` with epoll and to `EV_EOF` on the read filter
+    /// with kqueue.
+    #[inline]
+    pub fn hup() -> Interest {
+        HUP
+    }
+
+    /// Same as `hup`, named after the epoll flag it maps to.
+    #[inline]
+    pub fn rdhup() -> Interest {
+        HUP
+    }
+
+    /// Interest in urgent data, e.g. TCP data sent with `MSG_OOB`. Maps to
+    /// `EPOLLPRI` with epoll. kqueue flags urgent data on the read filter,
+    /// so there the handle is also reported readable for regular data.
+    #[inline]
+    pub fn oob() -> Interest {
+        OOB
+    }
+}
 
 impl fmt::Show for Interest {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
@@ -112,7 +171,8 @@ impl fmt::Show for Interest {
             (WRITABLE, "Writable"),
             (ERROR,    "Error"),
             (HUP,      "HupHint"),
-            (HINTED,   "Hinted")];
+            (HINTED,   "Hinted"),
+            (OOB,      "Oob")];
 
         for &(flag, msg) in flags.iter() {
             if self.contains(flag) {
@@ -131,7 +191,8 @@ bitflags!(
     flags ReadHint: uint {
         const DATAHINT    = 0x001,
         const HUPHINT     = 0x002,
-        const ERRORHINT   = 0x004
+        const ERRORHINT   = 0x004,
+        const OOBHINT     = 0x008
     }
 )
 
@@ -141,7 +202,8 @@ impl fmt::Show for ReadHint {
         let flags = [
             (DATAHINT, "DataHint"),
             (HUPHINT, "HupHint"),
-            (ERRORHINT, "ErrorHint")];
+            (ERRORHINT, "ErrorHint"),
+            (OOBHINT, "OobHint")];
 
         for &(flag, msg) in flags.iter() {
             if self.contains(flag) {
@@ -156,11 +218,173 @@ impl fmt::Show for ReadHint {
     }
 }
 
+/// The readiness reported for a handle, as opposed to the `Interest` it was
+/// registered with. For instance a handle registered for readable interest
+/// only may be reported hung up or in error.
+#[deriving(PartialEq, Eq, Clone)]
+pub struct EventSet(uint);
+
+const EVENT_READABLE: uint = 0x001;
+const EVENT_WRITABLE: uint = 0x002;
+const EVENT_ERROR: uint    = 0x004;
+const EVENT_HUP: uint      = 0x008;
+const EVENT_OOB: uint      = 0x010;
+const EVENT_ALL: uint      = 0x01f;
+
+impl EventSet {
+    #[inline]
+    pub fn empty() -> EventSet {
+        EventSet(0)
+    }
+
+    #[inline]
+    pub fn readable() -> EventSet {
+        EventSet(EVENT_READABLE)
+    }
+
+    #[inline]
+    pub fn writable() -> EventSet {
+        EventSet(EVENT_WRITABLE)
+    }
+
+    #[inline]
+    pub fn error() -> EventSet {
+        EventSet(EVENT_ERROR)
+    }
+
+    #[inline]
+    pub fn hup() -> EventSet {
+        EventSet(EVENT_HUP)
+    }
+
+    #[inline]
+    pub fn oob() -> EventSet {
+        EventSet(EVENT_OOB)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bits() == 0
+    }
+
+    /// Data can be read, or the peer hung up, which a read reports as end
+    /// of file
+    #[inline]
+    pub fn is_readable(&self) -> bool {
+        self.contains(EventSet::readable()) || self.is_hup() || self.is_oob()
+    }
+
+    #[inline]
+    pub fn is_writable(&self) -> bool {
+        self.contains(EventSet::writable())
+    }
+
+    #[inline]
+    pub fn is_error(&self) -> bool {
+        self.contains(EventSet::error())
+    }
+
+    #[inline]
+    pub fn is_hup(&self) -> bool {
+        self.contains(EventSet::hup())
+    }
+
+    #[inline]
+    pub fn is_oob(&self) -> bool {
+        self.contains(EventSet::oob())
+    }
+
+    /// The hint passed to `Handler::readable` for this readiness, see
+    /// `IoEvent::read_hint`.
+    pub fn read_hint(&self) -> ReadHint {
+        let mut hint = ReadHint::empty();
+
+        if self.is_hup() {
+            hint = hint | HUPHINT
+        }
+
+        if self.contains(EventSet::readable()) {
+            hint = hint | DATAHINT
+        }
+
+        if self.is_error() {
+            hint = hint | ERRORHINT
+        }
+
+        if self.is_oob() {
+            hint = hint | OOBHINT
+        }
+
+        hint
+    }
+
+    #[inline]
+    pub fn contains(&self, other: EventSet) -> bool {
+        (*self & other) == other
+    }
+
+    #[inline]
+    pub fn insert(&mut self, other: EventSet) {
+        *self = *self | other;
+    }
+
+    #[inline]
+    fn bits(&self) -> uint {
+        let EventSet(bits) = *self;
+        bits
+    }
+}
+
+impl BitOr<EventSet, EventSet> for EventSet {
+    #[inline]
+    fn bitor(&self, other: &EventSet) -> EventSet {
+        EventSet(self.bits() | other.bits())
+    }
+}
+
+impl BitAnd<EventSet, EventSet> for EventSet {
+    #[inline]
+    fn bitand(&self, other: &EventSet) -> EventSet {
+        EventSet(self.bits() & other.bits())
+    }
+}
+
+impl Not<EventSet> for EventSet {
+    #[inline]
+    fn not(&self) -> EventSet {
+        EventSet(!self.bits() & EVENT_ALL)
+    }
+}
+
+impl fmt::Show for EventSet {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut one = false;
+        let flags = [
+            (EventSet::readable(), "Readable"),
+            (EventSet::writable(), "Writable"),
+            (EventSet::error(),    "Error"),
+            (EventSet::hup(),      "Hup"),
+            (EventSet::oob(),      "Oob")];
+
+        for &(flag, msg) in flags.iter() {
+            if self.contains(flag) {
+                if one { try!(write!(fmt, " | ")) }
+                try!(write!(fmt, "{}", msg));
+
+                one = true
+            }
+        }
+
+        Ok(())
+    }
+}
 
 #[deriving(Show)]
 pub struct IoEvent {
     kind: Interest,
-    token: Token
+    token: Token,
+    data: i64,
+    fflags: u32
 }
 
 /// IoEvent represents the raw event that the OS-specific selector
@@ -172,9 +396,16 @@ pub struct IoEvent {
 impl IoEvent {
     /// Create a new IoEvent.
     pub fn new(kind: Interest, token: uint) -> IoEvent {
+        IoEvent::with_raw(kind, token, 0, 0)
+    }
+
+    /// Create a new IoEvent carrying the raw data reported by the selector.
+    pub fn with_raw(kind: Interest, token: uint, data: i64, fflags: u32) -> IoEvent {
         IoEvent {
             kind: kind,
-            token: Token(token)
+            token: Token(token),
+            data: data,
+            fflags: fflags
         }
     }
 
@@ -182,6 +413,47 @@ impl IoEvent {
         self.token
     }
 
+    /// The selector specific data of the event. With kqueue, this is the
+    /// kevent `data` field, e.g. the number of bytes available to read or
+    /// the number of expirations of an `EVFILT_TIMER`. With epoll, this is
+    /// the raw `events` mask.
+    pub fn raw_data(&self) -> i64 {
+        self.data
+    }
+
+    /// The kevent `fflags` of the event, e.g. the `NOTE_*` flags of an
+    /// `EVFILT_VNODE` filter. Always 0 with epoll.
+    pub fn raw_fflags(&self) -> u32 {
+        self.fflags
+    }
+
+    /// The full set of readiness reported by this event
+    pub fn kind(&self) -> EventSet {
+        let mut events = EventSet::empty();
+
+        if self.kind.contains(READABLE) {
+            events.insert(EventSet::readable());
+        }
+
+        if self.kind.contains(WRITABLE) {
+            events.insert(EventSet::writable());
+        }
+
+        if self.kind.contains(ERROR) {
+            events.insert(EventSet::error());
+        }
+
+        if self.kind.contains(HUP) {
+            events.insert(EventSet::hup());
+        }
+
+        if self.kind.contains(OOB) {
+            events.insert(EventSet::oob());
+        }
+
+        events
+    }
+
     /// Return an optional hint for a readable  handle. Currently,
     /// this method supports the HupHint, which indicates that the
     /// kernel reported that the remote side hung up. This allows a
@@ -206,12 +478,16 @@ impl IoEvent {
             hint = hint | ERRORHINT
         }
 
+        if self.kind.contains(OOB) {
+            hint = hint | OOBHINT
+        }
+
         hint
     }
 
     /// This event indicated that the  handle is now readable
     pub fn is_readable(&self) -> bool {
-        self.kind.contains(READABLE) || self.kind.contains(HUP)
+        self.kind.contains(READABLE) || self.kind.contains(HUP) || self.kind.contains(OOB)
     }
 
     /// This event indicated that the  handle is now writable
@@ -223,4 +499,31 @@ impl IoEvent {
     pub fn is_error(&self) -> bool {
         self.kind.contains(ERROR)
     }
+
+    /// This event indicated that the peer hung up
+    pub fn is_hup(&self) -> bool {
+        self.kind.contains(HUP)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{EventSet, IoEvent, READABLE, HUP, HINTED, DATAHINT, HUPHINT};
+
+    #[test]
+    pub fn test_event_set() {
+        let events = IoEvent::new(READABLE | HUP | HINTED, 0).kind();
+
+        assert!(events.is_readable());
+        assert!(events.is_hup());
+        assert!(!events.is_writable());
+        assert!(!events.is_error());
+        assert_eq!(events, EventSet::readable() | EventSet::hup());
+        assert_eq!(format!("{}", events), "Readable | Hup");
+        assert_eq!(events.read_hint(), DATAHINT | HUPHINT);
+
+        // Hanging up alone is reported as readable, reading returns EOF
+        assert!(EventSet::hup().is_readable());
+        assert!(IoEvent::new(HINTED, 0).kind().is_empty());
+    }
 }
@@ -1,5 +1,3 @@
-use std::mem;
-use nix::fcntl::Fd;
 use nix::sys::event::*;
 use nix::sys::event::EventFilter::*;
 use error::{MioResult, MioError};
@@ -8,33 +6,54 @@ use os::event;
 use os::event::{IoEvent, Interest, PollOpt};
 
 pub struct Selector {
-    kq: Fd,
+    kq: IoDesc,
     changes: Events
 }
 
 impl Selector {
     pub fn new() -> MioResult<Selector> {
+        let kq = try!(kqueue().map_err(MioError::from_sys_error));
+
         Ok(Selector {
-            kq: try!(kqueue().map_err(MioError::from_sys_error)),
+            kq: IoDesc { fd: kq },
             changes: Events::new()
         })
     }
 
+    /// The kqueue descriptor, which is readable when events are pending. This
+    /// allows a selector to be polled by another one.
+    pub fn desc(&self) -> &IoDesc {
+        &self.kq
+    }
+
+    /// Waits for events, submitting the registration changes queued since the
+    /// last call in the same `kevent` syscall.
     pub fn select(&mut self, evts: &mut Events, timeout_ms: uint) -> MioResult<()> {
-        let cnt = try!(kevent(self.kq, self.changes.as_slice(),
+        let cnt = try!(kevent(self.kq.fd, self.changes.as_slice(),
                               evts.as_mut_slice(), timeout_ms)
                                   .map_err(MioError::from_sys_error));
 
         self.changes.len = 0;
 
         evts.len = cnt;
+        evts.remove_errors();
+
         Ok(())
     }
 
+    /// Queues the filter changes for the handle. Changes are batched and only
+    /// submitted to the kernel on the next `select` (or once the change buffer
+    /// fills up), so a registration is not active until then and any error
+    /// it causes is logged rather than returned.
     pub fn register(&mut self, io: &IoDesc, token: uint, interests: Interest, opts: PollOpt) -> MioResult<()> {
         debug!("registering; token={}; interests={}", token, interests);
 
-        try!(self.ev_register(io, token, EVFILT_READ, interests.contains(event::READABLE), opts));
+        // Urgent data and hangups are flagged on the read filter
+        let read = interests.contains(event::READABLE)
+            || interests.contains(event::OOB)
+            || interests.contains(event::HUP);
+
+        try!(self.ev_register(io, token, EVFILT_READ, read, opts));
         try!(self.ev_register(io, token, EVFILT_WRITE, interests.contains(event::WRITABLE), opts));
 
         Ok(())
@@ -53,6 +72,30 @@ impl Selector {
         Ok(())
     }
 
+    /// Queues a raw filter, e.g. `EVFILT_VNODE` with `NOTE_WRITE` to watch a
+    /// file, or `EVFILT_TIMER` with the period in `data`. `ident` is
+    /// interpreted by the filter (a file descriptor, a timer id, ...). As
+    /// with `register`, the change is submitted on the next `select`.
+    pub fn register_raw(&mut self, ident: uint, token: uint, filter: EventFilter, fflags: FilterFlag, data: i64, opts: PollOpt) -> MioResult<()> {
+        debug!("registering raw filter; ident={}; token={}; filter={}", ident, token, filter);
+
+        let mut flags = EV_ADD | EV_ENABLE;
+
+        if opts.contains(event::EDGE) {
+            flags = flags | EV_CLEAR;
+        }
+
+        if opts.contains(event::ONESHOT) {
+            flags = flags | EV_ONESHOT;
+        }
+
+        self.ev_push_raw(ident, token, filter, flags, fflags, data)
+    }
+
+    pub fn deregister_raw(&mut self, ident: uint, filter: EventFilter) -> MioResult<()> {
+        self.ev_push_raw(ident, 0, filter, EV_DELETE, FilterFlag::empty(), 0)
+    }
+
     fn ev_register(&mut self, io: &IoDesc, token: uint, filter: EventFilter, enable: bool, opts: PollOpt) -> MioResult<()> {
         let mut flags = EV_ADD;
 
@@ -74,38 +117,65 @@ impl Selector {
     }
 
     fn ev_push(&mut self, io: &IoDesc, token: uint, filter: EventFilter, flags: EventFlag) -> MioResult<()> {
+        self.ev_push_raw(io.fd as uint, token, filter, flags, FilterFlag::empty(), 0)
+    }
+
+    fn ev_push_raw(&mut self, ident: uint, token: uint, filter: EventFilter, flags: EventFlag, fflags: FilterFlag, data: i64) -> MioResult<()> {
         try!(self.maybe_flush_changes());
 
         let idx = self.changes.len;
         let ev = &mut self.changes.events[idx];
 
-        ev_set(ev, io.fd as uint, filter, flags, FilterFlag::empty(), token);
+        ev_set(ev, ident, filter, flags, fflags, token);
+        ev.data = data as int;
 
         self.changes.len += 1;
         Ok(())
     }
 
+    /// Submits the queued changes right away, without waiting for events.
+    pub fn submit(&mut self) -> MioResult<()> {
+        try!(kevent(self.kq.fd, self.changes.as_slice(), &mut [], 0)
+                .map_err(MioError::from_sys_error));
+        self.changes.len = 0;
+
+        Ok(())
+    }
+
     fn maybe_flush_changes(&mut self) -> MioResult<()> {
         if self.changes.is_full() {
-            try!(kevent(self.kq, self.changes.as_slice(), &mut [], 0)
-                    .map_err(MioError::from_sys_error));
-            self.changes.len = 0;
+            try!(self.submit());
         }
 
         Ok(())
     }
 }
 
+// Set on read filter events when urgent data is pending, not exposed by nix
+const EV_OOBAND: uint = 0x2000;
+
+// Number of events returned by a single select when no capacity is given
+const DEFAULT_CAPACITY: uint = 1024;
+
 pub struct Events {
     len: uint,
-    events: [KEvent, ..1024]
+    events: Vec<KEvent>
 }
 
 impl Events {
     pub fn new() -> Events {
+        Events::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    pub fn with_capacity(capacity: uint) -> Events {
+        let mut events = Vec::with_capacity(capacity);
+
+        // The entries are only read after being filled in by the OS
+        unsafe { events.set_len(capacity); }
+
         Events {
             len: 0,
-            events: unsafe { mem::uninitialized() }
+            events: events
         }
     }
 
@@ -114,6 +184,22 @@ impl Events {
         self.len
     }
 
+    /// Maximum number of events returned by a single select
+    #[inline]
+    pub fn capacity(&self) -> uint {
+        self.events.len()
+    }
+
+    /// Grows the capacity to `capacity`, discarding any pending events.
+    pub fn grow(&mut self, capacity: uint) {
+        let additional = capacity - self.events.len();
+
+        self.len = 0;
+        self.events.reserve_exact(additional);
+
+        unsafe { self.events.set_len(capacity); }
+    }
+
     // TODO: We will get rid of this eventually in favor of an iterator
     #[inline]
     pub fn get(&self, idx: uint) -> IoEvent {
@@ -131,12 +217,16 @@ impl Events {
 
         let mut kind = event::HINTED;
 
-        if ev.filter == EVFILT_READ {
-            kind = kind | event::READABLE;
-        } else if ev.filter == EVFILT_WRITE {
+        if ev.filter == EVFILT_WRITE {
             kind = kind | event::WRITABLE;
         } else {
-            unimplemented!();
+            // Raw filters registered with `register_raw` are reported as
+            // readable, their details are in the raw data and fflags
+            kind = kind | event::READABLE;
+
+            if ev.filter == EVFILT_READ && (ev.flags.bits() as uint) & EV_OOBAND != 0 {
+                kind = kind | event::OOB;
+            }
         }
 
         if ev.flags.contains(EV_EOF) {
@@ -149,7 +239,26 @@ impl Events {
             }
         }
 
-        IoEvent::new(kind, token)
+        IoEvent::with_raw(kind, token, ev.data as i64, ev.fflags.bits() as u32)
+    }
+
+    // Changes submitted along with a select that fail are reported as
+    // EV_ERROR entries in the event list. They do not represent readiness, so
+    // drop them before the events are handed out.
+    fn remove_errors(&mut self) {
+        let mut i = 0u;
+
+        while i < self.len {
+            if self.events[i].flags.contains(EV_ERROR) {
+                debug!("kevent change failed; ident={}; errno={}",
+                       self.events[i].ident, self.events[i].data);
+
+                self.len -= 1;
+                self.events.swap(i, self.len);
+            } else {
+                i += 1;
+            }
+        }
     }
 
     #[inline]
@@ -5,9 +5,36 @@ use error::{MioResult, MioError};
 const MARK: &'static [u8] = b"0x000x000x000x000x000x000x000x01";
 
 mod nix {
+    pub use nix::errno::SysError;
     pub use nix::sys::eventfd::*;
 }
 
+mod ffi {
+    use libc::{c_int, c_long, time_t};
+
+    #[repr(C)]
+    pub struct timespec {
+        pub tv_sec: time_t,
+        pub tv_nsec: c_long
+    }
+
+    #[repr(C)]
+    pub struct itimerspec {
+        pub it_interval: timespec,
+        pub it_value: timespec
+    }
+
+    pub const CLOCK_MONOTONIC: c_int = 1;
+    pub const TFD_NONBLOCK: c_int = 0o4000;
+    pub const TFD_CLOEXEC: c_int = 0o2000000;
+
+    extern {
+        pub fn timerfd_create(clockid: c_int, flags: c_int) -> c_int;
+        pub fn timerfd_settime(fd: c_int, flags: c_int, new_value: *const itimerspec,
+                               old_value: *mut itimerspec) -> c_int;
+    }
+}
+
 pub struct Awakener {
     eventfd: IoDesc
 }
@@ -45,3 +72,43 @@ fn eventfd() -> MioResult<IoDesc> {
 
     Ok(IoDesc { fd: fd })
 }
+
+/// Creates a non-blocking timerfd on the monotonic clock, expiring once after
+/// `delay_ms`.
+pub fn timerfd(delay_ms: u64) -> MioResult<IoDesc> {
+    let fd = unsafe { ffi::timerfd_create(ffi::CLOCK_MONOTONIC, ffi::TFD_NONBLOCK | ffi::TFD_CLOEXEC) };
+
+    if fd < 0 {
+        return Err(MioError::from_sys_error(nix::SysError::last()));
+    }
+
+    let desc = IoDesc { fd: fd };
+
+    // An all zero value disarms the timer, expire as soon as possible instead
+    let (sec, nsec) = if delay_ms == 0 {
+        (0, 1)
+    } else {
+        (delay_ms / 1_000, (delay_ms % 1_000) * 1_000_000)
+    };
+
+    let spec = ffi::itimerspec {
+        it_interval: ffi::timespec { tv_sec: 0, tv_nsec: 0 },
+        it_value: ffi::timespec { tv_sec: sec as ::libc::time_t, tv_nsec: nsec as ::libc::c_long }
+    };
+
+    if unsafe { ffi::timerfd_settime(desc.fd, 0, &spec, 0 as *mut ffi::itimerspec) } < 0 {
+        return Err(MioError::from_sys_error(nix::SysError::last()));
+    }
+
+    Ok(desc)
+}
+
+/// Reads the number of times the timerfd expired since the last read.
+pub fn timerfd_read(desc: &IoDesc) -> MioResult<u64> {
+    let mut buf = [0u8, ..8];
+
+    try!(read(desc, buf.as_mut_slice()));
+
+    let cnt: u64 = unsafe { mem::transmute(buf) };
+    Ok(cnt)
+}
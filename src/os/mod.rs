@@ -8,7 +8,7 @@ pub use self::kqueue::{Events, Selector};
 pub use self::posix::*;
 
 #[cfg(target_os = "linux")]
-pub use self::linux::Awakener;
+pub use self::linux::{Awakener, timerfd, timerfd_read};
 
 #[cfg(any(target_os = "macos", target_os = "ios"))]
 pub use self::posix::PipeAwakener as Awakener;
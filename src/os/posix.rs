@@ -1,189 +1,1531 @@
-use std::mem;
-use std::num::Int;
+use std::{cmp, i32, mem, ptr};
+use std::num::{FromPrimitive, Int};
+use std::time::Duration;
+use libc::{c_void, off_t, size_t};
+use time::Timespec;
 use error::{MioResult, MioError};
-use net::{AddressFamily, SockAddr, IPv4Addr, SocketType};
-use net::SocketType::{Dgram, Stream};
+use os::event;
+use os::event::{EventSet, Interest};
+use net::{AddressFamily, SockAddr, IPv4Addr, IPv6Addr, SocketType, RecvFlags, RecvInfo, ConnState, Shutdown};
+use net::ConnState::{Connecting, Established, Closed};
+use net::{TRUNCATED, CONTROL_TRUNCATED};
+use net::{MsgFlags, MSG_DONTWAIT, MSG_NOSIGNAL, MSG_PEEK, MSG_OOB};
+use net::pipe::PeerCred;
+use net::SocketType::{Dgram, Stream, SeqPacket};
 use net::SockAddr::{InetAddr, UnixAddr};
 use net::AddressFamily::{Inet, Inet6, Unix};
-pub use std::io::net::ip::IpAddr;
+pub use net::IpAddr;
 
 mod nix {
     pub use nix::c_int;
     pub use nix::fcntl::{Fd, O_NONBLOCK, O_CLOEXEC};
-    pub use nix::errno::EINPROGRESS;
+    pub use nix::errno::{SysError, Errno, UnknownErrno, EAFNOSUPPORT, EAGAIN, ECONNREFUSED, EINPROGRESS, ENOTCONN};
     pub use nix::sys::socket::*;
     pub use nix::unistd::*;
 }
 
+// Bindings for the calls that nix does not expose in a usable form
+mod ffi {
+    use libc::{c_char, c_short, c_uint, c_void, gid_t, off_t, pid_t, size_t, ssize_t, time_t, uid_t};
+    #[cfg(target_os = "linux")]
+    use libc::c_long;
+    #[cfg(not(target_os = "linux"))]
+    use libc::suseconds_t;
+    use super::nix::{c_int, in_addr, sockaddr, socklen_t};
+
+    #[repr(C)]
+    pub struct iovec {
+        pub iov_base: *mut c_void,
+        pub iov_len: size_t
+    }
+
+    #[cfg(target_os = "linux")]
+    #[allow(non_camel_case_types)]
+    pub type controllen_t = size_t;
+
+    #[cfg(not(target_os = "linux"))]
+    #[allow(non_camel_case_types)]
+    pub type controllen_t = socklen_t;
+
+    #[cfg(target_os = "linux")]
+    #[repr(C)]
+    pub struct msghdr {
+        pub msg_name: *mut c_void,
+        pub msg_namelen: socklen_t,
+        pub msg_iov: *mut iovec,
+        pub msg_iovlen: size_t,
+        pub msg_control: *mut c_void,
+        pub msg_controllen: controllen_t,
+        pub msg_flags: c_int
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    #[repr(C)]
+    pub struct msghdr {
+        pub msg_name: *mut c_void,
+        pub msg_namelen: socklen_t,
+        pub msg_iov: *mut iovec,
+        pub msg_iovlen: c_int,
+        pub msg_control: *mut c_void,
+        pub msg_controllen: controllen_t,
+        pub msg_flags: c_int
+    }
+
+    #[cfg(target_os = "linux")]
+    #[repr(C)]
+    pub struct mmsghdr {
+        pub msg_hdr: msghdr,
+        pub msg_len: c_uint
+    }
+
+    #[cfg(target_os = "linux")]
+    pub const MSG_TRUNC: c_int = 0x20;
+    #[cfg(target_os = "linux")]
+    pub const MSG_CTRUNC: c_int = 0x08;
+
+    #[cfg(not(target_os = "linux"))]
+    pub const MSG_TRUNC: c_int = 0x10;
+    #[cfg(not(target_os = "linux"))]
+    pub const MSG_CTRUNC: c_int = 0x20;
+
+    #[cfg(target_os = "linux")]
+    #[repr(C)]
+    pub struct cmsghdr {
+        pub cmsg_len: size_t,
+        pub cmsg_level: c_int,
+        pub cmsg_type: c_int
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    #[repr(C)]
+    pub struct cmsghdr {
+        pub cmsg_len: socklen_t,
+        pub cmsg_level: c_int,
+        pub cmsg_type: c_int
+    }
+
+    #[repr(C)]
+    pub struct in_pktinfo {
+        pub ipi_ifindex: c_int,
+        pub ipi_spec_dst: in_addr,
+        pub ipi_addr: in_addr
+    }
+
+    #[repr(C)]
+    pub struct in6_pktinfo {
+        pub ipi6_addr: [u8, ..16],
+        pub ipi6_ifindex: c_uint
+    }
+
+    #[cfg(target_os = "linux")]
+    pub const IP_PKTINFO: c_int = 8;
+    #[cfg(target_os = "linux")]
+    pub const IP_RECVPKTINFO: c_int = 8;
+    #[cfg(target_os = "linux")]
+    pub const IPV6_RECVPKTINFO: c_int = 49;
+    #[cfg(target_os = "linux")]
+    pub const IPV6_PKTINFO: c_int = 50;
+
+    #[cfg(not(target_os = "linux"))]
+    pub const IP_PKTINFO: c_int = 26;
+    #[cfg(not(target_os = "linux"))]
+    pub const IP_RECVPKTINFO: c_int = 26;
+    #[cfg(not(target_os = "linux"))]
+    pub const IPV6_RECVPKTINFO: c_int = 61;
+    #[cfg(not(target_os = "linux"))]
+    pub const IPV6_PKTINFO: c_int = 46;
+
+    #[cfg(target_os = "linux")]
+    pub const MSG_DONTWAIT: c_int = 0x40;
+    #[cfg(target_os = "linux")]
+    pub const MSG_NOSIGNAL: c_int = 0x4000;
+
+    #[cfg(not(target_os = "linux"))]
+    pub const MSG_DONTWAIT: c_int = 0x80;
+    // Not available, SO_NOSIGPIPE is used instead
+    #[cfg(not(target_os = "linux"))]
+    pub const MSG_NOSIGNAL: c_int = 0;
+
+    #[cfg(target_os = "linux")]
+    pub const SO_TIMESTAMPNS: c_int = 35;
+    #[cfg(not(target_os = "linux"))]
+    pub const SO_TIMESTAMP: c_int = 0x400;
+    #[cfg(not(target_os = "linux"))]
+    pub const SCM_TIMESTAMP: c_int = 2;
+
+    #[cfg(target_os = "linux")]
+    #[repr(C)]
+    pub struct timespec {
+        pub tv_sec: time_t,
+        pub tv_nsec: c_long
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    #[repr(C)]
+    pub struct timeval {
+        pub tv_sec: time_t,
+        pub tv_usec: suseconds_t
+    }
+
+    #[cfg(target_os = "linux")]
+    pub const TCP_INFO: c_int = 11;
+    #[cfg(target_os = "linux")]
+    pub const TCP_ESTABLISHED: u8 = 1;
+    #[cfg(target_os = "linux")]
+    pub const TCP_SYN_SENT: u8 = 2;
+    #[cfg(target_os = "linux")]
+    pub const TCP_SYN_RECV: u8 = 3;
+
+    // Leading fields of `struct tcp_info`, the kernel truncates the rest
+    #[cfg(target_os = "linux")]
+    #[repr(C)]
+    pub struct tcp_info {
+        pub tcpi_state: u8,
+        pub tcpi_ca_state: u8,
+        pub tcpi_retransmits: u8,
+        pub tcpi_probes: u8
+    }
+
+    #[cfg(target_os = "linux")]
+    pub const SO_RCVLOWAT: c_int = 18;
+    #[cfg(not(target_os = "linux"))]
+    pub const SO_RCVLOWAT: c_int = 0x1004;
+
+    #[cfg(target_os = "freebsd")]
+    pub const SO_ACCEPTFILTER: c_int = 0x1000;
+
+    #[cfg(target_os = "linux")]
+    pub const IP_HDRINCL: c_int = 3;
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    pub const IP_HDRINCL: c_int = 2;
+
+    #[cfg(target_os = "freebsd")]
+    #[repr(C)]
+    pub struct accept_filter_arg {
+        pub af_name: [c_char, ..16],
+        pub af_arg: [c_char, ..240]
+    }
+
+    #[repr(C)]
+    pub struct pollfd {
+        pub fd: c_int,
+        pub events: c_short,
+        pub revents: c_short
+    }
+
+    pub const POLLIN: c_short = 0x001;
+    pub const POLLPRI: c_short = 0x002;
+    pub const POLLOUT: c_short = 0x004;
+    pub const POLLERR: c_short = 0x008;
+    pub const POLLHUP: c_short = 0x010;
+
+    pub const SOCK_RAW: c_int = 3;
+
+    pub const F_GETFD: c_int = 1;
+    pub const F_GETFL: c_int = 3;
+    pub const F_SETFL: c_int = 4;
+    pub const F_SETFD: c_int = 2;
+    pub const FD_CLOEXEC: c_int = 1;
+
+    pub const MSG_OOB: c_int = 0x01;
+    pub const MSG_PEEK: c_int = 0x02;
+
+    #[cfg(target_os = "linux")]
+    #[repr(C)]
+    pub struct ucred {
+        pub pid: pid_t,
+        pub uid: uid_t,
+        pub gid: gid_t
+    }
+
+    extern {
+        #[cfg(not(target_os = "linux"))]
+        pub fn getpeereid(fd: c_int, uid: *mut uid_t, gid: *mut gid_t) -> c_int;
+        pub fn unlink(path: *const c_char) -> c_int;
+        pub fn socket(domain: c_int, ty: c_int, protocol: c_int) -> c_int;
+        pub fn fcntl(fd: c_int, cmd: c_int, ...) -> c_int;
+        pub fn send(fd: c_int, buf: *const c_void, len: size_t, flags: c_int) -> ssize_t;
+        pub fn recv(fd: c_int, buf: *mut c_void, len: size_t, flags: c_int) -> ssize_t;
+        pub fn recvmsg(fd: c_int, msg: *mut msghdr, flags: c_int) -> ssize_t;
+        pub fn sendmsg(fd: c_int, msg: *const msghdr, flags: c_int) -> ssize_t;
+        #[cfg(target_os = "linux")]
+        pub fn accept4(fd: c_int, addr: *mut sockaddr, len: *mut socklen_t, flags: c_int) -> c_int;
+        #[cfg(not(target_os = "linux"))]
+        pub fn accept(fd: c_int, addr: *mut sockaddr, len: *mut socklen_t) -> c_int;
+        pub fn sendto(fd: c_int, buf: *const u8, len: size_t, flags: c_int,
+                      addr: *const sockaddr, addrlen: socklen_t) -> ssize_t;
+        pub fn getsockname(fd: c_int, addr: *mut sockaddr, len: *mut socklen_t) -> c_int;
+        pub fn connect(fd: c_int, addr: *const sockaddr, len: socklen_t) -> c_int;
+        pub fn shutdown(fd: c_int, how: c_int) -> c_int;
+        pub fn poll(fds: *mut pollfd, nfds: c_uint, timeout: c_int) -> c_int;
+        #[cfg(target_os = "freebsd")]
+        pub fn setsockopt(fd: c_int, level: c_int, name: c_int, val: *const c_void,
+                          len: socklen_t) -> c_int;
+        #[cfg(not(target_os = "linux"))]
+        pub fn getpeername(fd: c_int, addr: *mut sockaddr, len: *mut socklen_t) -> c_int;
+
+        #[cfg(target_os = "linux")]
+        pub fn sendmmsg(fd: c_int, msgvec: *mut mmsghdr, vlen: c_uint, flags: c_int) -> c_int;
+        #[cfg(target_os = "linux")]
+        pub fn recvmmsg(fd: c_int, msgvec: *mut mmsghdr, vlen: c_uint, flags: c_int,
+                        timeout: *mut c_void) -> c_int;
+
+        #[cfg(target_os = "linux")]
+        pub fn sendfile(out_fd: c_int, in_fd: c_int, offset: *mut off_t, count: size_t) -> ssize_t;
+
+        #[cfg(any(target_os = "macos", target_os = "ios"))]
+        pub fn sendfile(fd: c_int, s: c_int, offset: off_t, len: *mut off_t,
+                        hdtr: *mut c_void, flags: c_int) -> c_int;
+    }
+}
+
 /*
  *
  * ===== Awakener =====
  *
  */
 
-pub struct PipeAwakener {
-    reader: IoDesc,
-    writer: IoDesc
+pub struct PipeAwakener {
+    reader: IoDesc,
+    writer: IoDesc
+}
+
+impl PipeAwakener {
+    pub fn new() -> MioResult<PipeAwakener> {
+        let (rd, wr) = try!(pipe());
+
+        Ok(PipeAwakener {
+            reader: rd,
+            writer: wr
+        })
+    }
+
+    pub fn wakeup(&self) -> MioResult<()> {
+        write(&self.writer, b"0x01")
+            .map(|_| ())
+    }
+
+    pub fn desc(&self) -> &IoDesc {
+        &self.reader
+    }
+
+    pub fn cleanup(&self) {
+        let mut buf: [u8, ..128] = unsafe { mem::uninitialized() };
+
+        loop {
+            // Consume data until all bytes are purged
+            match read(&self.reader, buf.as_mut_slice()) {
+                Ok(_) => {}
+                Err(_) => return
+            }
+        }
+    }
+}
+
+/// Represents the OS's handle to the IO instance. In this case, it is the file
+/// descriptor.
+#[deriving(Show)]
+pub struct IoDesc {
+    pub fd: nix::Fd
+}
+
+impl IoDesc {
+    /// Closes the file descriptor, returning the error reported by
+    /// `close(2)`. The descriptor is released even if an error is returned.
+    pub fn close(self) -> MioResult<()> {
+        let fd = self.fd;
+
+        // Already closed, skip the close on drop
+        unsafe { mem::forget(self) };
+
+        nix::close(fd).map_err(MioError::from_sys_error)
+    }
+}
+
+// Best effort, use `close` to find out whether closing failed
+impl Drop for IoDesc {
+    fn drop(&mut self) {
+        let _ = nix::close(self.fd);
+    }
+}
+
+/*
+ *
+ * ===== Pipes =====
+ *
+ */
+
+pub fn pipe() -> MioResult<(IoDesc, IoDesc)> {
+    let (rd, wr) = try!(nix::pipe2(nix::O_NONBLOCK | nix::O_CLOEXEC)
+                        .map_err(MioError::from_sys_error));
+
+    Ok((IoDesc { fd: rd }, IoDesc { fd: wr }))
+}
+
+/*
+ *
+ * ===== Sockets =====
+ *
+ */
+
+pub fn socket(af: AddressFamily, sock_type: SocketType) -> MioResult<IoDesc> {
+    let family = match af {
+        Inet  => nix::AF_INET,
+        Inet6 => nix::AF_INET6,
+        Unix  => nix::AF_UNIX
+    };
+
+    let socket_type = match sock_type {
+        Dgram     => nix::SOCK_DGRAM,
+        Stream    => nix::SOCK_STREAM,
+        SeqPacket => nix::SOCK_SEQPACKET
+    };
+
+    let desc = IoDesc {
+        fd: try!(nix::socket(family, socket_type, nix::SOCK_NONBLOCK | nix::SOCK_CLOEXEC)
+                    .map_err(MioError::from_sys_error))
+    };
+
+    try!(set_nosigpipe(&desc));
+    Ok(desc)
+}
+
+/// Creates a non-blocking `SOCK_RAW` socket for `protocol`, e.g.
+/// `IPPROTO_ICMP`. nix only creates sockets for the default protocol, so the
+/// flags are set with `fcntl` once the socket exists.
+pub fn raw_socket(af: AddressFamily, protocol: i32) -> MioResult<IoDesc> {
+    let family = match af {
+        Inet  => nix::AF_INET,
+        Inet6 => nix::AF_INET6,
+        Unix  => return Err(MioError::invalid_input())
+    };
+
+    let fd = unsafe { ffi::socket(family, ffi::SOCK_RAW, protocol as nix::c_int) };
+
+    if fd < 0 {
+        return Err(MioError::from_sys_error(nix::SysError::last()));
+    }
+
+    // Closes the socket if any of the following fails
+    let desc = IoDesc { fd: fd };
+
+    try!(set_nonblock_cloexec(&desc));
+
+    try!(set_nosigpipe(&desc));
+    Ok(desc)
+}
+
+// Sockets are written to with MSG_NOSIGNAL where available so that writing to
+// a closed connection fails with EPIPE instead of raising SIGPIPE. Platforms
+// without the flag have a socket option to the same effect.
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn set_nosigpipe(io: &IoDesc) -> MioResult<()> {
+    const SO_NOSIGPIPE: nix::c_int = 0x1022;
+    let v: nix::c_int = 1;
+
+    nix::setsockopt(io.fd, nix::SOL_SOCKET, SO_NOSIGPIPE, &v)
+        .map_err(MioError::from_sys_error)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+fn set_nosigpipe(_io: &IoDesc) -> MioResult<()> {
+    Ok(())
+}
+
+pub fn connect(io: &IoDesc, addr: &SockAddr) -> MioResult<bool> {
+    match nix::connect(io.fd, &from_sockaddr(addr)) {
+        Ok(_) => Ok(true),
+        Err(e) => {
+            match e.kind {
+                nix::EINPROGRESS => Ok(false),
+                _ => Err(MioError::from_sys_error(e))
+            }
+        }
+    }
+}
+
+/// Dissolves the association of a connected datagram socket by connecting it
+/// to an `AF_UNSPEC` address. The local address is kept.
+pub fn disconnect(io: &IoDesc) -> MioResult<()> {
+    let mut addr: nix::sockaddr_storage = unsafe { mem::zeroed() };
+    addr.ss_family = nix::AF_UNSPEC as nix::sa_family_t;
+
+    let res = unsafe {
+        ffi::connect(io.fd, &addr as *const nix::sockaddr_storage as *const nix::sockaddr,
+                     mem::size_of::<nix::sockaddr_storage>() as nix::socklen_t)
+    };
+
+    if res < 0 {
+        let err = nix::SysError::last();
+
+        // The BSDs dissolve the association but still report the address
+        // family as unsupported
+        if err.kind != nix::EAFNOSUPPORT {
+            return Err(MioError::from_sys_error(err));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn bind(io: &IoDesc, addr: &SockAddr) -> MioResult<()> {
+    nix::bind(io.fd, &from_sockaddr(addr))
+        .map_err(MioError::from_sys_error)
+}
+
+/// Removes the Unix socket file at `path` if it was left behind by a process
+/// that is no longer listening on it. Nothing is removed if the path does not
+/// exist, is not a socket, or a listener still accepts connections on it.
+/// Returns whether the file was removed.
+pub fn unlink_stale_socket(path: &Path) -> MioResult<bool> {
+    use std::c_str::ToCStr;
+    use std::io::{fs, FileType};
+
+    let stat = match fs::lstat(path) {
+        Ok(stat) => stat,
+        Err(_) => return Ok(false)
+    };
+
+    // Sockets are reported as an unknown file type, which they share with
+    // character devices. Unlike devices, sockets have no device number.
+    if stat.kind != FileType::Unknown || stat.unstable.rdev != 0 {
+        return Ok(false);
+    }
+
+    // Only a socket nobody listens on refuses connections
+    let probe = try!(socket(Unix, Stream));
+
+    match nix::connect(probe.fd, &from_sockaddr(&UnixAddr(path.clone()))) {
+        Err(ref e) if e.kind == nix::ECONNREFUSED => {}
+        _ => return Ok(false)
+    }
+
+    let res = path.with_c_str(|p| unsafe { ffi::unlink(p) });
+
+    if res < 0 {
+        return Err(MioError::from_sys_error(nix::SysError::last()));
+    }
+
+    Ok(true)
+}
+
+/// Starts listening for connections. A backlog of zero is rejected with an
+/// `InvalidInput` error and larger values are clamped to `somaxconn()`, which
+/// is what the kernel would silently do anyway.
+pub fn listen(io: &IoDesc, backlog: uint) -> MioResult<()> {
+    if backlog == 0 {
+        return Err(MioError::invalid_input());
+    }
+
+    let backlog = cmp::min(backlog, somaxconn());
+
+    nix::listen(io.fd, backlog)
+        .map_err(MioError::from_sys_error)
+}
+
+// Value of SOMAXCONN in the system headers, used when the runtime limit cannot
+// be determined
+const SOMAXCONN: uint = 128;
+
+/// Returns the maximum listen backlog allowed by the kernel. On Linux this is
+/// read from `/proc/sys/net/core/somaxconn`, elsewhere (or if reading fails)
+/// the compile time `SOMAXCONN` value is used.
+#[cfg(target_os = "linux")]
+pub fn somaxconn() -> uint {
+    use std::io::File;
+
+    File::open(&Path::new("/proc/sys/net/core/somaxconn"))
+        .and_then(|mut f| f.read_to_string())
+        .ok()
+        .and_then(|s| from_str::<uint>(s.as_slice().trim()))
+        .unwrap_or(SOMAXCONN)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn somaxconn() -> uint {
+    SOMAXCONN
+}
+
+pub fn accept(io: &IoDesc) -> MioResult<IoDesc> {
+    let desc = IoDesc {
+        fd: try!(nix::accept4(io.fd, nix::SOCK_NONBLOCK | nix::SOCK_CLOEXEC)
+                     .map_err(MioError::from_sys_error))
+    };
+
+    try!(set_nosigpipe(&desc));
+    Ok(desc)
+}
+
+/// Accepts a connection, returning the peer's address as filled in by the
+/// accept call itself, which saves a `getpeername` syscall.
+pub fn accept_from(io: &IoDesc) -> MioResult<(IoDesc, SockAddr)> {
+    let mut addr: nix::sockaddr_storage = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<nix::sockaddr_storage>() as nix::socklen_t;
+
+    let fd = unsafe {
+        accept_raw(io.fd, &mut addr as *mut nix::sockaddr_storage as *mut nix::sockaddr, &mut len)
+    };
+
+    if fd < 0 {
+        return Err(MioError::from_sys_error(nix::SysError::last()));
+    }
+
+    // Take ownership of the descriptor before inspecting the address so that
+    // it is closed if the address is unusable
+    let desc = IoDesc { fd: fd };
+
+    if !cfg!(target_os = "linux") {
+        try!(set_nonblock_cloexec(&desc));
+    }
+
+    let addr = try!(from_sockaddr_storage(&addr, len));
+
+    try!(set_nosigpipe(&desc));
+
+    Ok((desc, to_sockaddr(&addr)))
+}
+
+// Sets the socket flags atomically where `accept4` is available
+#[cfg(target_os = "linux")]
+unsafe fn accept_raw(fd: nix::Fd, addr: *mut nix::sockaddr, len: *mut nix::socklen_t) -> nix::c_int {
+    ffi::accept4(fd, addr, len, (nix::SOCK_NONBLOCK | nix::SOCK_CLOEXEC).bits())
+}
+
+#[cfg(not(target_os = "linux"))]
+unsafe fn accept_raw(fd: nix::Fd, addr: *mut nix::sockaddr, len: *mut nix::socklen_t) -> nix::c_int {
+    ffi::accept(fd, addr, len)
+}
+
+// Makes a descriptor created without the socket flags non-blocking and
+// closed on exec
+fn set_nonblock_cloexec(desc: &IoDesc) -> MioResult<()> {
+    unsafe {
+        let flags = ffi::fcntl(desc.fd, ffi::F_GETFL);
+
+        if flags < 0 || ffi::fcntl(desc.fd, ffi::F_SETFL, flags | nix::O_NONBLOCK.bits()) < 0 {
+            return Err(MioError::from_sys_error(nix::SysError::last()));
+        }
+
+        if ffi::fcntl(desc.fd, ffi::F_SETFD, ffi::FD_CLOEXEC) < 0 {
+            return Err(MioError::from_sys_error(nix::SysError::last()));
+        }
+    }
+
+    Ok(())
+}
+
+#[inline]
+pub fn recvfrom(io: &IoDesc, buf: &mut [u8]) -> MioResult<(uint, SockAddr)> {
+    match nix::recvfrom(io.fd, buf).map_err(MioError::from_sys_error) {
+        Ok((cnt, addr)) => Ok((cnt, to_sockaddr(&addr))),
+        Err(e) => Err(e)
+    }
+}
+
+/// Receives a single message along with its source address and the flags
+/// reported by the OS, such as truncation of a datagram that did not fit in
+/// `buf`.
+pub fn recvmsg(io: &IoDesc, buf: &mut [u8]) -> MioResult<(uint, SockAddr, RecvFlags)> {
+    let (cnt, addr, flags, _) = try!(recvmsg_raw(io, buf, &mut []));
+    Ok((cnt, addr, flags))
+}
+
+/// Enables or disables reporting of the destination address and interface
+/// of received datagrams (`IP_PKTINFO` / `IPV6_RECVPKTINFO`).
+pub fn set_pktinfo(io: &IoDesc, val: bool) -> MioResult<()> {
+    let v: nix::c_int = if val { 1 } else { 0 };
+
+    let (level, opt) = match try!(socket_family(io)) {
+        Inet => (nix::IPPROTO_IP, ffi::IP_RECVPKTINFO),
+        Inet6 => (IPPROTO_IPV6, ffi::IPV6_RECVPKTINFO),
+        Unix => return Err(MioError::invalid_input())
+    };
+
+    nix::setsockopt(io.fd, level, opt, &v)
+        .map_err(MioError::from_sys_error)
+}
+
+/// Enables or disables kernel timestamps on received datagrams, with
+/// nanosecond resolution where available (`SO_TIMESTAMPNS`).
+#[cfg(target_os = "linux")]
+pub fn set_timestamping(io: &IoDesc, val: bool) -> MioResult<()> {
+    let v: nix::c_int = if val { 1 } else { 0 };
+
+    nix::setsockopt(io.fd, nix::SOL_SOCKET, ffi::SO_TIMESTAMPNS, &v)
+        .map_err(MioError::from_sys_error)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_timestamping(io: &IoDesc, val: bool) -> MioResult<()> {
+    let v: nix::c_int = if val { 1 } else { 0 };
+
+    nix::setsockopt(io.fd, nix::SOL_SOCKET, ffi::SO_TIMESTAMP, &v)
+        .map_err(MioError::from_sys_error)
+}
+
+// Large enough for a pktinfo control message of either family along with a
+// receive timestamp
+const CMSG_BUF_LEN: uint = 128;
+
+/// Receives a single message, also returning the local address it was sent to
+/// and the index of the interface it arrived on. These are only known once
+/// enabled with `set_pktinfo`.
+pub fn recvmsg_info(io: &IoDesc, buf: &mut [u8]) -> MioResult<RecvInfo> {
+    let mut control = [0u8, ..CMSG_BUF_LEN];
+    let (cnt, src, flags, clen) = try!(recvmsg_raw(io, buf, &mut control));
+
+    let mut info = RecvInfo {
+        len: cnt,
+        src: src,
+        flags: flags,
+        dst: None,
+        ifindex: None,
+        timestamp: None
+    };
+
+    for (level, ty, data) in Cmsgs::new(control.slice_to(clen)) {
+        if level == nix::IPPROTO_IP && ty == ffi::IP_PKTINFO
+                && data.len() >= mem::size_of::<ffi::in_pktinfo>() {
+            let pktinfo: &ffi::in_pktinfo = unsafe { mem::transmute(data.as_ptr()) };
+
+            info.dst = Some(u32be_to_ipv4(pktinfo.ipi_addr.s_addr));
+            info.ifindex = Some(pktinfo.ipi_ifindex as uint);
+        } else if level == IPPROTO_IPV6 && ty == ffi::IPV6_PKTINFO
+                && data.len() >= mem::size_of::<ffi::in6_pktinfo>() {
+            let pktinfo: &ffi::in6_pktinfo = unsafe { mem::transmute(data.as_ptr()) };
+
+            info.dst = Some(bytes_to_ipv6(&pktinfo.ipi6_addr));
+            info.ifindex = Some(pktinfo.ipi6_ifindex as uint);
+        } else if level == nix::SOL_SOCKET {
+            info.timestamp = cmsg_timestamp(ty, data);
+        }
+    }
+
+    Ok(info)
+}
+
+// Decodes a receive timestamp control message
+#[cfg(target_os = "linux")]
+fn cmsg_timestamp(ty: nix::c_int, data: &[u8]) -> Option<Timespec> {
+    // SCM_TIMESTAMPNS has the same value as the socket option
+    if ty != ffi::SO_TIMESTAMPNS || data.len() < mem::size_of::<ffi::timespec>() {
+        return None;
+    }
+
+    let ts: &ffi::timespec = unsafe { mem::transmute(data.as_ptr()) };
+    Some(Timespec::new(ts.tv_sec as i64, ts.tv_nsec as i32))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn cmsg_timestamp(ty: nix::c_int, data: &[u8]) -> Option<Timespec> {
+    if ty != ffi::SCM_TIMESTAMP || data.len() < mem::size_of::<ffi::timeval>() {
+        return None;
+    }
+
+    let tv: &ffi::timeval = unsafe { mem::transmute(data.as_ptr()) };
+    Some(Timespec::new(tv.tv_sec as i64, (tv.tv_usec as i32) * 1_000))
+}
+
+/// Sends `buf` to `tgt`. When `src` is given, the datagram is sent from that
+/// local address, which must be one of the host's addresses; this lets a
+/// socket bound to the wildcard address reply from the address a request was
+/// received on.
+pub fn sendmsg(io: &IoDesc, buf: &[u8], tgt: &SockAddr, src: Option<&IpAddr>) -> MioResult<uint> {
+    let addr = from_sockaddr(tgt);
+    let (name, namelen) = sockaddr_ptr(&addr);
+    let mut control = [0u8, ..CMSG_BUF_LEN];
+
+    let mut iov = ffi::iovec {
+        iov_base: buf.as_ptr() as *mut c_void,
+        iov_len: buf.len() as size_t
+    };
+
+    let mut msg: ffi::msghdr = unsafe { mem::zeroed() };
+    msg.msg_name = name as *mut c_void;
+    msg.msg_namelen = namelen;
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+
+    match src {
+        Some(&IPv4Addr(a, b, c, d)) => {
+            let pktinfo = ffi::in_pktinfo {
+                ipi_ifindex: 0,
+                ipi_spec_dst: ipv4_to_inaddr(a, b, c, d),
+                ipi_addr: nix::in_addr { s_addr: 0 }
+            };
+
+            let len = cmsg_put(&mut control, nix::IPPROTO_IP, ffi::IP_PKTINFO, &pktinfo);
+            msg.msg_control = control.as_mut_ptr() as *mut c_void;
+            msg.msg_controllen = len as ffi::controllen_t;
+        }
+        Some(&IPv6Addr(a, b, c, d, e, f, g, h)) => {
+            let pktinfo = ffi::in6_pktinfo {
+                ipi6_addr: ipv6_to_bytes([a, b, c, d, e, f, g, h]),
+                ipi6_ifindex: 0
+            };
+
+            let len = cmsg_put(&mut control, IPPROTO_IPV6, ffi::IPV6_PKTINFO, &pktinfo);
+            msg.msg_control = control.as_mut_ptr() as *mut c_void;
+            msg.msg_controllen = len as ffi::controllen_t;
+        }
+        None => {}
+    }
+
+    let res = unsafe { ffi::sendmsg(io.fd, &msg, ffi::MSG_DONTWAIT | ffi::MSG_NOSIGNAL) };
+
+    if res < 0 {
+        return Err(MioError::from_sys_error(nix::SysError::last()));
+    }
+
+    Ok(res as uint)
+}
+
+// Alignment of control messages and their payload
+#[cfg(target_os = "linux")]
+const CMSG_ALIGN_TO: uint = 8;
+#[cfg(not(target_os = "linux"))]
+const CMSG_ALIGN_TO: uint = 4;
+
+#[inline]
+fn cmsg_align(len: uint) -> uint {
+    (len + CMSG_ALIGN_TO - 1) & !(CMSG_ALIGN_TO - 1)
+}
+
+// Writes a single control message carrying `data` at the start of `buf`,
+// returning the number of bytes used
+fn cmsg_put<T>(buf: &mut [u8], level: nix::c_int, ty: nix::c_int, data: &T) -> uint {
+    let hdr_len = cmsg_align(mem::size_of::<ffi::cmsghdr>());
+    let data_len = mem::size_of::<T>();
+    let space = hdr_len + cmsg_align(data_len);
+
+    assert!(space <= buf.len(), "control message buffer too small");
+
+    unsafe {
+        let hdr: &mut ffi::cmsghdr = mem::transmute(buf.as_mut_ptr());
+        hdr.cmsg_len = (hdr_len + data_len) as ffi::controllen_t;
+        hdr.cmsg_level = level;
+        hdr.cmsg_type = ty;
+
+        ptr::copy_nonoverlapping_memory(
+            buf.as_mut_ptr().offset(hdr_len as int),
+            data as *const T as *const u8,
+            data_len);
+    }
+
+    space
+}
+
+// Iterates over the control messages in a buffer filled in by recvmsg,
+// yielding the level, type and payload of each
+struct Cmsgs<'a> {
+    buf: &'a [u8]
+}
+
+impl<'a> Cmsgs<'a> {
+    fn new(buf: &'a [u8]) -> Cmsgs<'a> {
+        Cmsgs { buf: buf }
+    }
+}
+
+impl<'a> Iterator<(nix::c_int, nix::c_int, &'a [u8])> for Cmsgs<'a> {
+    fn next(&mut self) -> Option<(nix::c_int, nix::c_int, &'a [u8])> {
+        let hdr_len = cmsg_align(mem::size_of::<ffi::cmsghdr>());
+
+        if self.buf.len() < hdr_len {
+            return None;
+        }
+
+        let hdr: &ffi::cmsghdr = unsafe { mem::transmute(self.buf.as_ptr()) };
+        let len = hdr.cmsg_len as uint;
+
+        if len < hdr_len || len > self.buf.len() {
+            return None;
+        }
+
+        let data = self.buf.slice(hdr_len, len);
+        let next = cmp::min(cmsg_align(len), self.buf.len());
+
+        self.buf = self.buf.slice_from(next);
+
+        Some((hdr.cmsg_level, hdr.cmsg_type, data))
+    }
+}
+
+// Calls recvmsg with the given buffer for ancillary data. Returns the number
+// of bytes received, the source address, the message flags and the length of
+// the ancillary data received.
+fn recvmsg_raw(io: &IoDesc, buf: &mut [u8], control: &mut [u8]) -> MioResult<(uint, SockAddr, RecvFlags, uint)> {
+    let mut addr: nix::sockaddr_storage = unsafe { mem::zeroed() };
+
+    let mut iov = ffi::iovec {
+        iov_base: buf.as_mut_ptr() as *mut c_void,
+        iov_len: buf.len() as size_t
+    };
+
+    let mut msg: ffi::msghdr = unsafe { mem::zeroed() };
+    msg.msg_name = &mut addr as *mut nix::sockaddr_storage as *mut c_void;
+    msg.msg_namelen = mem::size_of::<nix::sockaddr_storage>() as nix::socklen_t;
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+
+    if !control.is_empty() {
+        msg.msg_control = control.as_mut_ptr() as *mut c_void;
+        msg.msg_controllen = control.len() as ffi::controllen_t;
+    }
+
+    let res = unsafe { ffi::recvmsg(io.fd, &mut msg, nix::MSG_DONTWAIT.bits()) };
+
+    if res < 0 {
+        return Err(MioError::from_sys_error(nix::SysError::last()));
+    }
+
+    let addr = try!(from_sockaddr_storage(&addr, msg.msg_namelen));
+
+    Ok((res as uint, to_sockaddr(&addr), to_recv_flags(msg.msg_flags), msg.msg_controllen as uint))
+}
+
+fn to_recv_flags(msg_flags: nix::c_int) -> RecvFlags {
+    let mut flags = RecvFlags::empty();
+
+    if msg_flags & ffi::MSG_TRUNC != 0 {
+        flags.insert(TRUNCATED);
+    }
+
+    if msg_flags & ffi::MSG_CTRUNC != 0 {
+        flags.insert(CONTROL_TRUNCATED);
+    }
+
+    flags
+}
+
+/// Sends each buffer as a separate datagram to the address it is paired with,
+/// using a single `sendmmsg` call. Returns the number of datagrams sent,
+/// which may be less than `msgs.len()`.
+#[cfg(target_os = "linux")]
+pub fn sendmmsg(io: &IoDesc, msgs: &[(SockAddr, &[u8])]) -> MioResult<uint> {
+    if msgs.is_empty() {
+        return Ok(0);
+    }
+
+    let addrs: Vec<nix::SockAddr> = msgs.iter()
+        .map(|&(ref addr, _)| from_sockaddr(addr))
+        .collect();
+
+    let mut iovs: Vec<ffi::iovec> = msgs.iter()
+        .map(|&(_, buf)| ffi::iovec {
+            iov_base: buf.as_ptr() as *mut c_void,
+            iov_len: buf.len() as size_t
+        })
+        .collect();
+
+    let mut hdrs: Vec<ffi::mmsghdr> = Vec::with_capacity(msgs.len());
+
+    for (addr, iov) in addrs.iter().zip(iovs.iter_mut()) {
+        let (name, namelen) = sockaddr_ptr(addr);
+        let mut hdr: ffi::mmsghdr = unsafe { mem::zeroed() };
+
+        hdr.msg_hdr.msg_name = name as *mut c_void;
+        hdr.msg_hdr.msg_namelen = namelen;
+        hdr.msg_hdr.msg_iov = iov;
+        hdr.msg_hdr.msg_iovlen = 1;
+
+        hdrs.push(hdr);
+    }
+
+    let res = unsafe {
+        ffi::sendmmsg(io.fd, hdrs.as_mut_ptr(), hdrs.len() as ::libc::c_uint,
+                      ffi::MSG_DONTWAIT | ffi::MSG_NOSIGNAL)
+    };
+
+    if res < 0 {
+        return Err(MioError::from_sys_error(nix::SysError::last()));
+    }
+
+    Ok(res as uint)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn sendmmsg(_io: &IoDesc, _msgs: &[(SockAddr, &[u8])]) -> MioResult<uint> {
+    Err(MioError::unsupported())
+}
+
+/// Receives up to `bufs.len()` datagrams, one per buffer, using a single
+/// `recvmmsg` call. The length, source address and flags of each datagram
+/// are appended to `out`. Returns the number of datagrams received.
+#[cfg(target_os = "linux")]
+pub fn recvmmsg(io: &IoDesc, bufs: &mut [&mut [u8]], out: &mut Vec<(uint, SockAddr, RecvFlags)>) -> MioResult<uint> {
+    if bufs.is_empty() {
+        return Ok(0);
+    }
+
+    let mut addrs: Vec<nix::sockaddr_storage> = Vec::from_fn(bufs.len(), |_| unsafe { mem::zeroed() });
+
+    let mut iovs: Vec<ffi::iovec> = bufs.iter_mut()
+        .map(|buf| ffi::iovec {
+            iov_base: buf.as_mut_ptr() as *mut c_void,
+            iov_len: buf.len() as size_t
+        })
+        .collect();
+
+    let mut hdrs: Vec<ffi::mmsghdr> = Vec::with_capacity(bufs.len());
+
+    for (addr, iov) in addrs.iter_mut().zip(iovs.iter_mut()) {
+        let mut hdr: ffi::mmsghdr = unsafe { mem::zeroed() };
+
+        hdr.msg_hdr.msg_name = addr as *mut nix::sockaddr_storage as *mut c_void;
+        hdr.msg_hdr.msg_namelen = mem::size_of::<nix::sockaddr_storage>() as nix::socklen_t;
+        hdr.msg_hdr.msg_iov = iov;
+        hdr.msg_hdr.msg_iovlen = 1;
+
+        hdrs.push(hdr);
+    }
+
+    let res = unsafe {
+        ffi::recvmmsg(io.fd, hdrs.as_mut_ptr(), hdrs.len() as ::libc::c_uint,
+                      ffi::MSG_DONTWAIT, ptr::null_mut())
+    };
+
+    if res < 0 {
+        return Err(MioError::from_sys_error(nix::SysError::last()));
+    }
+
+    for (addr, hdr) in addrs.iter().zip(hdrs.iter()).take(res as uint) {
+        let src = try!(from_sockaddr_storage(addr, hdr.msg_hdr.msg_namelen));
+
+        out.push((hdr.msg_len as uint, to_sockaddr(&src), to_recv_flags(hdr.msg_hdr.msg_flags)));
+    }
+
+    Ok(res as uint)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn recvmmsg(_io: &IoDesc, _bufs: &mut [&mut [u8]], _out: &mut Vec<(uint, SockAddr, RecvFlags)>) -> MioResult<uint> {
+    Err(MioError::unsupported())
+}
+
+#[inline]
+pub fn sendto(io: &IoDesc, buf: &[u8], tgt: &SockAddr) -> MioResult<uint> {
+    let addr = from_sockaddr(tgt);
+    let (ptr, len) = sockaddr_ptr(&addr);
+
+    let res = unsafe {
+        ffi::sendto(io.fd, buf.as_ptr(), buf.len() as size_t,
+                    ffi::MSG_DONTWAIT | ffi::MSG_NOSIGNAL, ptr, len)
+    };
+
+    if res < 0 {
+        return Err(MioError::from_sys_error(nix::SysError::last()));
+    }
+
+    Ok(res as uint)
+}
+
+/// Opens a connection with TCP Fast Open, sending `buf` along with the SYN.
+/// Returns the number of bytes the kernel accepted. Zero means that the
+/// connection is in progress without any of the data (e.g. no Fast Open
+/// cookie is cached for the peer yet), in which case the payload must be
+/// written again once the socket becomes writable.
+#[cfg(target_os = "linux")]
+pub fn connect_fastopen(io: &IoDesc, addr: &SockAddr, buf: &[u8]) -> MioResult<uint> {
+    // MSG_FASTOPEN from linux/socket.h
+    const MSG_FASTOPEN: nix::c_int = 0x20000000;
+
+    let addr = from_sockaddr(addr);
+    let (ptr, len) = sockaddr_ptr(&addr);
+
+    let res = unsafe {
+        ffi::sendto(io.fd, buf.as_ptr(), buf.len() as size_t,
+                    MSG_FASTOPEN | ffi::MSG_NOSIGNAL, ptr, len)
+    };
+
+    if res < 0 {
+        let err = nix::SysError::last();
+
+        if err.kind == nix::EINPROGRESS {
+            return Ok(0);
+        }
+
+        return Err(MioError::from_sys_error(err));
+    }
+
+    Ok(res as uint)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn connect_fastopen(_io: &IoDesc, _addr: &SockAddr, _buf: &[u8]) -> MioResult<uint> {
+    Err(MioError::unsupported())
+}
+
+/// Enables TCP Fast Open on a listening socket. On Linux, `qlen` bounds the
+/// number of pending Fast Open requests; OS X only supports toggling it.
+#[cfg(target_os = "linux")]
+pub fn set_tcp_fastopen(io: &IoDesc, qlen: uint) -> MioResult<()> {
+    const TCP_FASTOPEN: nix::c_int = 23;
+    let v = qlen as nix::c_int;
+
+    nix::setsockopt(io.fd, nix::IPPROTO_TCP, TCP_FASTOPEN, &v)
+        .map_err(MioError::from_sys_error)
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub fn set_tcp_fastopen(io: &IoDesc, qlen: uint) -> MioResult<()> {
+    const TCP_FASTOPEN: nix::c_int = 0x105;
+    let v: nix::c_int = if qlen > 0 { 1 } else { 0 };
+
+    nix::setsockopt(io.fd, nix::IPPROTO_TCP, TCP_FASTOPEN, &v)
+        .map_err(MioError::from_sys_error)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "ios")))]
+pub fn set_tcp_fastopen(_io: &IoDesc, _qlen: uint) -> MioResult<()> {
+    Err(MioError::unsupported())
+}
+
+/// Defers accepting connections until the peer has sent data. On Linux,
+/// connections that stay silent for `secs` are accepted anyway; a value of 0
+/// disables the deferral.
+#[cfg(target_os = "linux")]
+pub fn set_defer_accept(io: &IoDesc, secs: u32) -> MioResult<()> {
+    const TCP_DEFER_ACCEPT: nix::c_int = 9;
+    let v = secs as nix::c_int;
+
+    nix::setsockopt(io.fd, nix::IPPROTO_TCP, TCP_DEFER_ACCEPT, &v)
+        .map_err(MioError::from_sys_error)
+}
+
+/// Installs the "dataready" accept filter, or removes the installed filter
+/// when `secs` is 0. The filter has no timeout, so `secs` is otherwise
+/// ignored. The socket must already be listening.
+#[cfg(target_os = "freebsd")]
+pub fn set_defer_accept(io: &IoDesc, secs: u32) -> MioResult<()> {
+    let res = if secs == 0 {
+        unsafe { ffi::setsockopt(io.fd, nix::SOL_SOCKET, ffi::SO_ACCEPTFILTER, ptr::null(), 0) }
+    } else {
+        let mut arg: ffi::accept_filter_arg = unsafe { mem::zeroed() };
+
+        for (dst, src) in arg.af_name.iter_mut().zip(b"dataready".iter()) {
+            *dst = *src as ::libc::c_char;
+        }
+
+        unsafe {
+            ffi::setsockopt(io.fd, nix::SOL_SOCKET, ffi::SO_ACCEPTFILTER,
+                            &arg as *const ffi::accept_filter_arg as *const c_void,
+                            mem::size_of::<ffi::accept_filter_arg>() as nix::socklen_t)
+        }
+    };
+
+    if res < 0 {
+        return Err(MioError::from_sys_error(nix::SysError::last()));
+    }
+
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "freebsd")))]
+pub fn set_defer_accept(_io: &IoDesc, _secs: u32) -> MioResult<()> {
+    Err(MioError::unsupported())
+}
+
+#[inline]
+pub fn read(io: &IoDesc, dst: &mut [u8]) -> MioResult<uint> {
+    let res = try!(nix::read(io.fd, dst).map_err(MioError::from_sys_error));
+
+    if res == 0 {
+        return Err(MioError::eof());
+    }
+
+    Ok(res)
+}
+
+#[inline]
+pub fn write(io: &IoDesc, src: &[u8]) -> MioResult<uint> {
+    nix::write(io.fd, src).map_err(MioError::from_sys_error)
+}
+
+/// Copies up to `count` bytes of `file`, starting at `offset`, to `io`
+/// without passing through userspace. The file's own offset is not changed.
+/// Returns the number of bytes transferred, which may be less than `count`.
+#[cfg(target_os = "linux")]
+pub fn sendfile(io: &IoDesc, file: &IoDesc, offset: u64, count: uint) -> MioResult<uint> {
+    let mut off = offset as off_t;
+
+    let res = unsafe {
+        ffi::sendfile(io.fd, file.fd, &mut off, count as size_t)
+    };
+
+    if res < 0 {
+        return Err(MioError::from_sys_error(nix::SysError::last()));
+    }
+
+    Ok(res as uint)
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub fn sendfile(io: &IoDesc, file: &IoDesc, offset: u64, count: uint) -> MioResult<uint> {
+    let mut len = count as off_t;
+
+    let res = unsafe {
+        ffi::sendfile(file.fd, io.fd, offset as off_t, &mut len, ptr::null_mut(), 0)
+    };
+
+    if res < 0 {
+        let err = nix::SysError::last();
+
+        // A non-blocking socket may accept part of the data before failing
+        // with EAGAIN, in which case `len` holds the number of bytes sent
+        if len > 0 && err.kind == nix::EAGAIN {
+            return Ok(len as uint);
+        }
+
+        return Err(MioError::from_sys_error(err));
+    }
+
+    Ok(len as uint)
 }
 
-impl PipeAwakener {
-    pub fn new() -> MioResult<PipeAwakener> {
-        let (rd, wr) = try!(pipe());
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "ios")))]
+pub fn sendfile(_io: &IoDesc, _file: &IoDesc, _offset: u64, _count: uint) -> MioResult<uint> {
+    Err(MioError::unsupported())
+}
 
-        Ok(PipeAwakener {
-            reader: rd,
-            writer: wr
-        })
-    }
+/// Sends `buf` on a connected socket, with the given per-call flags.
+pub fn send(io: &IoDesc, buf: &[u8], flags: MsgFlags) -> MioResult<uint> {
+    let res = unsafe {
+        ffi::send(io.fd, buf.as_ptr() as *const c_void, buf.len() as size_t, to_msg_flags(flags))
+    };
 
-    pub fn wakeup(&self) -> MioResult<()> {
-        write(&self.writer, b"0x01")
-            .map(|_| ())
+    if res < 0 {
+        return Err(MioError::from_sys_error(nix::SysError::last()));
     }
 
-    pub fn desc(&self) -> &IoDesc {
-        &self.reader
-    }
+    Ok(res as uint)
+}
 
-    pub fn cleanup(&self) {
-        let mut buf: [u8, ..128] = unsafe { mem::uninitialized() };
+/// Receives into `buf` from a connected socket, with the given per-call flags.
+pub fn recv(io: &IoDesc, buf: &mut [u8], flags: MsgFlags) -> MioResult<uint> {
+    let res = unsafe {
+        ffi::recv(io.fd, buf.as_mut_ptr() as *mut c_void, buf.len() as size_t, to_msg_flags(flags))
+    };
 
-        loop {
-            // Consume data until all bytes are purged
-            match read(&self.reader, buf.as_mut_slice()) {
-                Ok(_) => {}
-                Err(_) => return
-            }
-        }
+    if res < 0 {
+        return Err(MioError::from_sys_error(nix::SysError::last()));
     }
+
+    Ok(res as uint)
 }
 
-/// Represents the OS's handle to the IO instance. In this case, it is the file
-/// descriptor.
-#[deriving(Show)]
-pub struct IoDesc {
-    pub fd: nix::Fd
+fn to_msg_flags(flags: MsgFlags) -> nix::c_int {
+    let mut ret = 0;
+
+    if flags.contains(MSG_DONTWAIT) { ret |= ffi::MSG_DONTWAIT; }
+    if flags.contains(MSG_NOSIGNAL) { ret |= ffi::MSG_NOSIGNAL; }
+    if flags.contains(MSG_PEEK) { ret |= ffi::MSG_PEEK; }
+    if flags.contains(MSG_OOB) { ret |= ffi::MSG_OOB; }
+
+    ret
 }
 
-impl Drop for IoDesc {
-    fn drop(&mut self) {
-        let _ = nix::close(self.fd);
+/// Returns the credentials of the process on the other end of a connected
+/// Unix socket. The pid is only available on Linux.
+#[cfg(target_os = "linux")]
+pub fn peer_cred(io: &IoDesc) -> MioResult<PeerCred> {
+    const SO_PEERCRED: nix::c_int = 17;
+    let mut cred: ffi::ucred = unsafe { mem::zeroed() };
+
+    try!(nix::getsockopt(io.fd, nix::SOL_SOCKET, SO_PEERCRED, &mut cred)
+            .map_err(MioError::from_sys_error));
+
+    Ok(PeerCred {
+        pid: Some(cred.pid as i32),
+        uid: cred.uid as u32,
+        gid: cred.gid as u32
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn peer_cred(io: &IoDesc) -> MioResult<PeerCred> {
+    let mut uid = 0;
+    let mut gid = 0;
+
+    if unsafe { ffi::getpeereid(io.fd, &mut uid, &mut gid) } < 0 {
+        return Err(MioError::from_sys_error(nix::SysError::last()));
     }
+
+    Ok(PeerCred {
+        pid: None,
+        uid: uid as u32,
+        gid: gid as u32
+    })
 }
 
-/*
- *
- * ===== Pipes =====
- *
- */
+/// Returns the outcome of a non-blocking connect once the socket reported
+/// writable: `Ok` if the connection is established, otherwise the error
+/// that made it fail, as reported by `SO_ERROR`.
+pub fn connect_result(io: &IoDesc) -> MioResult<()> {
+    let mut v: nix::c_int = 0;
 
-pub fn pipe() -> MioResult<(IoDesc, IoDesc)> {
-    let (rd, wr) = try!(nix::pipe2(nix::O_NONBLOCK | nix::O_CLOEXEC)
-                        .map_err(MioError::from_sys_error));
+    try!(nix::getsockopt(io.fd, nix::SOL_SOCKET, nix::SO_ERROR, &mut v)
+            .map_err(MioError::from_sys_error));
 
-    Ok((IoDesc { fd: rd }, IoDesc { fd: wr }))
+    if v == 0 {
+        return Ok(());
+    }
+
+    let kind: nix::Errno = FromPrimitive::from_i32(v).unwrap_or(nix::UnknownErrno);
+    Err(MioError::from_sys_error(nix::SysError { kind: kind }))
 }
 
-/*
- *
- * ===== Sockets =====
- *
- */
+/// Returns the state of a TCP connection, from the `TCP_INFO` state.
+#[cfg(target_os = "linux")]
+pub fn connection_state(io: &IoDesc) -> MioResult<ConnState> {
+    let mut info: ffi::tcp_info = unsafe { mem::zeroed() };
 
-pub fn socket(af: AddressFamily, sock_type: SocketType) -> MioResult<IoDesc> {
-    let family = match af {
-        Inet  => nix::AF_INET,
-        Inet6 => nix::AF_INET6,
-        Unix  => nix::AF_UNIX
-    };
+    try!(nix::getsockopt(io.fd, nix::IPPROTO_TCP, ffi::TCP_INFO, &mut info)
+            .map_err(MioError::from_sys_error));
 
-    let socket_type = match sock_type {
-        Dgram  => nix::SOCK_DGRAM,
-        Stream => nix::SOCK_STREAM
+    match info.tcpi_state {
+        ffi::TCP_ESTABLISHED => Ok(Established),
+        ffi::TCP_SYN_SENT | ffi::TCP_SYN_RECV => Ok(Connecting),
+        _ => Ok(Closed)
+    }
+}
+
+/// Returns the state of a TCP connection: established if `getpeername`
+/// succeeds, otherwise closed if a socket error is pending, which this
+/// consumes, and connecting if not.
+#[cfg(not(target_os = "linux"))]
+pub fn connection_state(io: &IoDesc) -> MioResult<ConnState> {
+    let mut addr: nix::sockaddr_storage = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<nix::sockaddr_storage>() as nix::socklen_t;
+
+    let res = unsafe {
+        ffi::getpeername(io.fd, &mut addr as *mut nix::sockaddr_storage as *mut nix::sockaddr, &mut len)
     };
 
-    Ok(IoDesc {
-        fd: try!(nix::socket(family, socket_type, nix::SOCK_NONBLOCK | nix::SOCK_CLOEXEC)
-                    .map_err(MioError::from_sys_error))
-    })
+    if res == 0 {
+        return Ok(Established);
+    }
+
+    let err = nix::SysError::last();
+
+    if err.kind != nix::ENOTCONN {
+        return Err(MioError::from_sys_error(err));
+    }
+
+    let mut v: nix::c_int = 0;
+
+    try!(nix::getsockopt(io.fd, nix::SOL_SOCKET, nix::SO_ERROR, &mut v)
+            .map_err(MioError::from_sys_error));
+
+    if v != 0 {
+        Ok(Closed)
+    } else {
+        Ok(Connecting)
+    }
 }
 
-pub fn connect(io: &IoDesc, addr: &SockAddr) -> MioResult<bool> {
-    match nix::connect(io.fd, &from_sockaddr(addr)) {
-        Ok(_) => Ok(true),
-        Err(e) => {
-            match e.kind {
-                nix::EINPROGRESS => Ok(false),
-                _ => Err(MioError::from_sys_error(e))
-            }
-        }
+// ===== Socket options =====
+
+pub fn reuseaddr(io: &IoDesc) -> MioResult<bool> {
+    let mut v: nix::c_int = 0;
+
+    try!(nix::getsockopt(io.fd, nix::SOL_SOCKET, nix::SO_REUSEADDR, &mut v)
+            .map_err(MioError::from_sys_error));
+
+    Ok(v != 0)
+}
+
+/// Enables or disables `IP_HDRINCL` on a raw IPv4 socket, so that written
+/// datagrams start with the IP header instead of the kernel prepending one.
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+pub fn set_hdrincl(io: &IoDesc, val: bool) -> MioResult<()> {
+    let v: nix::c_int = if val { 1 } else { 0 };
+
+    match try!(socket_family(io)) {
+        Inet => {}
+        // There is no portable equivalent for IPv6
+        _ => return Err(MioError::unsupported())
     }
+
+    nix::setsockopt(io.fd, nix::IPPROTO_IP, ffi::IP_HDRINCL, &v)
+        .map_err(MioError::from_sys_error)
 }
 
-pub fn bind(io: &IoDesc, addr: &SockAddr) -> MioResult<()> {
-    nix::bind(io.fd, &from_sockaddr(addr))
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "ios", target_os = "freebsd")))]
+pub fn set_hdrincl(_io: &IoDesc, _val: bool) -> MioResult<()> {
+    Err(MioError::unsupported())
+}
+
+pub fn set_reuseaddr(io: &IoDesc, val: bool) -> MioResult<()> {
+    let v: nix::c_int = if val { 1 } else { 0 };
+
+    nix::setsockopt(io.fd, nix::SOL_SOCKET, nix::SO_REUSEADDR, &v)
         .map_err(MioError::from_sys_error)
 }
 
-pub fn listen(io: &IoDesc, backlog: uint) -> MioResult<()> {
-    nix::listen(io.fd, backlog)
+pub fn set_reuseport(io: &IoDesc, val: bool) -> MioResult<()> {
+    let v: nix::c_int = if val { 1 } else { 0 };
+
+    nix::setsockopt(io.fd, nix::SOL_SOCKET, nix::SO_REUSEPORT, &v)
         .map_err(MioError::from_sys_error)
 }
 
-pub fn accept(io: &IoDesc) -> MioResult<IoDesc> {
-    Ok(IoDesc {
-        fd: try!(nix::accept4(io.fd, nix::SOCK_NONBLOCK | nix::SOCK_CLOEXEC)
-                     .map_err(MioError::from_sys_error))
-    })
+pub fn recv_lowat(io: &IoDesc) -> MioResult<uint> {
+    let mut v: nix::c_int = 0;
+
+    try!(nix::getsockopt(io.fd, nix::SOL_SOCKET, ffi::SO_RCVLOWAT, &mut v)
+            .map_err(MioError::from_sys_error));
+
+    Ok(v as uint)
 }
 
-#[inline]
-pub fn recvfrom(io: &IoDesc, buf: &mut [u8]) -> MioResult<(uint, SockAddr)> {
-    match nix::recvfrom(io.fd, buf).map_err(MioError::from_sys_error) {
-        Ok((cnt, addr)) => Ok((cnt, to_sockaddr(&addr))),
-        Err(e) => Err(e)
+pub fn set_recv_lowat(io: &IoDesc, bytes: uint) -> MioResult<()> {
+    let v = bytes as nix::c_int;
+
+    nix::setsockopt(io.fd, nix::SOL_SOCKET, ffi::SO_RCVLOWAT, &v)
+        .map_err(MioError::from_sys_error)
+}
+
+pub fn broadcast(io: &IoDesc) -> MioResult<bool> {
+    let mut v: nix::c_int = 0;
+
+    try!(nix::getsockopt(io.fd, nix::SOL_SOCKET, nix::SO_BROADCAST, &mut v)
+            .map_err(MioError::from_sys_error));
+
+    Ok(v != 0)
+}
+
+pub fn set_broadcast(io: &IoDesc, val: bool) -> MioResult<()> {
+    let v: nix::c_int = if val { 1 } else { 0 };
+
+    nix::setsockopt(io.fd, nix::SOL_SOCKET, nix::SO_BROADCAST, &v)
+        .map_err(MioError::from_sys_error)
+}
+
+// Maximum length of an interface name, including the trailing NUL
+const IFNAMSIZ: uint = 16;
+
+#[cfg(target_os = "linux")]
+pub fn bind_to_device(io: &IoDesc, ifname: &str) -> MioResult<()> {
+    let name = ifname.as_bytes();
+
+    if name.len() >= IFNAMSIZ || name.contains(&0) {
+        return Err(MioError::invalid_input());
+    }
+
+    let mut v: [u8, ..IFNAMSIZ] = [0, ..IFNAMSIZ];
+
+    for (dst, src) in v.iter_mut().zip(name.iter()) {
+        *dst = *src;
     }
+
+    nix::setsockopt(io.fd, nix::SOL_SOCKET, nix::SO_BINDTODEVICE, &v)
+        .map_err(MioError::from_sys_error)
 }
 
-#[inline]
-pub fn sendto(io: &IoDesc, buf: &[u8], tgt: &SockAddr) -> MioResult<uint> {
-    let res = try!(nix::sendto(io.fd, buf, &from_sockaddr(tgt), nix::MSG_DONTWAIT).map_err(MioError::from_sys_error));
-    Ok(res)
+#[cfg(not(target_os = "linux"))]
+pub fn bind_to_device(_io: &IoDesc, _ifname: &str) -> MioResult<()> {
+    Err(MioError::unsupported())
 }
 
-#[inline]
-pub fn read(io: &IoDesc, dst: &mut [u8]) -> MioResult<uint> {
-    let res = try!(nix::read(io.fd, dst).map_err(MioError::from_sys_error));
+/// Returns the address family the socket was created with, as reported by
+/// `getsockname`.
+pub fn socket_family(io: &IoDesc) -> MioResult<AddressFamily> {
+    let mut addr: nix::sockaddr_storage = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<nix::sockaddr_storage>() as nix::socklen_t;
 
-    if res == 0 {
-        return Err(MioError::eof());
+    let res = unsafe {
+        ffi::getsockname(io.fd, &mut addr as *mut nix::sockaddr_storage as *mut nix::sockaddr, &mut len)
+    };
+
+    if res < 0 {
+        return Err(MioError::from_sys_error(nix::SysError::last()));
     }
 
-    Ok(res)
+    match addr.ss_family as nix::c_int {
+        nix::AF_INET => Ok(Inet),
+        nix::AF_INET6 => Ok(Inet6),
+        nix::AF_UNIX => Ok(Unix),
+        _ => Err(MioError::unsupported())
+    }
 }
 
-#[inline]
-pub fn write(io: &IoDesc, src: &[u8]) -> MioResult<uint> {
-    nix::write(io.fd, src).map_err(MioError::from_sys_error)
+/// Returns the local address the socket is bound to, as reported by
+/// `getsockname`. For a socket that was connected without binding first,
+/// this is the address and port picked by the kernel.
+pub fn local_addr(io: &IoDesc) -> MioResult<SockAddr> {
+    let mut addr: nix::sockaddr_storage = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<nix::sockaddr_storage>() as nix::socklen_t;
+
+    let res = unsafe {
+        ffi::getsockname(io.fd, &mut addr as *mut nix::sockaddr_storage as *mut nix::sockaddr, &mut len)
+    };
+
+    if res < 0 {
+        return Err(MioError::from_sys_error(nix::SysError::last()));
+    }
+
+    let addr = try!(from_sockaddr_storage(&addr, len));
+    Ok(to_sockaddr(&addr))
 }
 
-// ===== Socket options =====
+#[cfg(target_os = "linux")]
+const IP_TOS: nix::c_int = 1;
+#[cfg(not(target_os = "linux"))]
+const IP_TOS: nix::c_int = 3;
 
-pub fn reuseaddr(_io: &IoDesc) -> MioResult<uint> {
-    unimplemented!()
+#[cfg(target_os = "linux")]
+const IPV6_TCLASS: nix::c_int = 67;
+#[cfg(not(target_os = "linux"))]
+const IPV6_TCLASS: nix::c_int = 36;
+
+const IPPROTO_IPV6: nix::c_int = 41;
+
+#[cfg(target_os = "linux")]
+const IPV6_V6ONLY: nix::c_int = 26;
+#[cfg(not(target_os = "linux"))]
+const IPV6_V6ONLY: nix::c_int = 27;
+
+// Level and option name of the TOS / traffic class option for the socket
+fn tos_opt(io: &IoDesc) -> MioResult<(nix::c_int, nix::c_int)> {
+    match try!(socket_family(io)) {
+        Inet => Ok((nix::IPPROTO_IP, IP_TOS)),
+        Inet6 => Ok((IPPROTO_IPV6, IPV6_TCLASS)),
+        Unix => Err(MioError::invalid_input())
+    }
 }
 
-pub fn set_reuseaddr(io: &IoDesc, val: bool) -> MioResult<()> {
-    let v: nix::c_int = if val { 1 } else { 0 };
+pub fn tos(io: &IoDesc) -> MioResult<u8> {
+    let (level, opt) = try!(tos_opt(io));
+    let mut v: nix::c_int = 0;
 
-    nix::setsockopt(io.fd, nix::SOL_SOCKET, nix::SO_REUSEADDR, &v)
+    try!(nix::getsockopt(io.fd, level, opt, &mut v)
+            .map_err(MioError::from_sys_error));
+
+    Ok(v as u8)
+}
+
+pub fn set_tos(io: &IoDesc, tos: u8) -> MioResult<()> {
+    let (level, opt) = try!(tos_opt(io));
+    let v = tos as nix::c_int;
+
+    nix::setsockopt(io.fd, level, opt, &v)
         .map_err(MioError::from_sys_error)
 }
 
-pub fn set_reuseport(io: &IoDesc, val: bool) -> MioResult<()> {
+pub fn only_v6(io: &IoDesc) -> MioResult<bool> {
+    let mut v: nix::c_int = 0;
+
+    try!(nix::getsockopt(io.fd, IPPROTO_IPV6, IPV6_V6ONLY, &mut v)
+            .map_err(MioError::from_sys_error));
+
+    Ok(v != 0)
+}
+
+pub fn set_only_v6(io: &IoDesc, val: bool) -> MioResult<()> {
     let v: nix::c_int = if val { 1 } else { 0 };
 
-    nix::setsockopt(io.fd, nix::SOL_SOCKET, nix::SO_REUSEPORT, &v)
+    nix::setsockopt(io.fd, IPPROTO_IPV6, IPV6_V6ONLY, &v)
         .map_err(MioError::from_sys_error)
 }
 
@@ -238,22 +1580,110 @@ pub fn set_linger(io: &IoDesc, dur_s: uint) -> MioResult<()> {
         .map_err(MioError::from_sys_error)
 }
 
+/// Sets `SO_LINGER` with a zero timeout, so that closing the socket discards
+/// any unsent data and resets the connection instead of going through the
+/// normal shutdown and TIME_WAIT.
+pub fn set_linger_abort(io: &IoDesc) -> MioResult<()> {
+    let linger = nix::linger {
+        l_onoff: 1,
+        l_linger: 0
+    };
+
+    nix::setsockopt(io.fd, nix::SOL_SOCKET, nix::SO_LINGER, &linger)
+        .map_err(MioError::from_sys_error)
+}
+
+/// Waits for the handle to become ready for `interest` with a single `poll`
+/// call, returning the ready set, which is empty if the timeout expired.
+/// Waits indefinitely without a timeout.
+pub fn poll_ready(io: &IoDesc, interest: Interest, timeout: Option<Duration>) -> MioResult<EventSet> {
+    let mut events = 0;
+
+    if interest.contains(event::READABLE) { events |= ffi::POLLIN; }
+    if interest.contains(event::WRITABLE) { events |= ffi::POLLOUT; }
+    if interest.contains(event::OOB) { events |= ffi::POLLPRI; }
+
+    let mut pfd = ffi::pollfd {
+        fd: io.fd,
+        events: events,
+        revents: 0
+    };
+
+    let timeout_ms = match timeout {
+        Some(dur) => cmp::min(cmp::max(0, dur.num_milliseconds()), i32::MAX as i64) as nix::c_int,
+        None => -1
+    };
+
+    if unsafe { ffi::poll(&mut pfd, 1, timeout_ms) } < 0 {
+        return Err(MioError::from_sys_error(nix::SysError::last()));
+    }
+
+    let mut ready = EventSet::empty();
+
+    if pfd.revents & ffi::POLLIN != 0 { ready.insert(EventSet::readable()); }
+    if pfd.revents & ffi::POLLOUT != 0 { ready.insert(EventSet::writable()); }
+    if pfd.revents & ffi::POLLPRI != 0 { ready.insert(EventSet::oob()); }
+    if pfd.revents & ffi::POLLERR != 0 { ready.insert(EventSet::error()); }
+    if pfd.revents & ffi::POLLHUP != 0 { ready.insert(EventSet::hup()); }
+
+    Ok(ready)
+}
+
+/// Shuts down the read half, the write half or both halves of a connection.
+pub fn shutdown(io: &IoDesc, how: Shutdown) -> MioResult<()> {
+    const SHUT_RD: nix::c_int = 0;
+    const SHUT_WR: nix::c_int = 1;
+    const SHUT_RDWR: nix::c_int = 2;
+
+    let how = match how {
+        Shutdown::Read => SHUT_RD,
+        Shutdown::Write => SHUT_WR,
+        Shutdown::Both => SHUT_RDWR
+    };
+
+    if unsafe { ffi::shutdown(io.fd, how) } < 0 {
+        return Err(MioError::from_sys_error(nix::SysError::last()));
+    }
+
+    Ok(())
+}
+
 fn make_ip_mreq(group_addr: &IpAddr, iface_addr: &Option<IpAddr>) -> MioResult<nix::ip_mreq> {
     Ok(nix::ip_mreq {
-        imr_multiaddr: from_ip_addr_to_inaddr(&Some(*group_addr)),
-        imr_interface: from_ip_addr_to_inaddr(iface_addr)
+        imr_multiaddr: try!(from_ip_addr_to_inaddr(&Some(*group_addr))),
+        imr_interface: try!(from_ip_addr_to_inaddr(iface_addr))
     })
 }
 
-fn from_ip_addr_to_inaddr(addr: &Option<IpAddr>) -> nix::in_addr {
+// IPv6 groups are joined with `ipv6_mreq`, which is not supported yet
+fn from_ip_addr_to_inaddr(addr: &Option<IpAddr>) -> MioResult<nix::in_addr> {
     match *addr {
-        Some(ip) => {
-            match ip {
-                IPv4Addr(a, b, c, d) => ipv4_to_inaddr(a, b, c, d),
-                _ => unimplemented!()
+        Some(IPv4Addr(a, b, c, d)) => Ok(ipv4_to_inaddr(a, b, c, d)),
+        Some(IPv6Addr(..)) => Err(MioError::unsupported()),
+        None => Ok(nix::in_addr { s_addr: nix::INADDR_ANY })
+    }
+}
+
+// Interprets a `sockaddr_storage` filled in by the kernel
+fn from_sockaddr_storage(addr: &nix::sockaddr_storage, len: nix::socklen_t) -> MioResult<nix::SockAddr> {
+    let len = len as uint;
+
+    unsafe {
+        match addr.ss_family as nix::c_int {
+            nix::AF_INET if len >= mem::size_of::<nix::sockaddr_in>() => {
+                let sin: &nix::sockaddr_in = mem::transmute(addr);
+                Ok(nix::SockAddr::SockIpV4(*sin))
+            }
+            nix::AF_INET6 if len >= mem::size_of::<nix::sockaddr_in6>() => {
+                let sin6: &nix::sockaddr_in6 = mem::transmute(addr);
+                Ok(nix::SockAddr::SockIpV6(*sin6))
+            }
+            nix::AF_UNIX => {
+                let sun: &nix::sockaddr_un = mem::transmute(addr);
+                Ok(nix::SockAddr::SockUnix(*sun))
             }
+            _ => Err(MioError::unsupported())
         }
-        None => nix::in_addr { s_addr: nix::INADDR_ANY }
     }
 }
 
@@ -262,6 +1692,12 @@ fn to_sockaddr(addr: &nix::SockAddr) -> SockAddr {
         nix::SockAddr::SockIpV4(sin) => {
             InetAddr(u32be_to_ipv4(sin.sin_addr.s_addr), Int::from_be(sin.sin_port))
         }
+        nix::SockAddr::SockIpV6(sin6) => {
+            // `in6_addr` is 16 bytes in network order on all platforms
+            let bytes: [u8, ..16] = unsafe { mem::transmute(sin6.sin6_addr) };
+
+            InetAddr(bytes_to_ipv6(&bytes), Int::from_be(sin6.sin6_port))
+        }
         nix::SockAddr::SockUnix(addr) => {
             let mut str_path = String::new();
             for c in addr.sun_path.iter() {
@@ -271,7 +1707,24 @@ fn to_sockaddr(addr: &nix::SockAddr) -> SockAddr {
 
             UnixAddr(Path::new(str_path))
         }
-        _ => unimplemented!()
+    }
+}
+
+// Raw pointer and length of the address, for passing to libc directly
+fn sockaddr_ptr(addr: &nix::SockAddr) -> (*const nix::sockaddr, nix::socklen_t) {
+    match *addr {
+        nix::SockAddr::SockIpV4(ref sin) => {
+            (sin as *const nix::sockaddr_in as *const nix::sockaddr,
+             mem::size_of::<nix::sockaddr_in>() as nix::socklen_t)
+        }
+        nix::SockAddr::SockIpV6(ref sin6) => {
+            (sin6 as *const nix::sockaddr_in6 as *const nix::sockaddr,
+             mem::size_of::<nix::sockaddr_in6>() as nix::socklen_t)
+        }
+        nix::SockAddr::SockUnix(ref sun) => {
+            (sun as *const nix::sockaddr_un as *const nix::sockaddr,
+             mem::size_of::<nix::sockaddr_un>() as nix::socklen_t)
+        }
     }
 }
 
@@ -290,7 +1743,15 @@ fn from_sockaddr(addr: &SockAddr) -> nix::SockAddr {
 
                     nix::SockAddr::SockIpV4(addr)
                 }
-                _ => unimplemented!()
+                IPv6Addr(a, b, c, d, e, f, g, h) => {
+                    let mut addr: nix::sockaddr_in6 = unsafe { mem::zeroed() };
+
+                    addr.sin6_family = nix::AF_INET6 as nix::sa_family_t;
+                    addr.sin6_port = port.to_be();
+                    addr.sin6_addr = unsafe { mem::transmute(ipv6_to_bytes([a, b, c, d, e, f, g, h])) };
+
+                    nix::SockAddr::SockIpV6(addr)
+                }
             }
         }
         UnixAddr(ref path) => {
@@ -327,6 +1788,23 @@ fn u32_to_ipv4(net: u32) -> IpAddr {
          (net & 0xff) as u8)
 }
 
+fn bytes_to_ipv6(b: &[u8, ..16]) -> IpAddr {
+    let seg = |i: uint| (b[2 * i] as u16 << 8) | b[2 * i + 1] as u16;
+
+    IPv6Addr(seg(0), seg(1), seg(2), seg(3), seg(4), seg(5), seg(6), seg(7))
+}
+
+fn ipv6_to_bytes(segments: [u16, ..8]) -> [u8, ..16] {
+    let mut b = [0u8, ..16];
+
+    for (i, seg) in segments.iter().enumerate() {
+        b[2 * i] = (*seg >> 8) as u8;
+        b[2 * i + 1] = *seg as u8;
+    }
+
+    b
+}
+
 fn ipv4_to_inaddr(a: u8, b: u8, c: u8, d: u8) -> nix::in_addr {
     nix::in_addr {
         s_addr: ipv4_to_u32(a, b, c, d)
@@ -1,7 +1,12 @@
-#[deriving(Show, PartialEq, Eq, Hash)]
+#[deriving(Show, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Token(pub uint);
 
 impl Token {
+    #[inline]
+    pub fn new(val: uint) -> Token {
+        Token(val)
+    }
+
     #[inline]
     pub fn as_uint(self) -> uint {
         let Token(inner) = self;
@@ -1,4 +1,8 @@
-use error::MioResult;
+use std::cmp;
+use std::collections::HashMap;
+use std::collections::hash_map;
+use nix::fcntl::Fd;
+use error::{MioResult, MioError};
 use io::IoHandle;
 use os;
 use os::token::Token;
@@ -6,22 +10,108 @@ use os::event;
 
 pub struct Poll {
     selector: os::Selector,
-    events: os::Events
+    events: os::Events,
+    // Upper bound to which `events` grows after polls that fill it
+    max_capacity: uint,
+    // Tracks the handles currently registered with the selector, keyed by
+    // file descriptor. Used to handle duplicate registrations consistently
+    // (epoll errors, kqueue silently replaces the filters). Entries of
+    // handles closed without being deregistered stay until their file
+    // descriptor is registered again.
+    registrations: HashMap<Fd, Registration>,
+    // File descriptor of the handle last registered with each token
+    by_token: HashMap<Token, Fd>
+}
+
+struct Registration {
+    token: Token,
+    interest: event::Interest,
+    opts: event::PollOpt,
+    // User data attached with `register_with_data`
+    data: Option<uint>
 }
 
 impl Poll {
     pub fn new() -> MioResult<Poll> {
         Ok(Poll {
             selector: try!(os::Selector::new()),
-            events: os::Events::new()
+            events: os::Events::new(),
+            max_capacity: 0,
+            registrations: HashMap::new(),
+            by_token: HashMap::new()
         })
     }
 
+    /// Creates a poller returning up to `capacity` events per `poll`. When a
+    /// poll fills the events buffer, more events were likely ready, so the
+    /// buffer is doubled before the next poll, up to `max_capacity`.
+    pub fn with_capacity(capacity: uint, max_capacity: uint) -> MioResult<Poll> {
+        Poll::with_registrations(capacity, max_capacity, 0)
+    }
+
+    /// Same as `with_capacity`, allocating room to track `registrations`
+    /// handles up front.
+    pub fn with_registrations(capacity: uint, max_capacity: uint, registrations: uint) -> MioResult<Poll> {
+        Ok(Poll {
+            selector: try!(os::Selector::new()),
+            events: os::Events::with_capacity(capacity),
+            max_capacity: max_capacity,
+            registrations: HashMap::with_capacity(registrations),
+            by_token: HashMap::with_capacity(registrations)
+        })
+    }
+
+    /// Maximum number of events returned by a single `poll`
+    pub fn capacity(&self) -> uint {
+        self.events.capacity()
+    }
+
+    /// Registers the IO handle with the selector. Registering a handle that
+    /// is already registered, even with a different token, replaces its
+    /// registration as `reregister` would, dropping the attached data.
+    ///
+    /// A handle reusing the file descriptor of a handle that was closed
+    /// while registered takes over its stale registration the same way.
     pub fn register<H: IoHandle>(&mut self, io: &H, token: Token, interest: event::Interest, opts: event::PollOpt) -> MioResult<()> {
-        debug!("registering  with poller");
+        self.register_entry(io, token, interest, opts, None)
+    }
+
+    /// Same as `register`, attaching `data` to the registration, see
+    /// `EventLoop::register_with_data`.
+    pub fn register_with_data<H: IoHandle>(&mut self, io: &H, token: Token, interest: event::Interest, opts: event::PollOpt, data: uint) -> MioResult<()> {
+        self.register_entry(io, token, interest, opts, Some(data))
+    }
+
+    fn register_entry<H: IoHandle>(&mut self, io: &H, token: Token, interest: event::Interest, opts: event::PollOpt, data: Option<uint>) -> MioResult<()> {
+        let fd = io.desc().fd;
+
+        debug!("registering  with poller; fd={}; token={}", fd, token);
 
         // Register interests for this socket
-        try!(self.selector.register(io.desc(), token.as_uint(), interest, opts));
+        match self.selector.register(io.desc(), token.as_uint(), interest, opts) {
+            Ok(()) => {}
+            Err(e) => {
+                if !self.registrations.contains_key(&fd) {
+                    return Err(e);
+                }
+
+                // Still registered with the selector (epoll), update it
+                debug!("handle already registered; fd={}", fd);
+                try!(self.selector.reregister(io.desc(), token.as_uint(), interest, opts));
+            }
+        }
+
+        match self.registrations.insert(fd, Registration {
+            token: token,
+            interest: interest,
+            opts: opts,
+            data: data
+        }) {
+            Some(prev) => self.untrack_token(prev.token, fd),
+            None => {}
+        }
+
+        self.by_token.insert(token, fd);
 
         Ok(())
     }
@@ -32,6 +122,23 @@ impl Poll {
         // Register interests for this socket
         try!(self.selector.reregister(io.desc(), token.as_uint(), interest, opts));
 
+        let fd = io.desc().fd;
+
+        // The data attached to the handle is kept
+        let data = self.registrations.get(&fd).and_then(|r| r.data);
+
+        match self.registrations.insert(fd, Registration {
+            token: token,
+            interest: interest,
+            opts: opts,
+            data: data
+        }) {
+            Some(prev) => self.untrack_token(prev.token, fd),
+            None => {}
+        }
+
+        self.by_token.insert(token, fd);
+
         Ok(())
     }
 
@@ -41,10 +148,98 @@ impl Poll {
         // Deregister interests for this socket
         try!(self.selector.deregister(io.desc()));
 
+        let fd = io.desc().fd;
+
+        match self.registrations.remove(&fd) {
+            Some(prev) => self.untrack_token(prev.token, fd),
+            None => {}
+        }
+
         Ok(())
     }
 
+    // Forgets the token of a handle that changed token or was deregistered,
+    // unless another handle was registered with it since
+    fn untrack_token(&mut self, token: Token, fd: Fd) {
+        if self.by_token.get(&token) == Some(&fd) {
+            self.by_token.remove(&token);
+        }
+    }
+
+    /// Registers a raw kqueue filter, see `EventLoop::register_raw`.
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    pub fn register_raw(&mut self, ident: uint, token: Token, filter: event::EventFilter, fflags: event::FilterFlag, data: i64, opts: event::PollOpt) -> MioResult<()> {
+        self.selector.register_raw(ident, token.as_uint(), filter, fflags, data, opts)
+    }
+
+    /// Removes a raw kqueue filter registered with `register_raw`.
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    pub fn deregister_raw(&mut self, ident: uint, filter: event::EventFilter) -> MioResult<()> {
+        self.selector.deregister_raw(ident, filter)
+    }
+
+    /// Registers the IO handle with raw epoll event flags, see
+    /// `EventLoop::register_raw`.
+    #[cfg(target_os = "linux")]
+    pub fn register_raw<H: IoHandle>(&mut self, io: &H, token: Token, events: u32) -> MioResult<()> {
+        self.selector.register_raw(io.desc(), token.as_uint(), events)
+    }
+
+    pub fn is_registered<H: IoHandle>(&self, io: &H) -> bool {
+        self.registrations.contains_key(&io.desc().fd)
+    }
+
+    /// Returns the options the handle was last registered with.
+    pub fn opts<H: IoHandle>(&self, io: &H) -> Option<event::PollOpt> {
+        self.registrations.get(&io.desc().fd).map(|r| r.opts)
+    }
+
+    /// Returns the interest the handle registered with `token` was last
+    /// registered or reregistered with.
+    pub fn interest(&self, token: Token) -> Option<event::Interest> {
+        self.by_token.get(&token)
+            .and_then(|fd| self.registrations.get(fd))
+            .map(|r| r.interest)
+    }
+
+    /// Returns the token `io` is registered with, if any.
+    pub fn token<H: IoHandle>(&self, io: &H) -> Option<Token> {
+        self.registrations.get(&io.desc().fd).map(|r| r.token)
+    }
+
+    /// Whether a handle is still registered with `token`.
+    pub fn is_live(&self, token: Token) -> bool {
+        self.by_token.contains_key(&token)
+    }
+
+    /// Returns the data attached to the handle registered with `token`, if
+    /// it was registered with `register_with_data`.
+    pub fn data(&self, token: Token) -> Option<uint> {
+        self.by_token.get(&token)
+            .and_then(|fd| self.registrations.get(fd))
+            .and_then(|r| r.data)
+    }
+
+    /// Number of handles currently registered, excluding raw registrations.
+    pub fn count(&self) -> uint {
+        self.registrations.len()
+    }
+
+    /// Iterates over the tokens of the registered handles.
+    pub fn tokens<'a>(&'a self) -> Tokens<'a> {
+        Tokens { iter: self.registrations.values() }
+    }
+
     pub fn poll(&mut self, timeout_ms: uint) -> MioResult<uint> {
+        let capacity = self.events.capacity();
+
+        if self.events.len() == capacity && capacity < self.max_capacity {
+            let capacity = cmp::min(capacity * 2, self.max_capacity);
+
+            debug!("growing events buffer; capacity={}", capacity);
+            self.events.grow(capacity);
+        }
+
         try!(self.selector.select(&mut self.events, timeout_ms));
         Ok(self.events.len())
     }
@@ -53,3 +248,14 @@ impl Poll {
         self.events.get(idx)
     }
 }
+
+/// Iterator over the tokens of the handles registered with a `Poll`
+pub struct Tokens<'a> {
+    iter: hash_map::Values<'a, Fd, Registration>
+}
+
+impl<'a> Iterator<Token> for Tokens<'a> {
+    fn next(&mut self) -> Option<Token> {
+        self.iter.next().map(|r| r.token)
+    }
+}
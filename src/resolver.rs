@@ -0,0 +1,135 @@
+use std::io::net::addrinfo;
+use error::{MioResult, MioError};
+use io::IoHandle;
+use net::{SockAddr, Port};
+use net::SockAddr::InetAddr;
+use notify::Notify;
+use os;
+use os::token::Token;
+
+/// Result of a resolution, delivered to `Handler::resolved`
+pub type Resolved = (Token, MioResult<Vec<SockAddr>>);
+
+// A hostname to resolve on a worker thread
+struct Job {
+    host: String,
+    port: Port,
+    token: Token,
+}
+
+/// Resolves hostnames with the blocking `getaddrinfo` on a pool of worker
+/// threads. Results are pushed back to the event loop through a `Notify`,
+/// so they wake it up like messages sent to its channel.
+pub struct Resolver {
+    workers: Vec<Sender<Job>>,
+    // Index of the worker the next job is handed to
+    next: uint,
+    results: Notify<Resolved>,
+    // Resolutions not delivered yet, bounded by the capacity of the results
+    // queue so that workers can never find it full
+    pending: uint,
+    capacity: uint,
+}
+
+impl Resolver {
+    pub fn new(threads: uint, capacity: uint) -> MioResult<Resolver> {
+        if threads == 0 || capacity == 0 {
+            return Err(MioError::invalid_input());
+        }
+
+        let results = try!(Notify::with_capacity(capacity));
+        let mut workers = Vec::with_capacity(threads);
+
+        for _ in range(0, threads) {
+            let (tx, rx) = channel::<Job>();
+            let results = results.clone();
+
+            spawn(proc() {
+                // Exits once the resolver, and with it the sender, is dropped
+                for job in rx.iter() {
+                    let res = lookup(job.host.as_slice(), job.port);
+
+                    // Cannot fail, see `pending`
+                    let _ = results.notify((job.token, res));
+                }
+            });
+
+            workers.push(tx);
+        }
+
+        Ok(Resolver {
+            workers: workers,
+            next: 0,
+            results: results,
+            pending: 0,
+            capacity: capacity,
+        })
+    }
+
+    /// Queues the resolution of `host`, fails with a `BufOverflow` error if
+    /// `capacity` resolutions are already pending.
+    pub fn resolve(&mut self, host: &str, port: Port, token: Token) -> MioResult<()> {
+        if self.pending == self.capacity {
+            return Err(MioError::buf_overflow());
+        }
+
+        let job = Job {
+            host: host.to_string(),
+            port: port,
+            token: token,
+        };
+
+        if self.workers[self.next].send_opt(job).is_err() {
+            // The worker panicked
+            return Err(MioError::event_loop_terminated());
+        }
+
+        self.next = (self.next + 1) % self.workers.len();
+        self.pending += 1;
+
+        Ok(())
+    }
+
+    /// Number of resolutions queued or in progress
+    pub fn pending(&self) -> uint {
+        self.pending
+    }
+
+    #[inline]
+    pub fn check(&self, max: uint, will_sleep: bool) -> uint {
+        self.results.check(max, will_sleep)
+    }
+
+    pub fn poll(&mut self) -> Option<Resolved> {
+        let res = self.results.poll();
+
+        if res.is_some() {
+            self.pending -= 1;
+        }
+
+        res
+    }
+
+    #[inline]
+    pub fn cleanup(&self) {
+        self.results.cleanup();
+    }
+}
+
+impl IoHandle for Resolver {
+    fn desc(&self) -> &os::IoDesc {
+        self.results.desc()
+    }
+}
+
+pub fn lookup(host: &str, port: Port) -> MioResult<Vec<SockAddr>> {
+    debug!("resolving; host={}", host);
+
+    match addrinfo::get_host_addresses(host) {
+        Ok(ips) => Ok(ips.into_iter().map(|ip| InetAddr(ip, port)).collect()),
+        Err(e) => {
+            debug!("failed to resolve host; host={}; err={}", host, e);
+            Err(MioError::resolve_failed())
+        }
+    }
+}
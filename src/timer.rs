@@ -2,14 +2,18 @@ use std::uint;
 use std::cmp::max;
 use std::time::duration::Duration;
 use std::num::UnsignedInt;
-use time::precise_time_ns;
+use time::{precise_time_ns, Timespec};
+use error::{MioResult, MioError};
+use io::IoHandle;
+use os;
 use os::token::Token;
 use util::Slab;
 
-use self::TimerErrorKind::TimerOverflow;
+use self::TimerErrorKind::{TimerOverflow, TimerSysError};
 
 const EMPTY: Token = Token(uint::MAX);
 const NS_PER_MS: u64 = 1_000_000;
+const MS_PER_SEC: u64 = 1_000;
 
 // Implements coarse-grained timeouts using an algorithm based on hashed timing
 // wheels by Varghese & Lauck.
@@ -63,10 +67,15 @@ impl<T> Timer<T> {
         self.entries.count()
     }
 
+    // The next tick to be processed
+    pub fn current_tick(&self) -> u64 {
+        self.tick
+    }
+
     // Number of ms remaining until the next tick
     pub fn next_tick_in_ms(&self) -> u64 {
         let now = self.now_ms();
-        let nxt = self.start + (self.tick + 1) * self.tick_ms;
+        let nxt = self.start + self.tick * self.tick_ms;
 
         if nxt <= now {
             return 0;
@@ -103,15 +112,26 @@ impl<T> Timer<T> {
         self.timeout_at_ms(token, at)
     }
 
+    // Schedules the timeout against an absolute deadline expressed on the same
+    // monotonic clock that drives the timer (see `now_ts`).
+    pub fn timeout_at(&mut self, token: T, deadline: Timespec) -> TimerResult<Timeout> {
+        let at = (max(0, deadline.sec) as u64) * MS_PER_SEC +
+                 (max(0, deadline.nsec) as u64) / NS_PER_MS;
+
+        self.timeout_at_ms(token, at)
+    }
+
     pub fn timeout_at_ms(&mut self, token: T, mut at: u64) -> TimerResult<Timeout> {
-        // Make relative to start
-        at -= self.start;
+        // Make relative to start. Deadlines before the timer started are
+        // already expired.
+        at = if at > self.start { at - self.start } else { 0 };
         // Calculate tick
         let mut tick = (at + self.tick_ms - 1) / self.tick_ms;
 
-        // Always target at least 1 tick in the future
-        if tick <= self.tick {
-            tick = self.tick + 1;
+        // Deadlines that have already passed target the next tick to be
+        // processed, so they fire on the next call to `tick_to`
+        if tick < self.tick {
+            tick = self.tick;
         }
 
         self.insert(token, tick)
@@ -152,6 +172,12 @@ impl<T> Timer<T> {
         // Update the head slot
         self.wheel[slot] = token;
 
+        // The slot for the current tick may already be partially walked,
+        // restart from its head so the new entry is not skipped
+        if tick == self.tick {
+            self.next = token;
+        }
+
         debug!("inserted timout; slot={}; token={}", slot, token);
 
         // Return the new timeout
@@ -193,6 +219,15 @@ impl<T> Timer<T> {
         self.ms_to_tick(self.now_ms())
     }
 
+    // The current time of the timer's clock, suitable for computing deadlines
+    // passed to `timeout_at`
+    pub fn now_ts(&self) -> Timespec {
+        let ns = precise_time_ns();
+
+        Timespec::new((ns / (NS_PER_MS * MS_PER_SEC)) as i64,
+                      (ns % (NS_PER_MS * MS_PER_SEC)) as i32)
+    }
+
     pub fn tick_to(&mut self, now: u64) -> Option<T> {
         debug!("tick_to; now={}; tick={}", now, self.tick);
 
@@ -242,12 +277,21 @@ impl<T> Timer<T> {
         (self.mask & tick) as uint
     }
 
-    // Convert a ms duration into a number of ticks, rounds up
+    // Convert a ms duration into a number of ticks, rounds down. Times before
+    // the timer started map to tick 0.
     #[inline]
     fn ms_to_tick(&self, ms: u64) -> u64 {
+        if ms < self.start {
+            return 0;
+        }
+
         (ms - self.start) / self.tick_ms
     }
 
+    // The timer is driven by `precise_time_ns`, which reads the monotonic
+    // clock (CLOCK_MONOTONIC on Linux, mach_absolute_time on OS X). Wall clock
+    // time must not be used here: a backwards step (NTP adjustment, manual
+    // change) would stall every pending timeout until the clock caught up.
     #[inline]
     fn now_ms(&self) -> u64 {
         precise_time_ns() / NS_PER_MS
@@ -280,6 +324,187 @@ struct EntryLinks {
     next: Token
 }
 
+/// Timeouts managed by the kernel instead of the timer wheel, see
+/// `EventLoopConfig::use_native_timers`. Each timeout is a kernel timer
+/// (an `EVFILT_TIMER` kevent, or a timerfd on Linux) registered with a
+/// selector of its own. The descriptor of that selector is
+/// polled by the event loop along with the IO handles, and becomes readable
+/// when any of the timeouts expires.
+pub struct NativeTimer<T> {
+    selector: os::Selector,
+    events: os::Events,
+    entries: Slab<NativeEntry<T>>,
+    // Incremented for each timeout, so that a stale `Timeout` does not clear
+    // a newer one reusing the same slot
+    seq: u64,
+}
+
+struct NativeEntry<T> {
+    token: T,
+    seq: u64,
+    // The timerfd backing the timeout on Linux, closing it disarms the timer
+    // and removes it from the selector
+    desc: Option<os::IoDesc>,
+}
+
+impl<T> NativeTimer<T> {
+    /// Returns an `Unsupported` error on platforms without kernel timers.
+    pub fn new(capacity: uint) -> MioResult<NativeTimer<T>> {
+        if !NativeTimer::<T>::is_supported() {
+            return Err(MioError::unsupported());
+        }
+
+        Ok(NativeTimer {
+            selector: try!(os::Selector::new()),
+            events: os::Events::new(),
+            entries: Slab::new(capacity),
+            seq: 0
+        })
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "ios"))]
+    fn is_supported() -> bool {
+        true
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "ios")))]
+    fn is_supported() -> bool {
+        false
+    }
+
+    pub fn count(&self) -> uint {
+        self.entries.count()
+    }
+
+    pub fn timeout(&mut self, token: T, delay: Duration) -> TimerResult<Timeout> {
+        self.seq += 1;
+
+        let seq = self.seq;
+        let idx = try!(
+            self.entries.insert(NativeEntry { token: token, seq: seq, desc: None })
+            .map_err(|_| TimerError::overflow()));
+
+        let delay_ms = max(0, delay.num_milliseconds()) as u64;
+
+        if self.arm(idx, delay_ms).is_err() {
+            self.entries.remove(idx);
+            return Err(TimerError::sys_error());
+        }
+
+        Ok(Timeout {
+            token: idx,
+            tick: seq
+        })
+    }
+
+    pub fn clear(&mut self, timeout: Timeout) -> bool {
+        match self.entries.get(timeout.token) {
+            Some(e) if e.seq == timeout.tick => {}
+            _ => return false
+        }
+
+        // Fails if the timer already expired, the entry is removed either way
+        let _ = self.disarm(timeout.token);
+        self.entries.remove(timeout.token);
+
+        true
+    }
+
+    /// Takes the tokens of the timeouts that expired since the last call.
+    pub fn expired(&mut self) -> MioResult<Vec<T>> {
+        let mut ret = vec![];
+
+        try!(self.selector.select(&mut self.events, 0));
+
+        for i in range(0, self.events.len()) {
+            let idx = self.events.get(i).token();
+
+            let expired = match self.entries.get(idx) {
+                Some(e) => NativeTimer::has_expired(e),
+                None => false
+            };
+
+            if expired {
+                ret.push(self.entries.remove(idx).unwrap().token);
+            }
+        }
+
+        Ok(ret)
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    fn arm(&mut self, idx: Token, delay_ms: u64) -> MioResult<()> {
+        use os::event::{EventFilter, FilterFlag, ONESHOT};
+
+        try!(self.selector.register_raw(idx.as_uint(), idx.as_uint(), EventFilter::EVFILT_TIMER,
+                                        FilterFlag::empty(), delay_ms as i64, ONESHOT));
+
+        // Submit right away, the selector is only polled once timers expire
+        self.selector.submit()
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    fn disarm(&mut self, idx: Token) -> MioResult<()> {
+        use os::event::EventFilter;
+
+        try!(self.selector.deregister_raw(idx.as_uint(), EventFilter::EVFILT_TIMER));
+        self.selector.submit()
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    fn has_expired(_entry: &NativeEntry<T>) -> bool {
+        // The kevent is only reported once the timer expired
+        true
+    }
+
+    #[cfg(target_os = "linux")]
+    fn arm(&mut self, idx: Token, delay_ms: u64) -> MioResult<()> {
+        use os::event::{READABLE, LEVEL};
+
+        let desc = try!(os::timerfd(delay_ms));
+        try!(self.selector.register(&desc, idx.as_uint(), READABLE, LEVEL));
+
+        self.entries[idx].desc = Some(desc);
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    fn disarm(&mut self, _idx: Token) -> MioResult<()> {
+        // Done by closing the timerfd when the entry is removed
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    fn has_expired(entry: &NativeEntry<T>) -> bool {
+        // Reading the expiration count also resets the readiness
+        match entry.desc {
+            Some(ref desc) => os::timerfd_read(desc).map(|cnt| cnt > 0).unwrap_or(false),
+            None => false
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "ios")))]
+    fn arm(&mut self, _idx: Token, _delay_ms: u64) -> MioResult<()> {
+        Err(MioError::unsupported())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "ios")))]
+    fn disarm(&mut self, _idx: Token) -> MioResult<()> {
+        Err(MioError::unsupported())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "ios")))]
+    fn has_expired(_entry: &NativeEntry<T>) -> bool {
+        false
+    }
+}
+
+impl<T> IoHandle for NativeTimer<T> {
+    fn desc(&self) -> &os::IoDesc {
+        self.selector.desc()
+    }
+}
+
 pub type TimerResult<T> = Result<T, TimerError>;
 
 #[deriving(Show)]
@@ -295,11 +520,19 @@ impl TimerError {
             desc: "too many timer entries"
         }
     }
+
+    fn sys_error() -> TimerError {
+        TimerError {
+            kind: TimerSysError,
+            desc: "failed to arm the kernel timer"
+        }
+    }
 }
 
 #[deriving(Show)]
 pub enum TimerErrorKind {
     TimerOverflow,
+    TimerSysError,
 }
 
 #[cfg(test)]
@@ -329,6 +562,33 @@ mod test {
         assert_eq!(t.count(), 0);
     }
 
+    #[test]
+    pub fn test_zero_delay_fires_next_tick() {
+        let mut t = timer();
+        let mut tick;
+
+        t.set_start_ms(1_000);
+
+        tick = t.ms_to_tick(1_150);
+        assert_eq!(None, t.tick_to(tick));
+
+        // Scheduled for "now", between two ticks
+        t.timeout_at_ms("a", 1_150).unwrap();
+
+        tick = t.ms_to_tick(1_199);
+        assert_eq!(None, t.tick_to(tick));
+
+        tick = t.ms_to_tick(1_200);
+        assert_eq!(Some("a"), t.tick_to(tick));
+
+        // Scheduled in the past, exactly on a processed tick
+        t.timeout_at_ms("b", 1_100).unwrap();
+
+        tick = t.ms_to_tick(1_300);
+        assert_eq!(Some("b"), t.tick_to(tick));
+        assert_eq!(0, t.count());
+    }
+
     #[test]
     pub fn test_clearing_timeout() {
         let mut t = timer();
@@ -466,6 +726,59 @@ mod test {
         assert_eq!(0, t.count());
     }
 
+    #[test]
+    pub fn test_timeout_at_deadline() {
+        use time::Timespec;
+
+        let mut t = timer();
+        let mut tick;
+
+        t.timeout_at("a", Timespec::new(0, 110_000_000)).unwrap();
+
+        tick = t.ms_to_tick(100);
+        assert_eq!(None, t.tick_to(tick));
+
+        tick = t.ms_to_tick(200);
+        assert_eq!(Some("a"), t.tick_to(tick));
+        assert_eq!(0, t.count());
+    }
+
+    #[test]
+    pub fn test_timeout_at_deadline_before_start() {
+        use time::Timespec;
+
+        let mut t = timer();
+        t.set_start_ms(1_000);
+
+        t.timeout_at("a", Timespec::new(0, 0)).unwrap();
+
+        let tick = t.ms_to_tick(1_100);
+        assert_eq!(Some("a"), t.tick_to(tick));
+    }
+
+    #[test]
+    pub fn test_time_only_moves_forward() {
+        let mut t = timer();
+        let mut tick;
+
+        t.set_start_ms(1_000);
+        t.timeout_at_ms("a", 1_300).unwrap();
+
+        tick = t.ms_to_tick(1_200);
+        assert_eq!(None, t.tick_to(tick));
+
+        // Times before the start of the timer do not underflow
+        assert_eq!(0, t.ms_to_tick(500));
+
+        // A stale reading does not rewind the timer or fire early
+        tick = t.ms_to_tick(1_100);
+        assert_eq!(None, t.tick_to(tick));
+
+        tick = t.ms_to_tick(1_300);
+        assert_eq!(Some("a"), t.tick_to(tick));
+        assert_eq!(0, t.count());
+    }
+
     const TICK: u64 = 100;
     const SLOTS: uint = 16;
 
@@ -0,0 +1,117 @@
+use buf::RingBuf;
+use util::BoundedQueue;
+
+/// A pool of fixed size `RingBuf`s, so that buffers released by closed
+/// connections are reused by new ones instead of going back to the
+/// allocator. Clones share the same free list and can be moved to other
+/// threads.
+pub struct BufPool {
+    // Capacity of the buffers handed out
+    buf_size: uint,
+    // Buffers available for reuse
+    free: BoundedQueue<RingBuf>
+}
+
+impl BufPool {
+    /// Creates a pool of buffers holding `buf_size` bytes each (rounded up
+    /// to the next power of 2, as with `RingBuf::new`). At most `max_free`
+    /// buffers (also rounded up to a power of 2, and at least 2) are kept
+    /// for reuse, extra ones are freed on `checkin`.
+    pub fn new(buf_size: uint, max_free: uint) -> BufPool {
+        BufPool {
+            buf_size: RingBuf::new(buf_size).capacity(),
+            free: BoundedQueue::with_capacity(max_free)
+        }
+    }
+
+    /// Same as `new`, allocating `count` buffers up front.
+    pub fn preallocated(buf_size: uint, max_free: uint, count: uint) -> BufPool {
+        let pool = BufPool::new(buf_size, max_free);
+
+        for _ in range(0, count) {
+            if pool.free.push(RingBuf::new(pool.buf_size)).is_err() {
+                break;
+            }
+        }
+
+        pool
+    }
+
+    /// Capacity of the buffers handed out by the pool
+    pub fn buf_size(&self) -> uint {
+        self.buf_size
+    }
+
+    /// Takes an empty buffer from the pool, allocating one if none are free.
+    pub fn checkout(&self) -> RingBuf {
+        match self.free.pop() {
+            Some(buf) => buf,
+            None => RingBuf::new(self.buf_size)
+        }
+    }
+
+    /// Returns a buffer to the pool. Any data left in the buffer is
+    /// discarded. Buffers that were not handed out by this pool (their
+    /// capacity differs) and buffers exceeding `max_free` are freed.
+    pub fn checkin(&self, mut buf: RingBuf) {
+        if buf.capacity() != self.buf_size {
+            return;
+        }
+
+        buf.clear();
+
+        // Dropped if the pool is full
+        let _ = self.free.push(buf);
+    }
+}
+
+impl Clone for BufPool {
+    fn clone(&self) -> BufPool {
+        BufPool {
+            buf_size: self.buf_size,
+            free: self.free.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use buf::{Buf, MutBuf};
+    use super::BufPool;
+
+    #[test]
+    pub fn test_checkout_reuses_buffers() {
+        let pool = BufPool::new(100, 4);
+        assert_eq!(pool.buf_size(), 128);
+
+        let mut buf = pool.checkout();
+        assert_eq!(buf.capacity(), 128);
+
+        buf.writer().write(b"hello").unwrap();
+        let ptr = buf.reader().bytes().as_ptr();
+
+        pool.checkin(buf);
+
+        // The returned buffer is handed out again, emptied
+        let mut buf = pool.checkout();
+        assert!(buf.is_empty());
+        assert_eq!(buf.writer().remaining(), 128);
+        assert!(buf.writer().bytes().as_ptr() == ptr);
+    }
+
+    #[test]
+    pub fn test_checkin_frees_extra_buffers() {
+        let pool = BufPool::preallocated(16, 2, 2);
+
+        let bufs = Vec::from_fn(3, |_| pool.checkout());
+
+        for buf in bufs.into_iter() {
+            pool.checkin(buf);
+        }
+
+        // Only two buffers are kept
+        assert!(pool.free.pop().is_some());
+        assert!(pool.free.pop().is_some());
+        assert!(pool.free.pop().is_none());
+    }
+}
@@ -1,5 +1,10 @@
+pub use self::buf_pool::BufPool;
 pub use self::mpmc_bounded_queue::Queue as BoundedQueue;
 pub use self::slab::Slab;
 
+mod buf_pool;
 mod mpmc_bounded_queue;
 mod slab;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
@@ -84,7 +84,7 @@ impl<T: Send> State<T> {
         }
     }
 
-    fn push(&self, value: T) -> bool {
+    fn push(&self, value: T) -> Result<(), T> {
         let mask = self.mask;
         let mut pos = self.enqueue_pos.load(Relaxed);
         loop {
@@ -104,12 +104,12 @@ impl<T: Send> State<T> {
                     pos = enqueue_pos;
                 }
             } else if diff < 0 {
-                return false
+                return Err(value)
             } else {
                 pos = self.enqueue_pos.load(Relaxed);
             }
         }
-        true
+        Ok(())
     }
 
     fn pop(&self) -> Option<T> {
@@ -146,7 +146,8 @@ impl<T: Send> Queue<T> {
         }
     }
 
-    pub fn push(&self, value: T) -> bool {
+    /// Pushes the value onto the queue, handing it back if the queue is full.
+    pub fn push(&self, value: T) -> Result<(), T> {
         self.state.push(value)
     }
 
@@ -179,7 +180,7 @@ mod tests {
             spawn(proc() {
                 let q = q;
                 for i in range(0, nmsgs) {
-                    assert!(q.push(i));
+                    assert!(q.push(i).is_ok());
                 }
                 tx.send(());
             });
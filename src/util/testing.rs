@@ -0,0 +1,169 @@
+//! Helpers for testing protocols built on mio against a real server. Only
+//! built for the crate's own tests, or with the `testing` feature.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, SeqCst};
+use error::{MioResult, MioError};
+use event_loop::{EventLoop, EventLoopSender};
+use handler::Handler;
+use net::{Socket, SockAddr};
+use net::tcp::{TcpAcceptor, TcpSocket};
+
+/// An event loop running on a background thread, serving a TCP acceptor
+/// bound to an ephemeral port on 127.0.0.1. The thread is stopped and joined
+/// by `shutdown`, or when the server is dropped.
+pub struct TestServer {
+    addr: SockAddr,
+    sender: EventLoopSender<()>,
+    stop: Arc<AtomicBool>,
+    done: Receiver<MioResult<()>>,
+    stopped: bool,
+}
+
+impl TestServer {
+    /// Binds the acceptor and starts the event loop thread. `setup` is called
+    /// on that thread with the event loop and the acceptor; it is expected to
+    /// register the acceptor and return the handler to run the loop with.
+    ///
+    /// The handler receives a `()` message through `notify` when the server
+    /// is shut down.
+    pub fn start<H: Handler<Timeout=uint, Message=()> + Send>(setup: proc(&mut EventLoop<uint, ()>, TcpAcceptor):Send -> MioResult<H>) -> MioResult<TestServer> {
+        let addr = try!(SockAddr::parse_mio("127.0.0.1:0"));
+        let acceptor = try!(try!(try!(TcpSocket::v4()).bind(&addr)).listen_default());
+        let addr = try!(acceptor.local_addr());
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+
+        let (sender_tx, sender_rx) = channel();
+        let (done_tx, done_rx) = channel();
+
+        spawn(proc() {
+            done_tx.send(TestServer::run(acceptor, setup, thread_stop, sender_tx));
+        });
+
+        let sender = match sender_rx.recv_opt() {
+            Ok(Ok(sender)) => sender,
+            Ok(Err(e)) => return Err(e),
+            Err(_) => return Err(MioError::event_loop_terminated())
+        };
+
+        debug!("test server started; addr={}", addr);
+
+        Ok(TestServer {
+            addr: addr,
+            sender: sender,
+            stop: stop,
+            done: done_rx,
+            stopped: false
+        })
+    }
+
+    /// The address the server is listening on.
+    pub fn addr(&self) -> &SockAddr {
+        &self.addr
+    }
+
+    /// Stops the event loop and waits for its thread to exit, returning the
+    /// error the loop failed with, if any.
+    pub fn shutdown(mut self) -> MioResult<()> {
+        self.stop_and_join()
+    }
+
+    fn stop_and_join(&mut self) -> MioResult<()> {
+        self.stopped = true;
+        self.stop.store(true, SeqCst);
+
+        // Wake up the event loop so that it sees the flag right away
+        let _ = self.sender.send(());
+
+        match self.done.recv_opt() {
+            Ok(res) => res,
+            Err(_) => Err(MioError::event_loop_terminated())
+        }
+    }
+
+    fn run<H: Handler<Timeout=uint, Message=()> + Send>(acceptor: TcpAcceptor,
+                                        setup: proc(&mut EventLoop<uint, ()>, TcpAcceptor):Send -> MioResult<H>,
+                                        stop: Arc<AtomicBool>,
+                                        sender_tx: Sender<MioResult<EventLoopSender<()>>>) -> MioResult<()> {
+        let mut event_loop = match EventLoop::new() {
+            Ok(event_loop) => event_loop,
+            Err(e) => {
+                sender_tx.send(Err(e.clone()));
+                return Err(e);
+            }
+        };
+
+        let mut handler = match setup(&mut event_loop, acceptor) {
+            Ok(handler) => handler,
+            Err(e) => {
+                sender_tx.send(Err(e.clone()));
+                return Err(e);
+            }
+        };
+
+        sender_tx.send(event_loop.channel());
+
+        while !stop.load(SeqCst) {
+            handler = match event_loop.run_once(handler) {
+                Ok(handler) => handler,
+                Err(e) => return Err(e.error)
+            };
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if !self.stopped {
+            let _ = self.stop_and_join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::net::tcp::TcpStream;
+    use event_loop::EventLoop;
+    use handler::Handler;
+    use io::{IoAcceptor, IoWriter};
+    use io::NonBlock::Ready;
+    use net::tcp::TcpAcceptor;
+    use os::event;
+    use os::token::Token;
+    use super::TestServer;
+
+    struct Greeter {
+        srv: TcpAcceptor
+    }
+
+    impl Handler for Greeter {
+        type Timeout = uint;
+        type Message = ();
+
+        fn readable(&mut self, _event_loop: &mut EventLoop<uint, ()>, _token: Token, _hint: event::ReadHint) {
+            match self.srv.accept().unwrap() {
+                Ready(sock) => { sock.write_slice(b"hello").unwrap(); }
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_server_greets_client() {
+        let server = TestServer::start(proc(event_loop, srv) {
+            try!(event_loop.register(&srv, Token(0)));
+            Ok(Greeter { srv: srv })
+        }).unwrap();
+
+        let addr = server.addr().to_std().unwrap();
+        let mut cli = TcpStream::connect(addr).unwrap();
+
+        assert!(cli.read_to_end().unwrap().as_slice() == b"hello");
+
+        server.shutdown().unwrap();
+    }
+}
@@ -1,5 +1,6 @@
 #![feature(globs)]
 #![feature(phase)]
+#![feature(associated_types)]
 
 extern crate mio;
 
@@ -10,12 +11,20 @@ pub use ports::localhost;
 
 mod test_close_on_drop;
 mod test_echo_server;
+mod test_listen_connect;
 mod test_notify;
 mod test_timer;
+mod test_udp_recv_msg;
 mod test_udp_socket;
 mod test_udp_socket_connectionless;
+mod test_udp_socket_writable;
+mod test_raw_socket;
 mod test_register_deregister;
+mod test_resolver;
+mod test_tcp_socket;
 mod test_unix_echo_server;
+mod test_unix_seqpacket;
+mod test_unix_socket_path;
 
 mod ports {
     use std::sync::atomic::{AtomicUint, SeqCst, INIT_ATOMIC_UINT};
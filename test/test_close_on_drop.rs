@@ -33,7 +33,10 @@ impl TestHandler {
     }
 }
 
-impl Handler<uint, ()> for TestHandler {
+impl Handler for TestHandler {
+    type Timeout = uint;
+    type Message = ();
+
     fn readable(&mut self, event_loop: &mut TestEventLoop, tok: Token, hint: evt::ReadHint) {
         debug!("readable; tok={}; hint={}", tok, hint);
 
@@ -3,7 +3,7 @@ use mio::net::*;
 use mio::net::tcp::*;
 use mio::buf::{ByteBuf, SliceBuf};
 use mio::util::Slab;
-use super::localhost;
+use mio::util::testing::TestServer;
 use mio::event as evt;
 
 type TestEventLoop = EventLoop<uint, ()>;
@@ -213,30 +213,30 @@ impl EchoClient {
     }
 }
 
-struct EchoHandler {
-    server: EchoServer,
-    client: EchoClient,
+struct EchoServerHandler {
+    server: EchoServer
 }
 
-impl EchoHandler {
-    fn new(srv: TcpAcceptor, client: TcpSocket, msgs: Vec<&'static str>) -> EchoHandler {
-        EchoHandler {
+impl EchoServerHandler {
+    fn new(srv: TcpAcceptor) -> EchoServerHandler {
+        EchoServerHandler {
             server: EchoServer {
                 sock: srv,
                 conns: Slab::new_starting_at(Token(2), 128)
-            },
-            client: EchoClient::new(client, CLIENT, msgs)
+            }
         }
     }
 }
 
-impl Handler<uint, ()> for EchoHandler {
+impl Handler for EchoServerHandler {
+    type Timeout = uint;
+    type Message = ();
+
     fn readable(&mut self, event_loop: &mut TestEventLoop, token: Token, hint: evt::ReadHint) {
         assert_eq!(hint, evt::DATAHINT);
 
         match token {
             SERVER => self.server.accept(event_loop).unwrap(),
-            CLIENT => self.client.readable(event_loop).unwrap(),
             i => self.server.conn_readable(event_loop, i).unwrap()
         };
     }
@@ -244,39 +244,62 @@ impl Handler<uint, ()> for EchoHandler {
     fn writable(&mut self, event_loop: &mut TestEventLoop, token: Token) {
         match token {
             SERVER => panic!("received writable for token 0"),
-            CLIENT => self.client.writable(event_loop).unwrap(),
             _ => self.server.conn_writable(event_loop, token).unwrap()
         };
     }
 }
 
-#[test]
-pub fn test_echo_server() {
-    debug!("Starting TEST_ECHO_SERVER");
-    let mut event_loop = EventLoop::new().unwrap();
+struct EchoClientHandler {
+    client: EchoClient
+}
 
-    let addr = SockAddr::parse(localhost().as_slice())
-        .expect("could not parse InetAddr");
+impl EchoClientHandler {
+    fn new(client: TcpSocket, msgs: Vec<&'static str>) -> EchoClientHandler {
+        EchoClientHandler {
+            client: EchoClient::new(client, CLIENT, msgs)
+        }
+    }
+}
 
-    let srv = TcpSocket::v4().unwrap();
+impl Handler for EchoClientHandler {
+    type Timeout = uint;
+    type Message = ();
 
-    info!("setting re-use addr");
-    srv.set_reuseaddr(true).unwrap();
+    fn readable(&mut self, event_loop: &mut TestEventLoop, token: Token, hint: evt::ReadHint) {
+        assert_eq!(hint, evt::DATAHINT);
+        assert_eq!(token, CLIENT);
+
+        self.client.readable(event_loop).unwrap();
+    }
+
+    fn writable(&mut self, event_loop: &mut TestEventLoop, token: Token) {
+        assert_eq!(token, CLIENT);
+
+        self.client.writable(event_loop).unwrap();
+    }
+}
 
-    let srv = srv.bind(&addr).unwrap()
-        .listen(256u).unwrap();
+#[test]
+pub fn test_echo_server() {
+    debug!("Starting TEST_ECHO_SERVER");
 
-    info!("listen for connections");
-    event_loop.register_opt(&srv, SERVER, evt::READABLE, evt::PollOpt::edge()).unwrap();
+    // The server runs its own event loop on a background thread
+    let server = TestServer::start(proc(event_loop, srv) {
+        info!("listen for connections");
+        try!(event_loop.register_opt(&srv, SERVER, evt::READABLE, evt::PollOpt::edge()));
+        Ok(EchoServerHandler::new(srv))
+    }).unwrap();
 
+    let mut event_loop = EventLoop::new().unwrap();
     let sock = TcpSocket::v4().unwrap();
 
     // Connect to the server
     event_loop.register_opt(&sock, CLIENT, evt::WRITABLE, evt::PollOpt::edge()).unwrap();
-    sock.connect(&addr).unwrap();
+    sock.connect(server.addr()).unwrap();
 
     // Start the event loop
-    event_loop.run(EchoHandler::new(srv, sock, vec!["foo", "bar"]))
+    event_loop.run(EchoClientHandler::new(sock, vec!["foo", "bar"]))
         .ok().expect("failed to execute event loop");
 
+    server.shutdown().unwrap();
 }
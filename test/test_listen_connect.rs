@@ -0,0 +1,76 @@
+use mio::*;
+use mio::net::*;
+use mio::net::tcp::*;
+use mio::net::pipe::*;
+use std::io::TempDir;
+use std::io::timer::sleep;
+use std::time::Duration;
+use super::localhost;
+
+fn accept<A: IoAcceptor<S>, S>(srv: &mut A) -> S {
+    loop {
+        match srv.accept().unwrap() {
+            NonBlock::Ready(sock) => return sock,
+            NonBlock::WouldBlock => sleep(Duration::milliseconds(10))
+        }
+    }
+}
+
+// Transport agnostic echo of a single message
+fn round_trip<A: Listen + IoAcceptor<S>, S: Connect + IoReader + IoWriter>(addr: &SockAddr) {
+    let mut srv: A = Listen::listen_on(addr, 8).unwrap();
+    let cli: S = Connect::connect_to(addr).unwrap();
+
+    let conn = accept(&mut srv);
+
+    while cli.write_slice(b"ping").unwrap().would_block() {
+        sleep(Duration::milliseconds(10));
+    }
+
+    let mut buf = [0u8, ..8];
+
+    loop {
+        match conn.read_slice(&mut buf).unwrap() {
+            NonBlock::Ready(cnt) => {
+                assert!(buf.slice_to(cnt) == b"ping");
+                break;
+            }
+            NonBlock::WouldBlock => sleep(Duration::milliseconds(10))
+        }
+    }
+}
+
+#[test]
+pub fn test_listen_connect_tcp() {
+    let addr = SockAddr::parse(localhost().as_slice())
+        .expect("could not parse InetAddr for localhost");
+
+    round_trip::<TcpAcceptor, TcpSocket>(&addr);
+}
+
+#[test]
+pub fn test_listen_connect_unix() {
+    let tmp_dir = TempDir::new("test_listen_connect_unix").unwrap();
+    let addr = SockAddr::from_path(tmp_dir.path().join(Path::new("sock")));
+
+    round_trip::<UnixAcceptor, UnixSocket>(&addr);
+}
+
+#[test]
+pub fn test_listen_connect_wrong_family() {
+    let inet = SockAddr::parse(localhost().as_slice())
+        .expect("could not parse InetAddr for localhost");
+    let unix = SockAddr::from_path(Path::new("/tmp/mio-test-wrong-family"));
+
+    let res: MioResult<UnixAcceptor> = Listen::listen_on(&inet, 8);
+    assert_eq!(res.unwrap_err().kind, MioErrorKind::InvalidInput);
+
+    let res: MioResult<TcpAcceptor> = Listen::listen_on(&unix, 8);
+    assert_eq!(res.unwrap_err().kind, MioErrorKind::InvalidInput);
+
+    let res: MioResult<UnixSocket> = Connect::connect_to(&inet);
+    assert_eq!(res.unwrap_err().kind, MioErrorKind::InvalidInput);
+
+    let res: MioResult<TcpSocket> = Connect::connect_to(&unix);
+    assert_eq!(res.unwrap_err().kind, MioErrorKind::InvalidInput);
+}
@@ -1,3 +1,4 @@
+use std::default::Default;
 use std::io::timer::sleep;
 use std::time::Duration;
 use mio::*;
@@ -22,7 +23,10 @@ impl TestHandler {
     }
 }
 
-impl Handler<uint, String> for TestHandler {
+impl Handler for TestHandler {
+    type Timeout = uint;
+    type Message = String;
+
     fn notify(&mut self, event_loop: &mut TestEventLoop, msg: String) {
         match self.notify {
             0 => {
@@ -57,14 +61,14 @@ pub fn test_notify() {
 
     event_loop.register_opt(&srv, Token(0), evt::ALL, evt::EDGE).unwrap();
 
-    let sender = event_loop.channel();
+    let sender = event_loop.channel().unwrap();
 
     spawn(proc() {
         sleep(Duration::seconds(1));
         sender.send("First".to_string()).unwrap();
     });
 
-    let sender = event_loop.channel();
+    let sender = event_loop.channel().unwrap();
 
     // Start the event loop
     let h = event_loop.run(TestHandler::new(sender))
@@ -72,3 +76,173 @@ pub fn test_notify() {
 
     assert!(h.notify == 2, "actual={}", h.notify);
 }
+
+// A typical message type, other threads describe what the event loop should
+// do on their behalf
+#[deriving(Show, PartialEq)]
+enum Message {
+    Ping(uint),
+    Stop
+}
+
+struct MessageHandler {
+    pings: Vec<uint>
+}
+
+impl Handler for MessageHandler {
+    type Timeout = uint;
+    type Message = Message;
+
+    fn notify(&mut self, event_loop: &mut EventLoop<uint, Message>, msg: Message) {
+        match msg {
+            Message::Ping(n) => {
+                self.pings.push(n);
+
+                if n == 2 {
+                    event_loop.send_notify(Message::Stop).unwrap();
+                }
+            }
+            Message::Stop => event_loop.shutdown()
+        }
+    }
+}
+
+#[test]
+pub fn test_notify_message_enum() {
+    let mut event_loop: EventLoop<uint, Message> = EventLoop::new().unwrap();
+    let sender = event_loop.channel().unwrap();
+
+    event_loop.send_notify(Message::Ping(1)).unwrap();
+
+    spawn(proc() {
+        sender.send(Message::Ping(2)).unwrap();
+    });
+
+    let h = event_loop.run(MessageHandler { pings: vec![] })
+        .ok().expect("failed to execute event loop");
+
+    assert_eq!(h.pings, vec![1, 2]);
+}
+
+#[test]
+pub fn test_send_after_shutdown() {
+    let mut event_loop: EventLoop<uint, Message> = EventLoop::new().unwrap();
+    let sender = event_loop.channel().unwrap();
+
+    assert!(event_loop.is_running());
+    assert!(!sender.is_closed());
+
+    sender.send(Message::Stop).unwrap();
+
+    event_loop.run(MessageHandler { pings: vec![] })
+        .ok().expect("failed to execute event loop");
+
+    assert!(!event_loop.is_running());
+    assert!(sender.is_closed());
+
+    match sender.send(Message::Ping(1)) {
+        Err(NotifyError::Closed(Message::Ping(1))) => {}
+        res => panic!("unexpected send result; res={}", res)
+    }
+}
+
+#[test]
+pub fn test_notify_disabled() {
+    let config = EventLoopConfig {
+        notify_capacity: 0,
+        io_poll_timeout_ms: 10,
+        .. Default::default()
+    };
+
+    let mut event_loop: EventLoop<uint, Message> = EventLoop::configured(config).unwrap();
+
+    assert!(event_loop.channel().unwrap_err().is_unsupported());
+    assert_eq!(event_loop.send_notify(Message::Stop), Err(Message::Stop));
+
+    // Nothing to deliver, the tick only waits for IO
+    event_loop.run_once(MessageHandler { pings: vec![] })
+        .ok().expect("failed to execute event loop");
+}
+
+#[test]
+pub fn test_pending_messages() {
+    let mut event_loop: EventLoop<uint, Message> = EventLoop::new().unwrap();
+    let sender = event_loop.channel().unwrap();
+
+    assert_eq!(sender.pending(), 0);
+
+    sender.send(Message::Ping(1)).unwrap();
+    sender.send(Message::Ping(3)).unwrap();
+
+    assert_eq!(sender.pending(), 2);
+    assert_eq!(event_loop.notify_len(), 2);
+
+    let h = event_loop.run_once(MessageHandler { pings: vec![] })
+        .ok().expect("failed to execute event loop");
+
+    assert_eq!(h.pings, vec![1, 3]);
+    assert_eq!(sender.pending(), 0);
+}
+
+struct DrainHandler {
+    drained: Vec<Message>
+}
+
+impl Handler for DrainHandler {
+    type Timeout = uint;
+    type Message = Message;
+
+    fn notify(&mut self, event_loop: &mut EventLoop<uint, Message>, msg: Message) {
+        assert_eq!(msg, Message::Stop);
+        event_loop.shutdown();
+    }
+
+    fn notify_drained(&mut self, _event_loop: &mut EventLoop<uint, Message>, msgs: Vec<Message>) {
+        self.drained.extend(msgs.into_iter());
+    }
+}
+
+#[test]
+pub fn test_drain_on_shutdown() {
+    // Deliver a single message per tick, so that the ones queued behind
+    // `Stop` are left when the event loop shuts down
+    let config = EventLoopConfig {
+        messages_per_tick: 1,
+        .. Default::default()
+    };
+
+    let mut event_loop: EventLoop<uint, Message> = EventLoop::configured(config).unwrap();
+    let sender = event_loop.channel().unwrap();
+
+    sender.send(Message::Stop).unwrap();
+    sender.send(Message::Ping(1)).unwrap();
+    sender.send(Message::Ping(3)).unwrap();
+
+    let h = event_loop.run(DrainHandler { drained: vec![] })
+        .ok().expect("failed to execute event loop");
+
+    assert_eq!(h.drained, vec![Message::Ping(1), Message::Ping(3)]);
+    assert_eq!(sender.pending(), 0);
+}
+
+#[test]
+pub fn test_drain_forwards_to_notify() {
+    let config = EventLoopConfig {
+        messages_per_tick: 1,
+        .. Default::default()
+    };
+
+    let mut event_loop: EventLoop<uint, Message> = EventLoop::configured(config).unwrap();
+    let sender = event_loop.channel().unwrap();
+
+    sender.send(Message::Stop).unwrap();
+    sender.send(Message::Ping(1)).unwrap();
+    sender.send(Message::Ping(3)).unwrap();
+
+    // Without `notify_drained`, the messages left are handed to `notify`
+    let h = event_loop.run(MessageHandler { pings: vec![] })
+        .ok().expect("failed to execute event loop");
+
+    assert_eq!(h.pings, vec![1, 3]);
+    assert_eq!(sender.pending(), 0);
+}
@@ -0,0 +1,111 @@
+use mio::*;
+use mio::net::*;
+use mio::net::raw::*;
+use mio::buf::{RingBuf, SliceBuf};
+use mio::event as evt;
+
+const IPPROTO_ICMP: i32 = 1;
+const ECHO_REQUEST: u8 = 8;
+const ECHO_REPLY: u8 = 0;
+
+struct PingHandler {
+    sock: RawSocket,
+    reply: Option<Vec<u8>>
+}
+
+impl Handler for PingHandler {
+    type Timeout = uint;
+    type Message = ();
+
+    fn readable(&mut self, event_loop: &mut EventLoop<uint, ()>, _token: Token, _hint: evt::ReadHint) {
+        let mut buf = RingBuf::new(1024);
+
+        match self.sock.recv_from(&mut buf.writer()).unwrap() {
+            NonBlock::Ready(_) => {}
+            NonBlock::WouldBlock => return
+        }
+
+        let bytes = buf.reader().bytes().to_vec();
+
+        // Skip the IP header, its length is in the low nibble of the first
+        // byte, in 32 bit words
+        let ihl = (bytes[0] & 0xf) as uint * 4;
+        let icmp = bytes.slice_from(ihl);
+
+        // The request sent to the loopback interface is looped back as well
+        if icmp[0] == ECHO_REPLY {
+            self.reply = Some(icmp.to_vec());
+            event_loop.shutdown();
+        }
+    }
+}
+
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum = 0u32;
+
+    for chunk in data.chunks(2) {
+        let hi = chunk[0] as u32 << 8;
+        let lo = if chunk.len() > 1 { chunk[1] as u32 } else { 0 };
+        sum += hi | lo;
+    }
+
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+#[test]
+pub fn test_raw_socket_ping() {
+    let mut sock = match RawSocket::new(AddressFamily::Inet, IPPROTO_ICMP) {
+        Ok(sock) => sock,
+        // Raw sockets require privileges
+        Err(e) => {
+            debug!("skipping, could not create raw socket; err={}", e);
+            return;
+        }
+    };
+
+    let mut msg = vec![ECHO_REQUEST, 0, 0, 0, 0x12, 0x34, 0, 1, b'p', b'i', b'n', b'g'];
+    let sum = checksum(msg.as_slice());
+    msg[2] = (sum >> 8) as u8;
+    msg[3] = sum as u8;
+
+    let dst = SockAddr::inet4([127, 0, 0, 1], 0);
+    let mut buf = SliceBuf::wrap(msg.as_slice());
+
+    assert!(sock.send_to(&mut buf, &dst).unwrap().is_ready());
+
+    let mut event_loop = EventLoop::new().unwrap();
+    event_loop.register(&sock, Token(0)).unwrap();
+
+    let handler = event_loop.run(PingHandler { sock: sock, reply: None })
+        .ok().expect("failed to execute event loop");
+
+    let reply = handler.reply.expect("no echo reply");
+
+    // Same identifier, sequence number and payload
+    assert_eq!(reply.slice_from(4), msg.slice_from(4));
+}
+
+#[test]
+pub fn test_raw_socket_unix_family() {
+    let err = RawSocket::new(AddressFamily::Unix, 0).unwrap_err();
+    assert_eq!(err.kind, MioErrorKind::InvalidInput);
+}
+
+#[test]
+pub fn test_raw_socket_hdrincl() {
+    let sock = match RawSocket::new(AddressFamily::Inet, IPPROTO_ICMP) {
+        Ok(sock) => sock,
+        // Raw sockets require privileges
+        Err(_) => return
+    };
+
+    sock.set_hdrincl(true).unwrap();
+    sock.set_hdrincl(false).unwrap();
+
+    let sock = RawSocket::new(AddressFamily::Inet6, 58).unwrap();
+    assert!(sock.set_hdrincl(true).unwrap_err().is_unsupported());
+}
@@ -26,7 +26,10 @@ impl TestHandler {
     }
 }
 
-impl Handler<uint, ()> for TestHandler {
+impl Handler for TestHandler {
+    type Timeout = uint;
+    type Message = ();
+
     fn readable(&mut self, event_loop: &mut TestEventLoop, token: Token, _: ReadHint) {
         match token {
             SERVER => {
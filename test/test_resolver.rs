@@ -0,0 +1,38 @@
+use mio::*;
+use mio::net::*;
+
+struct ResolveHandler {
+    addrs: Vec<SockAddr>
+}
+
+impl Handler for ResolveHandler {
+    type Timeout = uint;
+    type Message = ();
+
+    fn resolved(&mut self, event_loop: &mut EventLoop<uint, ()>, token: Token, addrs: MioResult<Vec<SockAddr>>) {
+        assert_eq!(token, Token(3));
+
+        self.addrs = addrs.unwrap();
+        event_loop.shutdown();
+    }
+}
+
+#[test]
+pub fn test_resolve_localhost() {
+    let mut event_loop = EventLoop::new().unwrap();
+
+    event_loop.resolve("localhost", 8080, Token(3)).unwrap();
+
+    let handler = event_loop.run(ResolveHandler { addrs: vec![] })
+        .ok().expect("failed to execute event loop");
+
+    assert!(!handler.addrs.is_empty());
+
+    for addr in handler.addrs.iter() {
+        match *addr {
+            SockAddr::InetAddr(IPv4Addr(127, 0, 0, 1), 8080) => {}
+            SockAddr::InetAddr(IPv6Addr(0, 0, 0, 0, 0, 0, 0, 1), 8080) => {}
+            ref addr => panic!("unexpected address; addr={}", addr)
+        }
+    }
+}
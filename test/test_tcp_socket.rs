@@ -0,0 +1,521 @@
+use mio::*;
+use mio::net::*;
+use mio::net::tcp::*;
+use std::default::Default;
+use super::localhost;
+use std::io::timer::sleep;
+use std::time::Duration;
+
+fn accept(srv: &mut TcpAcceptor) -> TcpSocket {
+    loop {
+        match srv.accept().unwrap() {
+            NonBlock::Ready(sock) => return sock,
+            NonBlock::WouldBlock => sleep(Duration::milliseconds(10))
+        }
+    }
+}
+
+#[test]
+pub fn test_tcp_abort_resets_connection() {
+    let addr = SockAddr::parse(localhost().as_slice())
+        .expect("could not parse InetAddr for localhost");
+
+    let mut srv = TcpSocket::v4().unwrap().bind(&addr).unwrap().listen(8).unwrap();
+
+    let cli = TcpSocket::v4().unwrap();
+    cli.connect(&addr).unwrap();
+
+    let conn = accept(&mut srv);
+
+    cli.abort().unwrap();
+
+    let mut buf = [0u8, ..8];
+
+    // The peer sees a reset rather than an orderly shutdown
+    loop {
+        match conn.read_slice(&mut buf) {
+            Ok(NonBlock::WouldBlock) => sleep(Duration::milliseconds(10)),
+            Ok(NonBlock::Ready(cnt)) => panic!("unexpected read; cnt={}", cnt),
+            Err(e) => {
+                assert!(e.is_connection_reset(), "expected a reset, got {}", e);
+                break;
+            }
+        }
+    }
+}
+
+#[test]
+pub fn test_tcp_connect_from_bound_addr() {
+    let addr = SockAddr::parse(localhost().as_slice())
+        .expect("could not parse InetAddr for localhost");
+
+    // Any address in 127/8 is local on Linux, elsewhere only 127.0.0.1 is
+    // configured by default
+    let ip = if cfg!(target_os = "linux") { IPv4Addr(127, 0, 0, 2) } else { IPv4Addr(127, 0, 0, 1) };
+
+    let src = match SockAddr::parse(localhost().as_slice()) {
+        Some(SockAddr::InetAddr(_, port)) => SockAddr::InetAddr(ip, port),
+        _ => panic!("could not parse InetAddr for localhost")
+    };
+
+    let mut srv = TcpSocket::v4().unwrap().bind(&addr).unwrap().listen(8).unwrap();
+
+    let cli = TcpSocket::v4().unwrap();
+    cli.bind_in_place(&src).unwrap();
+    cli.connect(&addr).unwrap();
+
+    let _conn = accept(&mut srv);
+
+    match (cli.local_addr().unwrap(), src) {
+        (SockAddr::InetAddr(ip, port), SockAddr::InetAddr(src_ip, src_port)) => {
+            assert!(ip == src_ip);
+            assert_eq!(port, src_port);
+        }
+        _ => panic!("expected an IPv4 address")
+    }
+}
+
+#[test]
+pub fn test_tcp_set_only_v6() {
+    let sock = TcpSocket::v6().unwrap();
+
+    sock.set_only_v6(true).unwrap();
+    assert!(sock.only_v6().unwrap());
+
+    sock.set_only_v6(false).unwrap();
+    assert!(!sock.only_v6().unwrap());
+}
+
+#[test]
+pub fn test_socket_address_family() {
+    let addr = SockAddr::parse(localhost().as_slice())
+        .expect("could not parse InetAddr for localhost");
+
+    assert_eq!(TcpSocket::v4().unwrap().address_family().unwrap(), AddressFamily::Inet);
+    assert_eq!(TcpSocket::v6().unwrap().address_family().unwrap(), AddressFamily::Inet6);
+
+    let srv = TcpSocket::v4().unwrap().bind(&addr).unwrap().listen(8).unwrap();
+    assert_eq!(srv.address_family().unwrap(), AddressFamily::Inet);
+}
+
+#[test]
+pub fn test_tcp_connection_state() {
+    let addr = SockAddr::parse(localhost().as_slice())
+        .expect("could not parse InetAddr for localhost");
+
+    let mut srv = TcpSocket::v4().unwrap().bind(&addr).unwrap().listen(8).unwrap();
+
+    let cli = TcpSocket::v4().unwrap();
+    cli.connect(&addr).unwrap();
+
+    let conn = accept(&mut srv);
+
+    // The handshake may still be completing on the client side
+    loop {
+        match cli.connection_state().unwrap() {
+            ConnState::Established => break,
+            ConnState::Connecting => sleep(Duration::milliseconds(10)),
+            ConnState::Closed => panic!("connection closed")
+        }
+    }
+
+    assert_eq!(conn.connection_state().unwrap(), ConnState::Established);
+}
+
+struct HintHandler {
+    hint: event::ReadHint
+}
+
+impl Handler for HintHandler {
+    type Timeout = uint;
+    type Message = ();
+
+    fn readable(&mut self, event_loop: &mut EventLoop<uint, ()>, _token: Token, hint: event::ReadHint) {
+        self.hint = hint;
+        event_loop.shutdown();
+    }
+}
+
+// kqueue flags urgent data on the read filter, only epoll can be registered
+// for urgent data alone
+#[cfg(target_os = "linux")]
+#[test]
+pub fn test_tcp_oob_interest() {
+    let addr = SockAddr::parse(localhost().as_slice())
+        .expect("could not parse InetAddr for localhost");
+
+    let mut srv = TcpSocket::v4().unwrap().bind(&addr).unwrap().listen(8).unwrap();
+
+    let cli = TcpSocket::v4().unwrap();
+    cli.connect(&addr).unwrap();
+
+    let conn = accept(&mut srv);
+    let mut event_loop = EventLoop::new().unwrap();
+
+    event_loop.register_opt(&conn, Token(0), Interest::oob(), event::LEVEL).unwrap();
+    cli.send_flags(b"!", MSG_OOB).unwrap();
+
+    let handler = event_loop.run(HintHandler { hint: event::ReadHint::empty() })
+        .ok().expect("failed to execute event loop");
+
+    assert!(handler.hint.contains(event::OOBHINT), "actual={}", handler.hint);
+}
+
+struct CountingHandler {
+    readable: uint
+}
+
+impl Handler for CountingHandler {
+    type Timeout = uint;
+    type Message = ();
+
+    fn readable(&mut self, _event_loop: &mut EventLoop<uint, ()>, _token: Token, _hint: event::ReadHint) {
+        self.readable += 1;
+    }
+}
+
+// Counts the readable events, handling them in `ready`
+struct ReadyCountingHandler {
+    readable: uint
+}
+
+impl Handler for ReadyCountingHandler {
+    type Timeout = uint;
+    type Message = ();
+
+    fn ready(&mut self, _event_loop: &mut EventLoop<uint, ()>, _token: Token, events: event::EventSet) -> HandlerAction {
+        if events.is_readable() {
+            self.readable += 1;
+        }
+
+        HandlerAction::Continue
+    }
+}
+
+#[test]
+pub fn test_tcp_recv_lowat() {
+    let addr = SockAddr::parse(localhost().as_slice())
+        .expect("could not parse InetAddr for localhost");
+
+    let mut srv = TcpSocket::v4().unwrap().bind(&addr).unwrap().listen(8).unwrap();
+
+    let cli = TcpSocket::v4().unwrap();
+    cli.connect(&addr).unwrap();
+
+    let conn = accept(&mut srv);
+
+    conn.set_recv_lowat(8).unwrap();
+    assert_eq!(conn.recv_lowat().unwrap(), 8);
+
+    let config = EventLoopConfig {
+        io_poll_timeout_ms: 100,
+        .. Default::default()
+    };
+
+    let mut event_loop = EventLoop::configured(config).unwrap();
+    event_loop.register_opt(&conn, Token(0), event::READABLE, event::EDGE).unwrap();
+
+    // Below the low water mark, the socket is not readable yet
+    cli.write_slice(b"1234").unwrap();
+
+    let handler = event_loop.run_once(CountingHandler { readable: 0 }).ok().unwrap();
+    assert_eq!(handler.readable, 0);
+
+    cli.write_slice(b"5678").unwrap();
+
+    let handler = event_loop.run_once(handler).ok().unwrap();
+    assert_eq!(handler.readable, 1);
+
+    let mut buf = [0u8, ..16];
+    assert_eq!(conn.read_slice(&mut buf).unwrap().unwrap(), 8);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+pub fn test_tcp_defer_accept() {
+    let addr = SockAddr::parse(localhost().as_slice())
+        .expect("could not parse InetAddr for localhost");
+
+    let listener = TcpSocket::v4().unwrap().bind(&addr).unwrap();
+    listener.set_defer_accept(5).unwrap();
+
+    let mut srv = listener.listen(8).unwrap();
+
+    let cli = TcpSocket::v4().unwrap();
+    cli.connect(&addr).unwrap();
+
+    // The handshake completes, but the connection is held back until the
+    // client sends data
+    sleep(Duration::milliseconds(100));
+    assert!(srv.accept().unwrap().would_block());
+
+    while cli.write_slice(b"hello").unwrap().would_block() {
+        sleep(Duration::milliseconds(10));
+    }
+
+    let conn = accept(&mut srv);
+    let mut buf = [0u8, ..8];
+
+    assert_eq!(conn.read_slice(&mut buf).unwrap().unwrap(), 5);
+}
+
+#[test]
+pub fn test_tcp_hup_interest() {
+    let addr = SockAddr::parse(localhost().as_slice())
+        .expect("could not parse InetAddr for localhost");
+
+    let mut srv = TcpSocket::v4().unwrap().bind(&addr).unwrap().listen(8).unwrap();
+
+    let cli = TcpSocket::v4().unwrap();
+    cli.connect(&addr).unwrap();
+
+    let conn = accept(&mut srv);
+    let mut event_loop = EventLoop::new().unwrap();
+
+    // Only the hangup is of interest, no data is ever sent
+    event_loop.register_opt(&conn, Token(0), Interest::hup(), event::LEVEL).unwrap();
+    cli.shutdown(Shutdown::Write).unwrap();
+
+    let handler = event_loop.run(HintHandler { hint: event::ReadHint::empty() })
+        .ok().expect("failed to execute event loop");
+
+    assert!(handler.hint.contains(event::HUPHINT), "actual={}", handler.hint);
+}
+
+// Other platforms do not balance connections across the sockets
+#[cfg(target_os = "linux")]
+#[test]
+pub fn test_tcp_bind_reuseport() {
+    const CONNS: uint = 64;
+
+    let addr = SockAddr::parse(localhost().as_slice())
+        .expect("could not parse InetAddr for localhost");
+
+    let mut srvs = Vec::from_fn(4, |_| TcpAcceptor::bind_reuseport(&addr, 64).unwrap());
+
+    let clis = Vec::from_fn(CONNS, |_| {
+        let cli = TcpSocket::v4().unwrap();
+        cli.connect(&addr).unwrap();
+        cli
+    });
+
+    let mut accepted = Vec::from_elem(srvs.len(), 0u);
+    let mut total = 0;
+
+    while total < clis.len() {
+        for (srv, cnt) in srvs.iter_mut().zip(accepted.iter_mut()) {
+            while srv.accept().unwrap().is_ready() {
+                *cnt += 1;
+                total += 1;
+            }
+        }
+
+        sleep(Duration::milliseconds(10));
+    }
+
+    // The kernel hashes connections across the sockets, so with few
+    // connections a socket may legitimately get none
+    assert_eq!(total, CONNS);
+    assert!(accepted.iter().filter(|&&cnt| cnt > 0).count() > 1, "accepted={}", accepted);
+}
+
+#[test]
+pub fn test_tcp_bind_retry() {
+    let addr = SockAddr::parse(localhost().as_slice())
+        .expect("could not parse InetAddr for localhost");
+
+    let prev = TcpAcceptor::new(&addr, 8).unwrap();
+
+    assert!(TcpAcceptor::bind_retry(&addr, 8, 2, 10).unwrap_err().is_addr_in_use());
+
+    // The previous owner releases the address while retrying
+    spawn(proc() {
+        sleep(Duration::milliseconds(50));
+        drop(prev);
+    });
+
+    TcpAcceptor::bind_retry(&addr, 8, 50, 10).unwrap();
+}
+
+struct ConnectHandler {
+    sock: Option<MioResult<TcpSocket>>
+}
+
+impl Handler for ConnectHandler {
+    type Timeout = uint;
+    type Message = ();
+
+    fn connected(&mut self, event_loop: &mut EventLoop<uint, ()>, token: Token, sock: MioResult<TcpSocket>) {
+        assert_eq!(token, Token(1));
+        self.sock = Some(sock);
+        event_loop.shutdown();
+    }
+}
+
+fn localhost_port() -> Port {
+    match SockAddr::parse(localhost().as_slice()) {
+        Some(SockAddr::InetAddr(_, port)) => port,
+        _ => panic!("could not parse InetAddr for localhost")
+    }
+}
+
+#[test]
+pub fn test_tcp_connect_host() {
+    let port = localhost_port();
+    let addr = SockAddr::inet4([127, 0, 0, 1], port);
+
+    // Only listening on IPv4, "localhost" may resolve to ::1 first
+    let mut srv = TcpSocket::v4().unwrap().bind(&addr).unwrap().listen(8).unwrap();
+
+    let mut event_loop = EventLoop::new().unwrap();
+    event_loop.connect_host("localhost", port, Token(1)).unwrap();
+
+    let handler = event_loop.run(ConnectHandler { sock: None })
+        .ok().expect("failed to execute event loop");
+
+    let cli = handler.sock.expect("not notified").unwrap();
+    let conn = accept(&mut srv);
+
+    cli.write_slice(b"hello").unwrap();
+
+    let mut buf = [0u8, ..8];
+
+    loop {
+        match conn.read_slice(&mut buf).unwrap() {
+            NonBlock::Ready(cnt) => {
+                assert_eq!(buf.slice_to(cnt), b"hello");
+                break;
+            }
+            NonBlock::WouldBlock => sleep(Duration::milliseconds(10))
+        }
+    }
+}
+
+#[test]
+pub fn test_tcp_connect_host_refused() {
+    // Nothing is listening on the port
+    let port = localhost_port();
+
+    let mut event_loop = EventLoop::new().unwrap();
+
+    match event_loop.connect_host("localhost", port, Token(1)) {
+        // All addresses failed right away
+        Err(_) => return,
+        Ok(()) => {}
+    }
+
+    let handler = event_loop.run(ConnectHandler { sock: None })
+        .ok().expect("failed to execute event loop");
+
+    assert!(handler.sock.expect("not notified").is_err());
+}
+
+#[test]
+pub fn test_tcp_acceptor_local_addr() {
+    let addr = SockAddr::inet4([127, 0, 0, 1], 0);
+    let mut srv = TcpAcceptor::new(&addr, 8).unwrap();
+
+    // The kernel picked the port
+    let port = match srv.local_addr().unwrap() {
+        SockAddr::InetAddr(ip, port) => {
+            assert!(ip == IPv4Addr(127, 0, 0, 1));
+            port
+        }
+        _ => panic!("expected an IPv4 address")
+    };
+
+    assert!(port != 0);
+
+    let cli = TcpSocket::v4().unwrap();
+    cli.connect(&SockAddr::inet4([127, 0, 0, 1], port)).unwrap();
+
+    accept(&mut srv);
+}
+
+#[test]
+pub fn test_tcp_pause_accept() {
+    let addr = SockAddr::parse(localhost().as_slice())
+        .expect("could not parse InetAddr for localhost");
+
+    let mut srv = TcpAcceptor::new(&addr, 8).unwrap();
+
+    let config = EventLoopConfig {
+        io_poll_timeout_ms: 100,
+        .. Default::default()
+    };
+
+    let mut event_loop = EventLoop::configured(config).unwrap();
+    event_loop.register_opt(&srv, Token(0), event::READABLE, event::EDGE).unwrap();
+    event_loop.pause_accept(&srv, Token(0)).unwrap();
+    assert_eq!(event_loop.interest(Token(0)), Some(event::Interest::empty()));
+
+    let cli = TcpSocket::v4().unwrap();
+    cli.connect(&addr).unwrap();
+
+    // The connection waits in the backlog
+    let handler = event_loop.run_once(ReadyCountingHandler { readable: 0 }).ok().unwrap();
+    assert_eq!(handler.readable, 0);
+
+    // Resuming reports the pending connection, which arrived while paused
+    event_loop.resume_accept(&srv, Token(0)).unwrap();
+    assert_eq!(event_loop.interest(Token(0)), Some(event::READABLE));
+
+    let handler = event_loop.run_once(handler).ok().unwrap();
+    assert!(handler.readable > 0);
+
+    assert!(srv.accept().unwrap().is_ready());
+}
+
+#[test]
+pub fn test_tcp_accept_from() {
+    let addr = SockAddr::parse(localhost().as_slice())
+        .expect("could not parse InetAddr for localhost");
+
+    let mut srv = TcpAcceptor::new(&addr, 8).unwrap();
+
+    let cli = TcpSocket::v4().unwrap();
+    cli.connect(&addr).unwrap();
+
+    loop {
+        match srv.accept_from().unwrap() {
+            NonBlock::Ready((_, peer)) => {
+                assert_eq!(peer.to_std(), cli.local_addr().unwrap().to_std());
+                break;
+            }
+            NonBlock::WouldBlock => sleep(Duration::milliseconds(10))
+        }
+    }
+}
+
+#[test]
+pub fn test_tcp_v6_addresses() {
+    let loopback = [0, 0, 0, 0, 0, 0, 0, 1];
+
+    let mut srv = match TcpAcceptor::new(&SockAddr::inet6(loopback, 0), 8) {
+        Ok(srv) => srv,
+        // IPv6 is not configured on the host
+        Err(_) => return
+    };
+
+    let port = match srv.local_addr().unwrap() {
+        SockAddr::InetAddr(ip, port) => {
+            assert!(ip == IPv6Addr(0, 0, 0, 0, 0, 0, 0, 1));
+            port
+        }
+        _ => panic!("expected an IPv6 address")
+    };
+
+    let cli = TcpSocket::v6().unwrap();
+    cli.connect(&SockAddr::inet6(loopback, port)).unwrap();
+
+    loop {
+        match srv.accept_from().unwrap() {
+            NonBlock::Ready((_, SockAddr::InetAddr(ip, _))) => {
+                assert!(ip == IPv6Addr(0, 0, 0, 0, 0, 0, 0, 1));
+                break;
+            }
+            NonBlock::Ready(_) => panic!("expected an IPv6 address"),
+            NonBlock::WouldBlock => sleep(Duration::milliseconds(10))
+        }
+    }
+}
@@ -35,7 +35,10 @@ impl TestHandler {
     }
 }
 
-impl Handler<TcpSocket, ()> for TestHandler {
+impl Handler for TestHandler {
+    type Timeout = TcpSocket;
+    type Message = ();
+
     fn readable(&mut self, event_loop: &mut TestEventLoop, tok: Token, hint: evt::ReadHint) {
         match tok {
             SERVER => {
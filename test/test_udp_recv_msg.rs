@@ -0,0 +1,220 @@
+use mio::*;
+use mio::net::*;
+use mio::net::udp::*;
+use mio::buf::SliceBuf;
+use super::localhost;
+use std::io::timer::sleep;
+use std::time::Duration;
+
+// Receives a datagram, retrying while it is still in flight
+fn recv_msg(sock: &mut UdpSocket, buf: &mut [u8]) -> (uint, SockAddr, RecvFlags) {
+    loop {
+        match sock.recv_msg(buf).unwrap() {
+            NonBlock::Ready(res) => return res,
+            NonBlock::WouldBlock => sleep(Duration::milliseconds(10))
+        }
+    }
+}
+
+fn recv_msg_info(sock: &mut UdpSocket, buf: &mut [u8]) -> RecvInfo {
+    loop {
+        match sock.recv_msg_info(buf).unwrap() {
+            NonBlock::Ready(info) => return info,
+            NonBlock::WouldBlock => sleep(Duration::milliseconds(10))
+        }
+    }
+}
+
+#[test]
+pub fn test_udp_recv_msg_truncated() {
+    let mut send_sock = UdpSocket::v4().unwrap();
+    let mut recv_sock = UdpSocket::v4().unwrap();
+    let addr = SockAddr::parse(localhost().as_slice())
+        .expect("could not parse InetAddr for localhost");
+
+    recv_sock.bind(&addr).unwrap();
+
+    send_sock.send_to(&mut SliceBuf::wrap(b"hello"), &addr).unwrap();
+    send_sock.send_to(&mut SliceBuf::wrap(b"hello world"), &addr).unwrap();
+
+    let mut buf = [0u8, ..8];
+
+    let (cnt, src, flags) = recv_msg(&mut recv_sock, &mut buf);
+    assert_eq!(cnt, 5);
+    assert!(!flags.truncated());
+    assert!(buf.slice_to(cnt) == b"hello");
+
+    match src {
+        SockAddr::InetAddr(ip, _) => assert!(ip == IPv4Addr(127, 0, 0, 1)),
+        _ => panic!("expected an IPv4 address")
+    }
+
+    let (cnt, _, flags) = recv_msg(&mut recv_sock, &mut buf);
+    assert_eq!(cnt, 8);
+    assert!(flags.truncated());
+    assert!(buf.slice_to(cnt) == b"hello wo");
+}
+
+#[test]
+pub fn test_udp_recv_msg_info() {
+    let mut send_sock = UdpSocket::v4().unwrap();
+    let mut recv_sock = UdpSocket::v4().unwrap();
+    let addr = SockAddr::parse(localhost().as_slice())
+        .expect("could not parse InetAddr for localhost");
+
+    recv_sock.bind(&addr).unwrap();
+    recv_sock.set_pktinfo(true).unwrap();
+
+    send_sock.send_msg(b"ping", &addr, Some(&IPv4Addr(127, 0, 0, 1))).unwrap();
+
+    let mut buf = [0u8, ..8];
+
+    let info = recv_msg_info(&mut recv_sock, &mut buf);
+
+    assert_eq!(info.len, 4);
+    assert!(buf.slice_to(info.len) == b"ping");
+    assert!(info.dst == Some(IPv4Addr(127, 0, 0, 1)));
+    assert!(info.ifindex.is_some());
+}
+
+#[test]
+pub fn test_udp_recv_peek() {
+    let mut send_sock = UdpSocket::v4().unwrap();
+    let recv_sock = UdpSocket::v4().unwrap();
+    let addr = SockAddr::parse(localhost().as_slice())
+        .expect("could not parse InetAddr for localhost");
+
+    recv_sock.bind(&addr).unwrap();
+    send_sock.send_to(&mut SliceBuf::wrap(b"hello"), &addr).unwrap();
+
+    let mut buf = [0u8, ..8];
+
+    // Peeking leaves the datagram in the receive queue
+    while recv_sock.recv_flags(&mut buf, MSG_PEEK).unwrap().would_block() {
+        sleep(Duration::milliseconds(10));
+    }
+
+    assert!(buf.slice_to(5) == b"hello");
+
+    let cnt = recv_sock.recv_flags(&mut buf, MSG_DONTWAIT).unwrap().unwrap();
+    assert!(buf.slice_to(cnt) == b"hello");
+
+    assert!(recv_sock.recv_flags(&mut buf, MSG_DONTWAIT).unwrap().would_block());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+pub fn test_udp_send_recv_batch() {
+    let mut send_sock = UdpSocket::v4().unwrap();
+    let mut recv_sock = UdpSocket::v4().unwrap();
+    let local = localhost();
+    let addr = || SockAddr::parse(local.as_slice())
+        .expect("could not parse InetAddr for localhost");
+
+    recv_sock.bind(&addr()).unwrap();
+
+    let msgs = [(addr(), b"one"), (addr(), b"two"), (addr(), b"three")];
+    assert_eq!(send_sock.send_batch(&msgs).unwrap().unwrap(), 3);
+
+    let mut a = [0u8, ..8];
+    let mut b = [0u8, ..8];
+    let mut c = [0u8, ..8];
+    let mut out = Vec::new();
+
+    {
+        let mut bufs = [a.as_mut_slice(), b.as_mut_slice(), c.as_mut_slice()];
+        let mut cnt = 0;
+
+        // All three datagrams are normally received at once, but they may
+        // still be in flight
+        while cnt < 3 {
+            match recv_sock.recv_batch(bufs.slice_from_mut(cnt), &mut out).unwrap() {
+                NonBlock::Ready(n) => cnt += n,
+                NonBlock::WouldBlock => sleep(Duration::milliseconds(10))
+            }
+        }
+    }
+
+    let lens: Vec<uint> = out.iter().map(|&(len, _, _)| len).collect();
+    assert!(lens == vec![3, 3, 5]);
+    assert!(out.iter().all(|&(_, _, flags)| !flags.truncated()));
+
+    assert!(a.slice_to(3) == b"one");
+    assert!(b.slice_to(3) == b"two");
+    assert!(c.slice_to(5) == b"three");
+}
+
+#[test]
+pub fn test_udp_recv_timestamp() {
+    let mut send_sock = UdpSocket::v4().unwrap();
+    let mut recv_sock = UdpSocket::v4().unwrap();
+    let addr = SockAddr::parse(localhost().as_slice())
+        .expect("could not parse InetAddr for localhost");
+
+    recv_sock.bind(&addr).unwrap();
+
+    send_sock.send_to(&mut SliceBuf::wrap(b"before"), &addr).unwrap();
+    let info = recv_msg_info(&mut recv_sock, &mut [0u8, ..8]);
+    assert!(info.timestamp.is_none());
+
+    recv_sock.set_timestamping(true).unwrap();
+
+    send_sock.send_to(&mut SliceBuf::wrap(b"after"), &addr).unwrap();
+    let info = recv_msg_info(&mut recv_sock, &mut [0u8, ..8]);
+
+    match info.timestamp {
+        Some(ts) => assert!(ts.sec > 0),
+        None => panic!("expected a receive timestamp")
+    }
+}
+
+#[test]
+pub fn test_udp_disconnect() {
+    let mut sock = UdpSocket::v4().unwrap();
+    let peer = UdpSocket::v4().unwrap();
+    let mut other = UdpSocket::v4().unwrap();
+    let addr = SockAddr::parse(localhost().as_slice()).unwrap();
+    let peer_addr = SockAddr::parse(localhost().as_slice()).unwrap();
+    let other_addr = SockAddr::parse(localhost().as_slice()).unwrap();
+
+    sock.bind(&addr).unwrap();
+    peer.bind(&peer_addr).unwrap();
+    other.bind(&other_addr).unwrap();
+
+    sock.connect(&peer_addr).unwrap();
+    sock.disconnect().unwrap();
+
+    // The local address is kept and datagrams from any peer are accepted
+    assert!(sock.local_addr().unwrap().to_std() == addr.to_std());
+
+    other.send_to(&mut SliceBuf::wrap(b"hello"), &addr).unwrap();
+
+    let mut buf = [0u8, ..8];
+
+    let (cnt, src, _) = recv_msg(&mut sock, &mut buf);
+    assert_eq!(cnt, 5);
+    assert!(src.to_std() == other_addr.to_std());
+
+    // Sending requires a destination again
+    sock.send_to(&mut SliceBuf::wrap(b"world"), &other_addr).unwrap();
+
+    let (cnt, src, _) = recv_msg(&mut other, &mut buf);
+    assert_eq!(cnt, 5);
+    assert!(buf.slice_to(cnt) == b"world");
+    assert!(src.to_std() == addr.to_std());
+}
+
+#[test]
+pub fn test_udp_send_to_message_too_big() {
+    let mut sock = UdpSocket::v4().unwrap();
+    let addr = SockAddr::parse(localhost().as_slice()).unwrap();
+
+    // Larger than the maximum IPv4 datagram
+    let payload = Vec::from_elem(70_000, 0u8);
+    let mut buf = SliceBuf::wrap(payload.as_slice());
+
+    let err = sock.send_to(&mut buf, &addr).unwrap_err();
+
+    assert!(err.is_message_too_big(), "unexpected error; err={}", err);
+    assert_eq!(buf.remaining(), 70_000);
+}
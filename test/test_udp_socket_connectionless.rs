@@ -33,7 +33,10 @@ impl UdpHandler {
     }
 }
 
-impl Handler<uint, ()> for UdpHandler {
+impl Handler for UdpHandler {
+    type Timeout = uint;
+    type Message = ();
+
     fn readable(&mut self, event_loop: &mut TestEventLoop, token: Token, _: evt::ReadHint) {
         match token {
             LISTENER => {
@@ -0,0 +1,88 @@
+use mio::*;
+use mio::net::*;
+use mio::net::udp::*;
+use super::localhost;
+use mio::event as evt;
+
+type TestEventLoop = EventLoop<uint, ()>;
+
+const SENDER: Token = Token(0);
+
+// Upper bound on datagrams sent per writable event, the loopback interface
+// may drain the send buffer fast enough that a send never blocks
+const MAX_BURST: uint = 4096;
+
+struct UdpWritableHandler {
+    sock: UdpSocket,
+    payload: Vec<u8>,
+    writable: uint,
+    sent: uint,
+    blocked: bool
+}
+
+impl Handler for UdpWritableHandler {
+    type Timeout = uint;
+    type Message = ();
+
+    fn writable(&mut self, event_loop: &mut TestEventLoop, token: Token) {
+        assert_eq!(token, SENDER);
+
+        self.writable += 1;
+
+        if self.writable == 1 {
+            // Fill the send buffer until the socket reports that it would
+            // block, readiness must be signalled again once it drains
+            for _ in range(0, MAX_BURST) {
+                match self.sock.write_slice(self.payload.as_slice()) {
+                    Ok(NonBlock::Ready(_)) => self.sent += 1,
+                    Ok(NonBlock::WouldBlock) => {
+                        self.blocked = true;
+                        break;
+                    }
+                    Err(e) => panic!("send failed; err={}", e)
+                }
+            }
+
+            assert!(self.blocked, "send buffer never filled up");
+            return;
+        }
+
+        event_loop.shutdown();
+    }
+}
+
+// Linux frees each datagram sent over loopback as soon as the receiver
+// queues or drops it, so the send buffer never fills up there
+#[cfg(not(target_os = "linux"))]
+#[test]
+pub fn test_udp_socket_writable() {
+    let mut event_loop = EventLoop::new().unwrap();
+
+    let sock = UdpSocket::v4().unwrap();
+    let recv_sock = UdpSocket::v4().unwrap();
+    let addr = SockAddr::parse(localhost().as_slice())
+        .expect("could not parse InetAddr for localhost");
+
+    // The receiver is never read from, once its buffer is full datagrams are
+    // dropped after leaving the send buffer
+    recv_sock.bind(&addr).unwrap();
+    sock.connect(&addr).unwrap();
+
+    // Edge triggered, so the second writable event is only delivered once
+    // the full send buffer drains
+    event_loop.register_opt(&sock, SENDER, evt::WRITABLE, evt::EDGE).unwrap();
+
+    let handler = UdpWritableHandler {
+        sock: sock,
+        payload: Vec::from_elem(8192, 0u8),
+        writable: 0,
+        sent: 0,
+        blocked: false
+    };
+
+    let handler = event_loop.run(handler).ok().expect("failed to execute event loop");
+
+    assert!(handler.sent > 0);
+    assert!(handler.blocked);
+    assert_eq!(handler.writable, 2);
+}
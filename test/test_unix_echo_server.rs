@@ -230,7 +230,10 @@ impl EchoHandler {
     }
 }
 
-impl Handler<uint, ()> for EchoHandler {
+impl Handler for EchoHandler {
+    type Timeout = uint;
+    type Message = ();
+
     fn readable(&mut self, event_loop: &mut TestEventLoop, token: Token, hint: evt::ReadHint) {
         assert_eq!(hint, evt::DATAHINT);
 
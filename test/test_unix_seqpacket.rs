@@ -0,0 +1,34 @@
+use mio::*;
+use mio::net::*;
+use mio::net::pipe::*;
+use std::io::TempDir;
+
+#[test]
+pub fn test_seqpacket_preserves_message_boundaries() {
+    let tmp_dir = TempDir::new("test_unix_seqpacket").unwrap();
+    let addr = SockAddr::from_path(tmp_dir.path().join(Path::new("sock")));
+
+    let mut srv = UnixSocket::seqpacket().unwrap()
+        .bind(&addr).unwrap()
+        .listen(8).unwrap();
+
+    let cli = UnixSocket::seqpacket().unwrap();
+    cli.connect(&addr).unwrap();
+
+    // Connecting a Unix socket completes right away
+    let sock = srv.accept().unwrap().unwrap();
+
+    cli.write_slice(b"one").unwrap().unwrap();
+    cli.write_slice(b"three").unwrap().unwrap();
+
+    let mut buf = [0u8, ..64];
+
+    // Both messages are queued, still each read returns a single one
+    assert_eq!(sock.read_slice(&mut buf).unwrap().unwrap(), 3);
+    assert!(buf.slice_to(3) == b"one");
+
+    assert_eq!(sock.read_slice(&mut buf).unwrap().unwrap(), 5);
+    assert!(buf.slice_to(5) == b"three");
+
+    assert!(sock.read_slice(&mut buf).unwrap().would_block());
+}
@@ -0,0 +1,138 @@
+use mio::{IoAcceptor, IoReader, IoWriter};
+use mio::net::*;
+use mio::net::pipe::*;
+use std::io::{File, TempDir};
+
+#[test]
+pub fn test_bind_unlink_stale_socket() {
+    let tmp_dir = TempDir::new("test_bind_unlink").unwrap();
+    let addr = SockAddr::from_path(tmp_dir.path().join(Path::new("sock")));
+
+    // A listener that goes away without removing its socket file
+    let mut srv = UnixSocket::stream().unwrap().bind(&addr).unwrap();
+    srv.take_path();
+    drop(srv);
+
+    assert!(UnixSocket::stream().unwrap().bind(&addr).unwrap_err().is_addr_in_use());
+
+    UnixSocket::stream().unwrap().bind_unlink(&addr).unwrap();
+}
+
+#[test]
+pub fn test_bind_unlink_live_socket() {
+    let tmp_dir = TempDir::new("test_bind_unlink").unwrap();
+    let addr = SockAddr::from_path(tmp_dir.path().join(Path::new("sock")));
+
+    let _srv = UnixSocket::stream().unwrap().bind(&addr).unwrap().listen(8).unwrap();
+
+    // The socket is in use, it must be left alone
+    assert!(UnixSocket::stream().unwrap().bind_unlink(&addr).unwrap_err().is_addr_in_use());
+}
+
+#[test]
+pub fn test_bind_unlink_regular_file() {
+    let tmp_dir = TempDir::new("test_bind_unlink").unwrap();
+    let path = tmp_dir.path().join(Path::new("sock"));
+
+    File::create(&path).unwrap();
+
+    let addr = SockAddr::from_path(path.clone());
+    assert!(UnixSocket::stream().unwrap().bind_unlink(&addr).is_err());
+    assert!(path.exists());
+}
+
+#[test]
+pub fn test_socket_file_removed_on_drop() {
+    let tmp_dir = TempDir::new("test_unix_drop").unwrap();
+    let path = tmp_dir.path().join(Path::new("sock"));
+    let addr = SockAddr::from_path(path.clone());
+
+    let srv = UnixSocket::stream().unwrap().bind(&addr).unwrap().listen(8).unwrap();
+    assert!(path.exists());
+
+    drop(srv);
+    assert!(!path.exists());
+}
+
+#[test]
+pub fn test_peer_cred() {
+    let tmp_dir = TempDir::new("test_peer_cred").unwrap();
+    let addr = SockAddr::from_path(tmp_dir.path().join(Path::new("sock")));
+
+    let mut srv = UnixSocket::stream().unwrap().bind(&addr).unwrap().listen(8).unwrap();
+
+    let cli = UnixSocket::stream().unwrap();
+    cli.connect(&addr).unwrap();
+
+    let conn = srv.accept().unwrap().unwrap();
+
+    // Both ends belong to this process
+    let cred = conn.peer_cred().unwrap();
+    assert_eq!(cred, cli.peer_cred().unwrap());
+
+    if cfg!(target_os = "linux") {
+        assert!(cred.pid.is_some());
+    }
+}
+
+#[test]
+pub fn test_bind_in_place() {
+    let tmp_dir = TempDir::new("test_bind_in_place").unwrap();
+    let path = tmp_dir.path().join(Path::new("sock"));
+    let addr = SockAddr::from_path(path.clone());
+
+    let sock = UnixSocket::stream().unwrap();
+    sock.bind_in_place(&addr).unwrap();
+    assert!(path.exists());
+
+    let mut srv = sock.listen(8).unwrap();
+
+    let cli = UnixSocket::stream().unwrap();
+    cli.connect(&addr).unwrap();
+
+    srv.accept().unwrap().unwrap();
+
+    // The socket file is left for the caller to remove
+    drop(srv);
+    assert!(path.exists());
+}
+
+#[test]
+pub fn test_shutdown_write() {
+    let tmp_dir = TempDir::new("test_shutdown_write").unwrap();
+    let addr = SockAddr::from_path(tmp_dir.path().join(Path::new("sock")));
+
+    let mut srv = UnixSocket::stream().unwrap().bind(&addr).unwrap().listen(8).unwrap();
+
+    let cli = UnixSocket::stream().unwrap();
+    cli.connect(&addr).unwrap();
+
+    let conn = srv.accept().unwrap().unwrap();
+
+    cli.write_slice(b"ping").unwrap();
+    cli.shutdown(Shutdown::Write).unwrap();
+
+    // The request is delimited by end of file
+    let mut buf = [0u8, ..8];
+    assert_eq!(conn.read_slice(&mut buf).unwrap().unwrap(), 4);
+    assert!(buf.slice_to(4) == b"ping");
+    assert!(conn.read_slice(&mut buf).unwrap_err().is_eof());
+
+    // The response can still be read
+    conn.write_slice(b"pong").unwrap();
+    assert_eq!(cli.read_slice(&mut buf).unwrap().unwrap(), 4);
+    assert!(buf.slice_to(4) == b"pong");
+}
+
+#[test]
+pub fn test_unix_acceptor_local_addr() {
+    let tmp_dir = TempDir::new("test_unix_acceptor_local_addr").unwrap();
+    let path = tmp_dir.path().join(Path::new("sock"));
+
+    let srv = UnixAcceptor::new(&SockAddr::from_path(path.clone()), 8).unwrap();
+
+    match srv.local_addr().unwrap() {
+        SockAddr::UnixAddr(p) => assert_eq!(p, path),
+        _ => panic!("expected a Unix address")
+    }
+}